@@ -0,0 +1,15 @@
+use seed::{prelude::*, *};
+use shared::{
+    i18n::Language,
+    validation::{errors_for, Field, FieldError},
+};
+
+/// Errors of a form field, shown under its input
+pub fn view<Ms: 'static>(errors: &[FieldError], field: Field, language: Language) -> Node<Ms> {
+    ul![
+        C!["field-errors"],
+        errors_for(errors, field)
+            .iter()
+            .map(|error| li![error.message.translate(language)])
+    ]
+}
@@ -10,21 +10,31 @@ use crate::{
 };
 use enum_map::Enum;
 use heck::SnakeCase;
-use shared::models::user::LoggedUser;
+use shared::{
+    i18n::Language,
+    models::{policy::PasswordPolicy, role::Permission, user::LoggedUser},
+};
 
 extern crate enum_map;
 
 // use shared::User;
+mod field_errors;
 mod pages;
+mod policy_checklist;
+mod power_bar;
 // pub mod route;
 mod router;
 mod theme;
 mod top_bar;
 
 const ADMIN: &str = "admin";
+const CHAT: &str = "chat";
 const LOGIN: &str = "login";
+const LOGINS: &str = "logins";
 const REGISTER: &str = "register";
 const DASHBOARD: &str = "dashboard";
+/// Local storage key of the language chosen by the user
+const LANGUAGE_STORAGE_KEY: &str = "tiny_avocado_tree_language";
 // ------ ------
 //     Init
 // ------ ------
@@ -33,20 +43,34 @@ fn init(url: Url, orders: &mut impl Orders<Msg>) -> Model {
     orders
         .subscribe(Msg::UrlChanged)
         .subscribe(Msg::UrlRequested)
-        .subscribe(Msg::UserLogged);
+        .subscribe(Msg::UserLogged)
+        .perform_cmd(async {
+            let response = fetch("/api/policy").await.expect("HTTP request failed");
+            if response.status().is_ok() {
+                response.json().await.ok().map(Msg::PolicyFetched)
+            } else {
+                None
+            }
+        });
     let mut router: Router<Routes> = Router::new();
     router.set_base_url(url.to_base_url());
     router
         .add_route(Routes::Home, "home".to_string())
         .add_route(Routes::Register, "register".to_string())
         .add_route(Routes::Login, "login".to_string())
-        .add_route(Routes::Dashboard, "dashboard".to_string());
+        .add_route(Routes::Dashboard, "dashboard".to_string())
+        .add_route(Routes::ChangePassword, "change_password".to_string())
+        .add_route(Routes::Chat, CHAT.to_string())
+        .add_route(Routes::Logins, LOGINS.to_string())
+        .add_route(Routes::Admin, ADMIN.to_string());
 
     Model {
         theme: Theme::default(),
         state: Default::default(),
         router,
         logged_user: None,
+        policy: PasswordPolicy::default(),
+        language: LocalStorage::get(LANGUAGE_STORAGE_KEY).unwrap_or_default(),
     }
 }
 
@@ -56,9 +80,31 @@ pub enum Routes {
     Login,
     Register,
     Dashboard,
-    // Admin(page::admin::Model),
+    ChangePassword,
+    Chat,
+    Logins,
+    Admin,
     NotFound,
 }
+
+impl Routes {
+    /// Routes only reachable by a logged user
+    pub fn requires_auth(self) -> bool {
+        matches!(
+            self,
+            Routes::Dashboard
+                | Routes::ChangePassword
+                | Routes::Chat
+                | Routes::Logins
+                | Routes::Admin
+        )
+    }
+
+    /// Routes only reachable by a user managing the others
+    pub fn requires_admin(self) -> bool {
+        matches!(self, Routes::Admin)
+    }
+}
 // ------ ------
 //     Model
 // ------ ------
@@ -68,6 +114,10 @@ struct Model {
     router: Router<Routes>,
     logged_user: Option<LoggedUser>,
     theme: Theme,
+    /// Password policy of the server, the default one until it is fetched
+    policy: PasswordPolicy,
+    /// Language of the messages, remembered in the local storage
+    language: Language,
 }
 
 // ------ State for component ------
@@ -75,6 +125,10 @@ struct Model {
 pub struct State {
     pub register: pages::register::Model,
     pub login: pages::login::Model,
+    pub password: pages::password::Model,
+    pub chat: pages::chat::Model,
+    pub logins: pages::logins::Model,
+    pub admin: pages::admin::Model,
 }
 
 /// Page Struct regroups all the possible root navigate on your App
@@ -138,8 +192,14 @@ pub enum Msg {
     UrlRequested(subs::UrlRequested),
     Register(pages::register::Msg),
     Login(pages::login::Msg),
+    Password(pages::password::Msg),
+    Chat(pages::chat::Msg),
+    Logins(pages::logins::Msg),
+    Admin(pages::admin::Msg),
     UserLogged(LoggedUser),
+    PolicyFetched(PasswordPolicy),
     SwitchToTheme(Theme),
+    SwitchToLanguage(Language),
 }
 
 /// Main update for the entire APP, every component action/message should me
@@ -155,10 +215,35 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
             log!("URL requested");
             let url = request.0;
             model.router.navigate_to_url(url);
+            if model.router.is_current_route(Routes::Chat) && model.logged_user.is_some() {
+                orders.send_msg(Msg::Chat(pages::chat::Msg::Open));
+            }
+            if model.router.is_current_route(Routes::Logins) {
+                orders.send_msg(Msg::Logins(pages::logins::Msg::Fetch));
+            }
+            if model.router.is_current_route(Routes::Admin) && is_admin(model) {
+                orders.send_msg(Msg::Admin(pages::admin::Msg::Fetch));
+            }
         }
+        Msg::Chat(chat_message) => pages::chat::update(
+            chat_message,
+            &mut model.state.chat,
+            &mut orders.proxy(Msg::Chat),
+        ),
+        Msg::Logins(logins_message) => pages::logins::update(
+            logins_message,
+            &mut model.state.logins,
+            &mut orders.proxy(Msg::Logins),
+        ),
+        Msg::Admin(admin_message) => pages::admin::update(
+            admin_message,
+            &mut model.state.admin,
+            &mut orders.proxy(Msg::Admin),
+        ),
         Msg::Register(register_message) => pages::register::update(
             register_message,
             &mut model.state.register,
+            model.language,
             &mut orders.proxy(Msg::Register),
         ),
         Msg::Login(login_message) => pages::login::update(
@@ -166,14 +251,37 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
             &mut model.state.login,
             &mut orders.proxy(Msg::Login),
         ),
+        Msg::Password(password_message) => {
+            if let pages::password::Msg::ChangeSucceed(_) = &password_message {
+                if let Some(user) = model.logged_user.as_mut() {
+                    user.password_reset_required = false;
+                }
+            }
+            pages::password::update(
+                password_message,
+                &mut model.state.password,
+                model.logged_user.as_ref(),
+                model.language,
+                &mut orders.proxy(Msg::Password),
+            )
+        }
         Msg::UserLogged(user) => {
             log!("got user logged");
             model.logged_user = Some(user);
+            // Connected to the chat from any page for the unread counts
+            orders.send_msg(Msg::Chat(pages::chat::Msg::Open));
             // orders.notify(subs::UrlRequested::new(
             //     Urls::new(&model.base_url).build_url(DASHBOARD),
             // ));
         }
+        Msg::PolicyFetched(policy) => model.policy = policy,
         Msg::SwitchToTheme(theme) => model.theme = theme,
+        Msg::SwitchToLanguage(language) => {
+            if let Err(err) = LocalStorage::insert(LANGUAGE_STORAGE_KEY, &language) {
+                error!("Could not remember the language", err);
+            }
+            model.language = language;
+        }
     }
 }
 
@@ -190,9 +298,13 @@ fn view(model: &Model) -> impl IntoNodes<Msg> {
                     Routes::Home => home(&model.theme),
                     // Page::Admin(admin_model) => page::admin::view(admin_model, &model.ctx),
                     Routes::NotFound => div!["404"],
-                    Routes::Login => pages::login::view(&model.state.login).map_msg(Msg::Login),
+                    Routes::Login => {
+                        pages::login::view(&model.state.login, &model.policy, model.language)
+                            .map_msg(Msg::Login)
+                    }
                     Routes::Register => {
-                        pages::register::view(&model.state.register).map_msg(Msg::Register)
+                        pages::register::view(&model.state.register, &model.policy, model.language)
+                            .map_msg(Msg::Register)
                     }
                     _ => div!["404"],
                 }
@@ -201,16 +313,41 @@ fn view(model: &Model) -> impl IntoNodes<Msg> {
             },
         ]
     } else {
-        vec![div!["Authenticated Routing not working"]]
-        // vec![
-        //     authenticated_header(&model.base_url, &model.page),
-        //     match &model.page {
-        //         Route::Dashboard => div![div!["Welcome Dashboard!"],],
-        //         // Page::Admin(admin_model) => page::admin::view(admin_model,
-        // &model.ctx),         Route::NotFound => div!["404"],
-        //         _ => div!["404"],
-        //     },
-        // ]
+        // An admin asked for a new password, nothing else until it is changed
+        let reset_required =
+            matches!(&model.logged_user, Some(user) if user.password_reset_required);
+        vec![
+            authenticated_header(&model),
+            match &model.router.current_route {
+                _ if reset_required => pages::password::view(
+                    &model.state.password,
+                    &model.policy,
+                    model.logged_user.as_ref(),
+                    model.language,
+                )
+                .map_msg(Msg::Password),
+                Some(Routes::ChangePassword) => pages::password::view(
+                    &model.state.password,
+                    &model.policy,
+                    model.logged_user.as_ref(),
+                    model.language,
+                )
+                .map_msg(Msg::Password),
+                Some(Routes::Chat) => {
+                    pages::chat::view(&model.state.chat, model.logged_user.as_ref())
+                        .map_msg(Msg::Chat)
+                }
+                Some(Routes::Logins) => {
+                    pages::logins::view(&model.state.logins).map_msg(Msg::Logins)
+                }
+                Some(Routes::Admin) if is_admin(model) => {
+                    pages::admin::view(&model.state.admin).map_msg(Msg::Admin)
+                }
+                Some(Routes::NotFound) | Some(Routes::Admin) => div!["404"],
+                // Guest routes fall back to the dashboard once logged
+                _ => div![div!["Welcome Dashboard!"],],
+            },
+        ]
     }
 }
 
@@ -220,15 +357,56 @@ fn header(model: &Model) -> Node<Msg> {
     let mut list: Vec<Node<Msg>> = Vec::new();
 
     for route in &model.router.mapped_routes() {
-        list.push(render_route(route));
+        if !route.route.requires_auth() {
+            list.push(render_route(route));
+        }
     }
     div![
         TopBar::new("Welcome Guest").style(model.theme.clone()),
+        language_switch(model.language),
+        ul![list]
+    ]
+}
+
+fn authenticated_header(model: &Model) -> Node<Msg> {
+    let mut list: Vec<Node<Msg>> = Vec::new();
+
+    for route in &model.router.mapped_routes() {
+        if route.route.requires_auth() && (!route.route.requires_admin() || is_admin(model)) {
+            list.push(render_route(route));
+        }
+    }
+    let username = model
+        .logged_user
+        .as_ref()
+        .map_or("", |user| user.username());
+    div![
+        TopBar::new(format!("Welcome {}", username))
+            .style(model.theme.clone())
+            .unread(pages::chat::unread_total(&model.state.chat)),
+        language_switch(model.language),
         ul![list]
     ]
 }
 //
 
+/// The logged user can manage the other users
+fn is_admin(model: &Model) -> bool {
+    matches!(&model.logged_user, Some(user) if user.has_permission(Permission::ManageUsers))
+}
+
+/// Buttons to pick the language of the messages
+fn language_switch(current: Language) -> Node<Msg> {
+    div![Language::ALL.iter().map(|language| {
+        let language = *language;
+        button![
+            C!["route", IF!(language == current => "active-route")],
+            language.name(),
+            ev(Ev::Click, move |_| Msg::SwitchToLanguage(language)),
+        ]
+    })]
+}
+
 fn render_route(route: &ExtractedRoute<Routes>) -> Node<Msg> {
     li![a![
         C!["route", IF!( route.is_active => "active-route" )],
@@ -248,10 +426,6 @@ fn render_route(route: &ExtractedRoute<Routes>) -> Node<Msg> {
 //     ]]
 // }
 
-// fn authenticated_header(base_url: &Url, page: &Route) -> Node<Msg> {
-//     ul![route(base_url, page, "Dashboard"),]
-// }
-
 fn home(theme: &Theme) -> Node<Msg> {
     div![
         div!["Welcome home!"],
@@ -0,0 +1,402 @@
+use seed::{prelude::*, *};
+use shared::models::{
+    admin::{ManagedUser, SortOrder, UserPage, UserQuery, UserSort},
+    role::{Role, RolesChange},
+    user::{AccountStatus, StatusTransition},
+};
+
+#[derive(Default)]
+pub struct Model {
+    query: UserQuery,
+    /// What is typed in the search box, sent on submit
+    search: String,
+    page: Option<UserPage>,
+    is_loading: bool,
+    error: Option<String>,
+    /// User opened in the detail drawer
+    selected: Option<ManagedUser>,
+    /// Why the status of the selected user is changed
+    reason: String,
+}
+
+/// Action on admin page
+pub enum Msg {
+    Fetch,
+    Fetched(UserPage),
+    RequestFailed {
+        message: String,
+        code: String,
+    },
+    SearchChanged(String),
+    Search,
+    StatusFilterChanged(String),
+    RoleFilterChanged(String),
+    SortBy(UserSort),
+    PageChanged(u32),
+    Select(ManagedUser),
+    Selected(ManagedUser),
+    CloseDrawer,
+    ReasonChanged(String),
+    ChangeStatus(AccountStatus),
+    ChangeRoles(Vec<Role>),
+    /// An action on the selected user succeeded, reload the list and the user
+    Changed,
+}
+
+/// Update on admin page
+pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
+    match msg {
+        Msg::Fetch => {
+            model.is_loading = true;
+            let url = format!("/api/admin/users?{}", model.query.to_query_string());
+            orders.perform_cmd(async move {
+                let response = fetch(url).await.expect("HTTP request failed");
+                if response.status().is_ok() {
+                    Msg::Fetched(response.json().await.unwrap())
+                } else {
+                    failure(response).await
+                }
+            });
+        }
+        Msg::Fetched(page) => {
+            model.is_loading = false;
+            model.error = None;
+            model.page = Some(page);
+        }
+        Msg::RequestFailed { message, code } => {
+            model.is_loading = false;
+            model.error = Some(format!(
+                "An error happened {} with the code {}",
+                message, code
+            ));
+        }
+        Msg::SearchChanged(search) => model.search = search,
+        Msg::Search => {
+            let search = model.search.trim();
+            model.query.search = if search.is_empty() {
+                None
+            } else {
+                Some(search.to_string())
+            };
+            model.query.page = 0;
+            orders.send_msg(Msg::Fetch);
+        }
+        Msg::StatusFilterChanged(status) => {
+            model.query.status = AccountStatus::ALL
+                .iter()
+                .find(|s| s.as_str() == status)
+                .cloned();
+            model.query.page = 0;
+            orders.send_msg(Msg::Fetch);
+        }
+        Msg::RoleFilterChanged(role) => {
+            model.query.role = Role::ALL.iter().find(|r| r.as_str() == role).cloned();
+            model.query.page = 0;
+            orders.send_msg(Msg::Fetch);
+        }
+        Msg::SortBy(sort) => {
+            // Clicking the sorted column again reverses the order
+            model.query.order = if model.query.sort == sort && model.query.order == SortOrder::Asc {
+                SortOrder::Desc
+            } else {
+                SortOrder::Asc
+            };
+            model.query.sort = sort;
+            orders.send_msg(Msg::Fetch);
+        }
+        Msg::PageChanged(page) => {
+            model.query.page = page;
+            orders.send_msg(Msg::Fetch);
+        }
+        Msg::Select(user) => {
+            model.reason = "".to_string();
+            model.selected = Some(user);
+        }
+        Msg::Selected(user) => model.selected = Some(user),
+        Msg::CloseDrawer => model.selected = None,
+        Msg::ReasonChanged(reason) => model.reason = reason,
+        Msg::ChangeStatus(status) => {
+            if let Some(user) = &model.selected {
+                let reason = model.reason.trim();
+                let transition = StatusTransition {
+                    status,
+                    reason: if reason.is_empty() {
+                        None
+                    } else {
+                        Some(reason.to_string())
+                    },
+                };
+                let request = Request::new(user_url(user, "/status"))
+                    .method(Method::Post)
+                    .json(&transition)
+                    .expect("Serialization failed");
+                send_action(request, orders);
+            }
+        }
+        Msg::ChangeRoles(roles) => {
+            if let Some(user) = &model.selected {
+                let request = Request::new(user_url(user, "/roles"))
+                    .method(Method::Post)
+                    .json(&RolesChange { roles })
+                    .expect("Serialization failed");
+                send_action(request, orders);
+            }
+        }
+        Msg::Changed => {
+            model.reason = "".to_string();
+            orders.send_msg(Msg::Fetch);
+            if let Some(user) = &model.selected {
+                let url = user_url(user, "");
+                orders.perform_cmd(async move {
+                    let response = fetch(url).await.expect("HTTP request failed");
+                    if response.status().is_ok() {
+                        Msg::Selected(response.json().await.unwrap())
+                    } else {
+                        failure(response).await
+                    }
+                });
+            }
+        }
+    }
+}
+
+fn user_url(user: &ManagedUser, action: &str) -> String {
+    format!(
+        "/api/admin/users/{}{}",
+        user.info.credentials.username(),
+        action
+    )
+}
+
+fn send_action(request: Request<'static>, orders: &mut impl Orders<Msg>) {
+    orders.perform_cmd(async {
+        let response = fetch(request).await.expect("HTTP request failed");
+        if response.status().is_ok() {
+            Msg::Changed
+        } else {
+            failure(response).await
+        }
+    });
+}
+
+async fn failure(response: Response) -> Msg {
+    Msg::RequestFailed {
+        code: response.status().code.to_string(),
+        message: response.text().await.unwrap_or_default(),
+    }
+}
+
+/// view of admin page
+pub fn view(model: &Model) -> Node<Msg> {
+    div![
+        C!["admin"],
+        filters(model),
+        IF!(model.is_loading => div![C!["lds-ring"], div![], div![], div![], div![]]),
+        model.error.as_ref().map(|error| p![C!["centred"], error]),
+        model
+            .page
+            .as_ref()
+            .map(|page| users_table(page, &model.query)),
+        model.page.as_ref().map(pagination),
+        model
+            .selected
+            .as_ref()
+            .map(|user| drawer(user, &model.reason)),
+    ]
+}
+
+fn filters(model: &Model) -> Node<Msg> {
+    let status = model.query.status.map_or("", AccountStatus::as_str);
+    let role = model.query.role.map_or("", Role::as_str);
+    form![
+        C!["admin-filters"],
+        ev(Ev::Submit, |event| {
+            event.prevent_default();
+            Msg::Search
+        }),
+        input![
+            attrs! {
+                At::Type => "search",
+                At::Placeholder => "Username, name or email",
+                At::Value => model.search,
+            },
+            input_ev(Ev::Input, Msg::SearchChanged),
+        ],
+        button!["Search", attrs! { At::Type => "submit" }],
+        select![
+            option![attrs! { At::Value => "", At::Selected => status.is_empty().as_at_value() }, "Any status"],
+            AccountStatus::ALL.iter().map(|s| option![
+                attrs! { At::Value => s.as_str(), At::Selected => (s.as_str() == status).as_at_value() },
+                s.as_str()
+            ]),
+            input_ev(Ev::Change, Msg::StatusFilterChanged),
+        ],
+        select![
+            option![attrs! { At::Value => "", At::Selected => role.is_empty().as_at_value() }, "Any role"],
+            Role::ALL.iter().map(|r| option![
+                attrs! { At::Value => r.as_str(), At::Selected => (r.as_str() == role).as_at_value() },
+                r.as_str()
+            ]),
+            input_ev(Ev::Change, Msg::RoleFilterChanged),
+        ],
+    ]
+}
+
+fn users_table(page: &UserPage, query: &UserQuery) -> Node<Msg> {
+    table![
+        C!["admin-users"],
+        thead![tr![
+            sort_header("Username", UserSort::Username, query),
+            sort_header("Name", UserSort::LastName, query),
+            th!["Email"],
+            sort_header("Status", UserSort::Status, query),
+            th!["Roles"],
+            sort_header("Registered", UserSort::RegisteredAt, query),
+        ]],
+        tbody![page.users.iter().map(|user| {
+            let selected = user.clone();
+            tr![
+                ev(Ev::Click, move |_| Msg::Select(selected)),
+                td![user.info.credentials.username()],
+                td![format!("{} {}", user.info.first_name, user.info.last_name)],
+                td![user.info.credentials.email()],
+                td![user.account.status.as_str()],
+                td![roles_text(&user.roles)],
+                td![user.registered_at.as_deref().unwrap_or("-")],
+            ]
+        })],
+    ]
+}
+
+fn sort_header(title: &str, sort: UserSort, query: &UserQuery) -> Node<Msg> {
+    let arrow = match (query.sort == sort, query.order) {
+        (true, SortOrder::Asc) => " ▲",
+        (true, SortOrder::Desc) => " ▼",
+        (false, _) => "",
+    };
+    th![
+        C!["sortable"],
+        format!("{}{}", title, arrow),
+        ev(Ev::Click, move |_| Msg::SortBy(sort)),
+    ]
+}
+
+fn pagination(page: &UserPage) -> Node<Msg> {
+    let per_page = u64::from(page.per_page.max(1));
+    let last_page = ((page.total + per_page - 1) / per_page).max(1) as u32 - 1;
+    let current = page.page;
+    div![
+        C!["pagination"],
+        button![
+            "Previous",
+            attrs! { At::Disabled => (current == 0).as_at_value() },
+            ev(Ev::Click, move |_| Msg::PageChanged(
+                current.saturating_sub(1)
+            )),
+        ],
+        span![format!(
+            "Page {} of {} ({} users)",
+            current + 1,
+            last_page + 1,
+            page.total
+        )],
+        button![
+            "Next",
+            attrs! { At::Disabled => (current >= last_page).as_at_value() },
+            ev(Ev::Click, move |_| Msg::PageChanged(current + 1)),
+        ],
+    ]
+}
+
+fn roles_text(roles: &[Role]) -> String {
+    roles
+        .iter()
+        .map(|role| role.as_str())
+        .collect::<Vec<&str>>()
+        .join(", ")
+}
+
+/// Details of a user with the actions an admin can do on it
+fn drawer(user: &ManagedUser, reason: &str) -> Node<Msg> {
+    let status = user.account.status;
+    div![
+        C!["drawer"],
+        button!["Close", ev(Ev::Click, |_| Msg::CloseDrawer)],
+        h3![user.info.credentials.username()],
+        p![format!("{} {}", user.info.first_name, user.info.last_name)],
+        p![user.info.credentials.email()],
+        p![format!(
+            "Registered {}",
+            user.registered_at
+                .as_deref()
+                .unwrap_or("before the registration date was kept")
+        )],
+        p![format!(
+            "Status {} since {}",
+            status.as_str(),
+            user.account.changed_at.as_deref().unwrap_or("-")
+        )],
+        user.account
+            .reason
+            .as_ref()
+            .map(|reason| p![format!("Reason: {}", reason)]),
+        IF!(user.password_reset_required => p!["Has to change its password"]),
+        h4!["Status"],
+        input![
+            attrs! {
+                At::Type => "text",
+                At::Placeholder => "Reason",
+                At::Value => reason,
+            },
+            input_ev(Ev::Input, Msg::ReasonChanged),
+        ],
+        AccountStatus::ALL
+            .iter()
+            .filter(|next| status.can_become(**next))
+            .map(|next| {
+                let next = *next;
+                button![
+                    status_action(status, next),
+                    ev(Ev::Click, move |_| Msg::ChangeStatus(next)),
+                ]
+            }),
+        h4!["Roles"],
+        Role::ALL
+            .iter()
+            .filter(|role| **role != Role::User)
+            .map(|role| {
+                let role = *role;
+                let has_role = user.roles.contains(&role);
+                let roles: Vec<Role> = if has_role {
+                    user.roles.iter().filter(|r| **r != role).cloned().collect()
+                } else {
+                    user.roles
+                        .iter()
+                        .cloned()
+                        .chain(std::iter::once(role))
+                        .collect()
+                };
+                label![
+                    input![
+                        attrs! {
+                            At::Type => "checkbox",
+                            At::Checked => has_role.as_at_value(),
+                        },
+                        ev(Ev::Change, move |_| Msg::ChangeRoles(roles)),
+                    ],
+                    role.as_str(),
+                ]
+            }),
+    ]
+}
+
+fn status_action(current: AccountStatus, next: AccountStatus) -> &'static str {
+    match (current, next) {
+        (AccountStatus::Pending, AccountStatus::Active) => "Approve",
+        (AccountStatus::Suspended, AccountStatus::Active) => "Restore",
+        (_, AccountStatus::Active) => "Activate",
+        (_, AccountStatus::Suspended) => "Suspend",
+        (_, AccountStatus::Deleted) => "Delete",
+        (_, AccountStatus::Pending) => "Set pending",
+    }
+}
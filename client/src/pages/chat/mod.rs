@@ -0,0 +1,1502 @@
+use seed::{prelude::*, *};
+use shared::{
+    models::{
+        chat::{
+            Cursor, HistoryPage, HistoryQuery, Message, NewConversation, NewMessage, NewRoom,
+            Reaction, ReadReceipt, Room, RoomKind, RoomSummary, ThreadSummary,
+        },
+        presence::{Presence, PresenceStatus},
+        role::Permission,
+        user::LoggedUser,
+    },
+    protocol::{ClientEvent, Frame, ServerEvent},
+};
+use std::collections::HashMap;
+use web_sys::HtmlElement;
+
+/// Older messages are fetched when the timeline is scrolled this close to
+/// its top, in pixels
+const LOAD_OLDER_THRESHOLD: i32 = 100;
+/// The server forgets a connection without heartbeat for 90 seconds
+const HEARTBEAT_INTERVAL_MS: u32 = 30_000;
+/// Offered under every message, any other emoji is accepted by the server
+const QUICK_REACTIONS: [&str; 6] = ["👍", "❤️", "😂", "😮", "😢", "🎉"];
+/// Typing is sent at most this often while writing
+const TYPING_THROTTLE_MS: u32 = 3_000;
+/// Someone is shown typing this long after the last notice
+const TYPING_DISPLAY_MS: u32 = 5_000;
+
+/// State of the WebSocket shown next to the room list
+#[derive(Clone, Copy, PartialEq)]
+pub enum ConnectionStatus {
+    Disconnected,
+    Connecting,
+    Connected,
+    /// Waiting before the next attempt, counting from 1
+    Reconnecting(usize),
+}
+
+impl Default for ConnectionStatus {
+    fn default() -> Self {
+        ConnectionStatus::Disconnected
+    }
+}
+
+/// Someone writing in a room
+struct TypingUser {
+    room: String,
+    username: String,
+    /// Tells which notice a timeout is for
+    notice: usize,
+}
+
+/// A message of the timeline with its replies, shown next to it
+struct ThreadView {
+    /// Key of the message starting the thread
+    root: String,
+    /// Replies, oldest first
+    replies: Vec<Message>,
+    /// Cursor of the page before the first reply
+    older: Option<String>,
+    draft: String,
+}
+
+impl ThreadView {
+    fn new(root: String) -> Self {
+        ThreadView {
+            root,
+            replies: Vec::new(),
+            older: None,
+            draft: String::new(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Model {
+    rooms: Vec<RoomSummary>,
+    /// Key of the room shown in the timeline
+    current: Option<String>,
+    /// Messages of the current room, oldest first
+    messages: Vec<Message>,
+    /// Cursor of the page before the first message, None at the start of
+    /// the room
+    older: Option<String>,
+    loading_older: bool,
+    /// Scrolled list of the messages
+    timeline: ElRef<HtmlElement>,
+    /// Last message read by each member of the current room
+    receipts: HashMap<String, String>,
+    /// Key of the last message the server was told the user read
+    marked_read: Option<String>,
+    draft: String,
+    /// Key and new body of the message being edited
+    editing: Option<(String, String)>,
+    /// Open thread of the current room
+    thread: Option<ThreadView>,
+    new_room: NewRoom,
+    /// Usernames separated by commas
+    new_conversation: String,
+    /// Username given by the server once connected
+    me: Option<String>,
+    /// Users online or away, the others are offline
+    presence: HashMap<String, PresenceStatus>,
+    typing: Vec<TypingUser>,
+    typing_notices: usize,
+    /// A typing notice was sent lately
+    typing_throttled: bool,
+    heartbeat: Option<StreamHandle>,
+    status: ConnectionStatus,
+    /// None when the page never connected
+    web_socket: Option<WebSocket>,
+    /// Retries the connection with a growing delay until it is open again
+    reconnector: Option<StreamHandle>,
+    error: Option<String>,
+}
+
+/// Action on chat page
+pub enum Msg {
+    /// The page is shown, fetch the rooms and connect if needed
+    Open,
+    RoomsFetched(Vec<RoomSummary>),
+    PresenceFetched(Vec<Presence>),
+    ReceiptsFetched {
+        room: String,
+        receipts: Vec<ReadReceipt>,
+    },
+    SendHeartbeat,
+    TypingThrottleEnded,
+    TypingExpired(usize),
+    Enter(String),
+    HistoryFetched {
+        room: String,
+        page: HistoryPage,
+    },
+    TimelineScrolled,
+    OlderFetched {
+        room: String,
+        page: HistoryPage,
+    },
+    /// Messages missed while disconnected
+    NewerFetched {
+        room: String,
+        page: HistoryPage,
+    },
+    Join(String),
+    Joined(String),
+    Leave(String),
+    Left(String),
+    NewRoomNameChanged(String),
+    NewRoomTopicChanged(String),
+    CreateRoom,
+    RoomCreated(RoomSummary),
+    NewConversationChanged(String),
+    OpenConversation,
+    /// Also used when opening an existing conversation
+    ConversationOpened(RoomSummary),
+    DraftChanged(String),
+    StartEdit(String),
+    EditChanged(String),
+    SaveEdit,
+    CancelEdit,
+    Delete(String),
+    /// Edited or deleted
+    MessageUpdated(Message),
+    /// Add the reaction of the user, or remove it when it is there
+    ToggleReaction {
+        message: String,
+        emoji: String,
+    },
+    ReactionToggled {
+        message: String,
+        emoji: String,
+        added: bool,
+    },
+    Send,
+    /// Posted message or reply
+    Sent(Message),
+    OpenThread(String),
+    CloseThread,
+    ThreadFetched {
+        root: String,
+        page: HistoryPage,
+    },
+    OlderRepliesRequested,
+    OlderRepliesFetched {
+        root: String,
+        page: HistoryPage,
+    },
+    ReplyChanged(String),
+    SendReply,
+    WebSocketOpened,
+    WebSocketClosed(CloseEvent),
+    WebSocketFailed,
+    ReconnectWebSocket(usize),
+    FrameReceived(WebSocketMessage),
+    RequestFailed {
+        message: String,
+        code: String,
+    },
+}
+
+/// Update on chat page
+pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
+    match msg {
+        Msg::Open => {
+            fetch_rooms(orders);
+            fetch_presence(orders);
+            if model.heartbeat.is_none() {
+                model.heartbeat = Some(
+                    orders.stream_with_handle(streams::interval(HEARTBEAT_INTERVAL_MS, || {
+                        Msg::SendHeartbeat
+                    })),
+                );
+            }
+            if model.web_socket.is_none() {
+                model.status = ConnectionStatus::Connecting;
+                model.web_socket = Some(create_web_socket(orders));
+            }
+        }
+        Msg::RoomsFetched(rooms) => {
+            model.rooms = rooms;
+            subscribe_all(model);
+        }
+        Msg::ReceiptsFetched { room, receipts } => {
+            if model.current.as_ref() == Some(&room) {
+                model.receipts = receipts
+                    .into_iter()
+                    .map(|receipt| (receipt.username, receipt.message))
+                    .collect();
+            }
+        }
+        Msg::PresenceFetched(presence) => {
+            model.presence = presence
+                .into_iter()
+                .map(|presence| (presence.username, presence.status))
+                .collect();
+        }
+        Msg::SendHeartbeat => {
+            let active = page_is_active();
+            send_event(model, ClientEvent::Heartbeat { active });
+            // Messages which came while the page was in the background
+            if active {
+                mark_read(model);
+            }
+        }
+        Msg::TypingThrottleEnded => model.typing_throttled = false,
+        Msg::TypingExpired(notice) => model.typing.retain(|typing| typing.notice != notice),
+        Msg::Enter(room) => {
+            // The other rooms stay subscribed to count their unread messages
+            model.current = Some(room.clone());
+            model.messages.clear();
+            model.older = None;
+            model.loading_older = false;
+            model.receipts.clear();
+            model.thread = None;
+            send_event(model, ClientEvent::Subscribe { room: room.clone() });
+            fetch_receipts(room.clone(), orders);
+            fetch_history(
+                room,
+                HistoryQuery::default(),
+                |room, page| Msg::HistoryFetched { room, page },
+                orders,
+            );
+        }
+        Msg::HistoryFetched { room, page } => {
+            if model.current.as_ref() == Some(&room) {
+                model.messages = page.messages;
+                model.older = page.older;
+                scroll_to_bottom(model, orders);
+                mark_read(model);
+            }
+        }
+        Msg::TimelineScrolled => {
+            let near_top = matches!(
+                model.timeline.get(),
+                Some(timeline) if timeline.scroll_top() < LOAD_OLDER_THRESHOLD
+            );
+            if !near_top || model.loading_older {
+                return;
+            }
+            if let (Some(room), Some(older)) = (model.current.clone(), model.older.clone()) {
+                model.loading_older = true;
+                let query = HistoryQuery {
+                    before: Some(older),
+                    ..HistoryQuery::default()
+                };
+                fetch_history(
+                    room,
+                    query,
+                    |room, page| Msg::OlderFetched { room, page },
+                    orders,
+                );
+            }
+        }
+        Msg::OlderFetched { room, page } => {
+            if model.current.as_ref() != Some(&room) {
+                return;
+            }
+            model.loading_older = false;
+            model.older = page.older;
+            let mut messages = page.messages;
+            messages.retain(|message| !model.messages.iter().any(|m| m.key == message.key));
+            messages.append(&mut model.messages);
+            model.messages = messages;
+            // Keep the messages read in place while the older ones are added
+            // above them
+            if let Some(timeline) = model.timeline.get() {
+                let from_bottom = timeline.scroll_height() - timeline.scroll_top();
+                let timeline_ref = model.timeline.clone();
+                orders.after_next_render(move |_| {
+                    if let Some(timeline) = timeline_ref.get() {
+                        timeline.set_scroll_top(timeline.scroll_height() - from_bottom);
+                    }
+                });
+            }
+        }
+        Msg::NewerFetched { room, page } => {
+            if model.current.as_ref() != Some(&room) {
+                return;
+            }
+            for message in page.messages {
+                add_message(model, message, orders);
+            }
+            if let Some(newer) = page.newer {
+                let query = HistoryQuery {
+                    after: Some(newer),
+                    ..HistoryQuery::default()
+                };
+                fetch_history(
+                    room,
+                    query,
+                    |room, page| Msg::NewerFetched { room, page },
+                    orders,
+                );
+            } else {
+                mark_read(model);
+            }
+        }
+        Msg::Join(room) => {
+            let request = Request::new(format!("/api/rooms/{}/join", room)).method(Method::Post);
+            orders.perform_cmd(async move {
+                let response = fetch(request).await.expect("HTTP request failed");
+                if response.status().is_ok() {
+                    Msg::Joined(room)
+                } else {
+                    failure(response).await
+                }
+            });
+        }
+        Msg::Joined(room) => {
+            fetch_rooms(orders);
+            orders.send_msg(Msg::Enter(room));
+        }
+        Msg::Leave(room) => {
+            let request = Request::new(format!("/api/rooms/{}/leave", room)).method(Method::Post);
+            orders.perform_cmd(async move {
+                let response = fetch(request).await.expect("HTTP request failed");
+                if response.status().is_ok() {
+                    Msg::Left(room)
+                } else {
+                    failure(response).await
+                }
+            });
+        }
+        Msg::Left(room) => {
+            if model.current.as_ref() == Some(&room) {
+                model.current = None;
+                model.messages.clear();
+                model.older = None;
+                model.thread = None;
+            }
+            fetch_rooms(orders);
+        }
+        Msg::NewRoomNameChanged(name) => model.new_room.name = name,
+        Msg::NewRoomTopicChanged(topic) => {
+            model.new_room.topic = if topic.trim().is_empty() {
+                None
+            } else {
+                Some(topic)
+            }
+        }
+        Msg::CreateRoom => {
+            let request = Request::new("/api/rooms")
+                .method(Method::Post)
+                .json(&model.new_room)
+                .expect("Serialization failed");
+            model.new_room = NewRoom::default();
+            orders.perform_cmd(async {
+                let response = fetch(request).await.expect("HTTP request failed");
+                if response.status().is_ok() {
+                    Msg::RoomCreated(response.json().await.unwrap())
+                } else {
+                    failure(response).await
+                }
+            });
+        }
+        Msg::RoomCreated(summary) => {
+            let room = summary.room.key.clone();
+            model.rooms.push(summary);
+            orders.send_msg(Msg::Enter(room));
+        }
+        Msg::NewConversationChanged(participants) => model.new_conversation = participants,
+        Msg::OpenConversation => {
+            let conversation = NewConversation {
+                participants: model
+                    .new_conversation
+                    .split(',')
+                    .map(|username| username.trim().to_string())
+                    .filter(|username| !username.is_empty())
+                    .collect(),
+            };
+            let request = Request::new("/api/conversations")
+                .method(Method::Post)
+                .json(&conversation)
+                .expect("Serialization failed");
+            model.new_conversation.clear();
+            orders.perform_cmd(async {
+                let response = fetch(request).await.expect("HTTP request failed");
+                if response.status().is_ok() {
+                    Msg::ConversationOpened(response.json().await.unwrap())
+                } else {
+                    failure(response).await
+                }
+            });
+        }
+        Msg::ConversationOpened(summary) => {
+            let room = summary.room.key.clone();
+            if !model.rooms.iter().any(|s| s.room.key == room) {
+                model.rooms.push(summary);
+            }
+            orders.send_msg(Msg::Enter(room));
+        }
+        Msg::DraftChanged(draft) => {
+            model.draft = draft;
+            if let (Some(room), false) = (model.current.clone(), model.typing_throttled) {
+                if model.status == ConnectionStatus::Connected && !model.draft.is_empty() {
+                    send_event(model, ClientEvent::Typing { room });
+                    model.typing_throttled = true;
+                    orders.perform_cmd(cmds::timeout(TYPING_THROTTLE_MS, || {
+                        Msg::TypingThrottleEnded
+                    }));
+                }
+            }
+        }
+        Msg::Send => {
+            let room = match &model.current {
+                Some(room) => room.clone(),
+                None => return,
+            };
+            let message = NewMessage {
+                body: std::mem::take(&mut model.draft),
+                parent: None,
+            };
+            post(model, room, message, orders);
+        }
+        Msg::Sent(message) => add_message(model, message, orders),
+        Msg::OpenThread(root) => {
+            let room = match &model.current {
+                Some(room) => room.clone(),
+                None => return,
+            };
+            model.thread = Some(ThreadView::new(root.clone()));
+            fetch_thread(
+                room,
+                root,
+                HistoryQuery::default(),
+                |root, page| Msg::ThreadFetched { root, page },
+                orders,
+            );
+        }
+        Msg::CloseThread => model.thread = None,
+        Msg::ThreadFetched { root, page } => {
+            if let Some(thread) = model.thread.as_mut().filter(|thread| thread.root == root) {
+                thread.replies = page.messages;
+                thread.older = page.older;
+            }
+        }
+        Msg::OlderRepliesRequested => {
+            let thread = model.thread.as_ref();
+            if let (Some(room), Some(thread)) = (model.current.clone(), thread) {
+                if let Some(older) = thread.older.clone() {
+                    let query = HistoryQuery {
+                        before: Some(older),
+                        ..HistoryQuery::default()
+                    };
+                    fetch_thread(
+                        room,
+                        thread.root.clone(),
+                        query,
+                        |root, page| Msg::OlderRepliesFetched { root, page },
+                        orders,
+                    );
+                }
+            }
+        }
+        Msg::OlderRepliesFetched { root, page } => {
+            if let Some(thread) = model.thread.as_mut().filter(|thread| thread.root == root) {
+                thread.older = page.older;
+                let mut replies = page.messages;
+                replies.retain(|reply| !thread.replies.iter().any(|r| r.key == reply.key));
+                replies.append(&mut thread.replies);
+                thread.replies = replies;
+            }
+        }
+        Msg::ReplyChanged(draft) => {
+            if let Some(thread) = model.thread.as_mut() {
+                thread.draft = draft;
+            }
+        }
+        Msg::SendReply => {
+            let (room, thread) = match (model.current.clone(), model.thread.as_mut()) {
+                (Some(room), Some(thread)) => (room, thread),
+                _ => return,
+            };
+            let message = NewMessage {
+                body: std::mem::take(&mut thread.draft),
+                parent: Some(thread.root.clone()),
+            };
+            post(model, room, message, orders);
+        }
+        Msg::StartEdit(key) => {
+            if let Some(message) = find_message(model, &key) {
+                model.editing = Some((key, message.body.clone()));
+            }
+        }
+        Msg::EditChanged(body) => {
+            if let Some((_, draft)) = model.editing.as_mut() {
+                *draft = body;
+            }
+        }
+        Msg::SaveEdit => {
+            let (room, (key, body)) = match (model.current.clone(), model.editing.take()) {
+                (Some(room), Some(editing)) => (room, editing),
+                _ => return,
+            };
+            let request = Request::new(format!("/api/rooms/{}/messages/{}", room, key))
+                .method(Method::Put)
+                .json(&NewMessage { body, parent: None })
+                .expect("Serialization failed");
+            update_message(request, orders);
+        }
+        Msg::CancelEdit => model.editing = None,
+        Msg::Delete(key) => {
+            let room = match &model.current {
+                Some(room) => room.clone(),
+                None => return,
+            };
+            let request = Request::new(format!("/api/rooms/{}/messages/{}", room, key))
+                .method(Method::Delete);
+            update_message(request, orders);
+        }
+        Msg::MessageUpdated(message) => replace_message(model, message),
+        Msg::ToggleReaction { message, emoji } => {
+            let (room, me) = match (&model.current, &model.me) {
+                (Some(room), Some(me)) => (room.clone(), me.clone()),
+                _ => return,
+            };
+            let added = !find_message(model, &message)
+                .into_iter()
+                .flat_map(|message| message.reactions.iter())
+                .any(|reaction| reaction.emoji == emoji && reaction.users.contains(&me));
+            let request = Request::new(format!(
+                "/api/rooms/{}/messages/{}/reactions/{}",
+                room,
+                message,
+                String::from(js_sys::encode_uri_component(&emoji))
+            ))
+            .method(if added { Method::Put } else { Method::Delete });
+            orders.perform_cmd(async move {
+                let response = fetch(request).await.expect("HTTP request failed");
+                if response.status().is_ok() {
+                    Msg::ReactionToggled {
+                        message,
+                        emoji,
+                        added,
+                    }
+                } else {
+                    failure(response).await
+                }
+            });
+        }
+        Msg::ReactionToggled {
+            message,
+            emoji,
+            added,
+        } => {
+            if let Some(me) = model.me.clone() {
+                apply_reaction(model, &message, emoji, me, added);
+            }
+        }
+        Msg::WebSocketOpened => {
+            model.reconnector = None;
+            model.status = ConnectionStatus::Connected;
+            model.error = None;
+            send_event(
+                model,
+                ClientEvent::Heartbeat {
+                    active: page_is_active(),
+                },
+            );
+            // Changes were missed while disconnected
+            fetch_presence(orders);
+            // Listen again to the rooms and catch up what was missed
+            model.marked_read = None;
+            subscribe_all(model);
+            fetch_rooms(orders);
+            if let (Some(room), Some(thread)) = (model.current.clone(), model.thread.as_ref()) {
+                fetch_thread(
+                    room,
+                    thread.root.clone(),
+                    HistoryQuery::default(),
+                    |root, page| Msg::ThreadFetched { root, page },
+                    orders,
+                );
+            }
+            if let Some(room) = model.current.clone() {
+                match model.messages.last() {
+                    Some(last) => {
+                        let query = HistoryQuery {
+                            after: Some(Cursor::of(last).encode()),
+                            ..HistoryQuery::default()
+                        };
+                        fetch_history(
+                            room,
+                            query,
+                            |room, page| Msg::NewerFetched { room, page },
+                            orders,
+                        );
+                    }
+                    None => fetch_history(
+                        room,
+                        HistoryQuery::default(),
+                        |room, page| Msg::HistoryFetched { room, page },
+                        orders,
+                    ),
+                }
+            }
+        }
+        Msg::WebSocketClosed(_) | Msg::WebSocketFailed => {
+            if model.reconnector.is_none() {
+                model.status = ConnectionStatus::Reconnecting(1);
+                model.reconnector = Some(
+                    orders.stream_with_handle(streams::backoff(None, Msg::ReconnectWebSocket)),
+                );
+            }
+        }
+        Msg::ReconnectWebSocket(retries) => {
+            model.status = ConnectionStatus::Reconnecting(retries + 1);
+            model.web_socket = Some(create_web_socket(orders));
+        }
+        Msg::FrameReceived(message) => {
+            let frame = message
+                .text()
+                .map_err(|err| format!("{:?}", err))
+                .and_then(|text| {
+                    Frame::<ServerEvent>::from_json(&text).map_err(|err| format!("{:?}", err))
+                });
+            match frame {
+                Ok(frame) => handle_event(frame.event, model, orders),
+                Err(err) => error!("Could not read a frame", err),
+            }
+        }
+        Msg::RequestFailed { message, code } => {
+            model.loading_older = false;
+            model.error = Some(format!(
+                "An error happened {} with the code {}",
+                message, code
+            ));
+        }
+    }
+}
+
+fn handle_event(event: ServerEvent, model: &mut Model, orders: &mut impl Orders<Msg>) {
+    match event {
+        ServerEvent::Welcome { username } => model.me = Some(username),
+        ServerEvent::Subscribed { .. } => {}
+        ServerEvent::Unsubscribed { room } => {
+            if model.current.as_ref() == Some(&room) {
+                model.current = None;
+                model.messages.clear();
+                model.older = None;
+            }
+        }
+        ServerEvent::MessagePosted { message } => {
+            model
+                .typing
+                .retain(|typing| typing.room != message.room || typing.username != message.author);
+            let is_current = model.current.as_ref() == Some(&message.room);
+            let seen = is_current && page_is_active();
+            // Only the timeline is counted
+            let counted = message.parent.is_none() && Some(&message.author) != model.me.as_ref();
+            if !seen && counted {
+                if let Some(summary) = model.rooms.iter_mut().find(|s| s.room.key == message.room) {
+                    summary.unread += 1;
+                }
+            }
+            add_message(model, message, orders);
+            if seen {
+                mark_read(model);
+            }
+        }
+        ServerEvent::ReadMarkerMoved { room, receipt } => {
+            if Some(&receipt.username) == model.me.as_ref() {
+                // Read on another device, the counts are exact from the server
+                if model.current.as_ref() != Some(&room) {
+                    fetch_rooms(orders);
+                }
+            } else if model.current.as_ref() == Some(&room) {
+                model.receipts.insert(receipt.username, receipt.message);
+            }
+        }
+        ServerEvent::MessageEdited { message } | ServerEvent::MessageDeleted { message } => {
+            replace_message(model, message)
+        }
+        ServerEvent::ReactionAdded {
+            message,
+            emoji,
+            username,
+            ..
+        } => apply_reaction(model, &message, emoji, username, true),
+        ServerEvent::ReactionRemoved {
+            message,
+            emoji,
+            username,
+            ..
+        } => apply_reaction(model, &message, emoji, username, false),
+        ServerEvent::PresenceChanged { presence } => {
+            if presence.status == PresenceStatus::Offline {
+                model.presence.remove(&presence.username);
+            } else {
+                model.presence.insert(presence.username, presence.status);
+            }
+        }
+        ServerEvent::Typing { room, username } => {
+            model.typing_notices += 1;
+            let notice = model.typing_notices;
+            model
+                .typing
+                .retain(|typing| typing.room != room || typing.username != username);
+            model.typing.push(TypingUser {
+                room,
+                username,
+                notice,
+            });
+            orders.perform_cmd(cmds::timeout(TYPING_DISPLAY_MS, move || {
+                Msg::TypingExpired(notice)
+            }));
+        }
+        ServerEvent::RoomOpened { room } => {
+            if !model.rooms.iter().any(|s| s.room.key == room.key) {
+                model.rooms.push(RoomSummary {
+                    members: room.participants.len() as u64,
+                    joined: true,
+                    unread: 0,
+                    room,
+                });
+            }
+        }
+        ServerEvent::MemberJoined { room, .. } => {
+            if let Some(summary) = model.rooms.iter_mut().find(|s| s.room.key == room) {
+                summary.members += 1;
+            }
+        }
+        ServerEvent::MemberLeft { room, .. } => {
+            if let Some(summary) = model.rooms.iter_mut().find(|s| s.room.key == room) {
+                summary.members = summary.members.saturating_sub(1);
+            }
+        }
+        ServerEvent::Error { message } => model.error = Some(message),
+    }
+}
+
+/// A message can come from the WebSocket and from the REST answer
+fn add_message(model: &mut Model, message: Message, orders: &mut impl Orders<Msg>) {
+    if model.current.as_ref() != Some(&message.room) {
+        return;
+    }
+    match message.parent.clone() {
+        Some(parent) => add_reply(model, &parent, message),
+        None if !model.messages.iter().any(|m| m.key == message.key) => {
+            model.messages.push(message);
+            scroll_to_bottom(model, orders);
+        }
+        None => {}
+    }
+}
+
+/// Goes to the open thread and to the summary of its parent, the last reply
+/// is not counted twice
+fn add_reply(model: &mut Model, parent: &str, reply: Message) {
+    if let Some(thread) = model.thread.as_mut().filter(|thread| thread.root == parent) {
+        if !thread.replies.iter().any(|r| r.key == reply.key) {
+            thread.replies.push(reply.clone());
+        }
+    }
+    if let Some(root) = model.messages.iter_mut().find(|m| m.key == parent) {
+        match root.thread.as_mut() {
+            Some(summary) if summary.last_reply.key == reply.key => {}
+            Some(summary) => {
+                summary.replies += 1;
+                summary.last_reply = Box::new(reply);
+            }
+            None => {
+                root.thread = Some(ThreadSummary {
+                    replies: 1,
+                    last_reply: Box::new(reply),
+                })
+            }
+        }
+    }
+}
+
+/// A message of the timeline or a reply of the open thread
+fn find_message<'a>(model: &'a Model, key: &str) -> Option<&'a Message> {
+    let replies = model.thread.iter().flat_map(|thread| thread.replies.iter());
+    model.messages.iter().chain(replies).find(|m| m.key == key)
+}
+
+fn find_message_mut<'a>(model: &'a mut Model, key: &str) -> Option<&'a mut Message> {
+    let replies = model
+        .thread
+        .iter_mut()
+        .flat_map(|thread| thread.replies.iter_mut());
+    model
+        .messages
+        .iter_mut()
+        .chain(replies)
+        .find(|m| m.key == key)
+}
+
+/// New version of a message, the REST answer and the WebSocket both bring it
+fn replace_message(model: &mut Model, message: Message) {
+    if message.deleted && matches!(&model.editing, Some((key, _)) if key == &message.key) {
+        model.editing = None;
+    }
+    if let Some(parent) = &message.parent {
+        let summary = model
+            .messages
+            .iter_mut()
+            .find(|m| &m.key == parent)
+            .and_then(|root| root.thread.as_mut());
+        if let Some(summary) = summary.filter(|summary| summary.last_reply.key == message.key) {
+            summary.last_reply = Box::new(message.clone());
+        }
+    }
+    if let Some(current) = find_message_mut(model, &message.key) {
+        let reactions = std::mem::take(&mut current.reactions);
+        let thread = current.thread.take();
+        *current = message;
+        // Only the timeline gives the reactions and the threads, replies stay
+        // under a deleted message
+        if !current.deleted {
+            current.reactions = reactions;
+        }
+        current.thread = thread;
+    }
+}
+
+/// Both the REST answer and the WebSocket bring a change, applying it twice
+/// does nothing
+fn apply_reaction(model: &mut Model, message: &str, emoji: String, username: String, added: bool) {
+    let reactions = match find_message_mut(model, message) {
+        Some(message) => &mut message.reactions,
+        None => return,
+    };
+    match reactions
+        .iter_mut()
+        .position(|reaction| reaction.emoji == emoji)
+    {
+        Some(index) => {
+            let users = &mut reactions[index].users;
+            users.retain(|user| user != &username);
+            if added {
+                users.push(username);
+            } else if users.is_empty() {
+                reactions.remove(index);
+            }
+        }
+        None if added => reactions.push(Reaction {
+            emoji,
+            users: vec![username],
+        }),
+        None => {}
+    }
+}
+
+fn scroll_to_bottom(model: &Model, orders: &mut impl Orders<Msg>) {
+    let timeline = model.timeline.clone();
+    orders.after_next_render(move |_| {
+        if let Some(timeline) = timeline.get() {
+            timeline.set_scroll_top(timeline.scroll_height());
+        }
+    });
+}
+
+/// Unread messages of every room, shown in the top bar
+pub fn unread_total(model: &Model) -> u64 {
+    model.rooms.iter().map(|summary| summary.unread).sum()
+}
+
+/// Events of every joined room are received, the server ignores
+/// subscriptions it already has
+fn subscribe_all(model: &Model) {
+    for summary in model.rooms.iter().filter(|summary| summary.joined) {
+        send_event(
+            model,
+            ClientEvent::Subscribe {
+                room: summary.room.key.clone(),
+            },
+        );
+    }
+}
+
+/// The user saw the last message of the current room
+fn mark_read(model: &mut Model) {
+    let (room, message) = match (&model.current, model.messages.last()) {
+        (Some(room), Some(last)) => (room.clone(), last.key.clone()),
+        _ => return,
+    };
+    if let Some(summary) = model.rooms.iter_mut().find(|s| s.room.key == room) {
+        summary.unread = 0;
+    }
+    if model.status == ConnectionStatus::Connected && model.marked_read.as_ref() != Some(&message) {
+        model.marked_read = Some(message.clone());
+        send_event(model, ClientEvent::MarkRead { room, message });
+    }
+}
+
+/// Away when the user is in another window or tab
+fn page_is_active() -> bool {
+    document().has_focus().unwrap_or(true)
+}
+
+fn send_event(model: &Model, event: ClientEvent) {
+    if model.status != ConnectionStatus::Connected {
+        return;
+    }
+    if let Some(web_socket) = &model.web_socket {
+        if let Err(err) = web_socket.send_text(Frame::new(event).to_json()) {
+            error!("Could not send a frame", err);
+        }
+    }
+}
+
+fn create_web_socket(orders: &impl Orders<Msg>) -> WebSocket {
+    WebSocket::builder(web_socket_url(), orders)
+        .on_open(|| Msg::WebSocketOpened)
+        .on_message(Msg::FrameReceived)
+        .on_close(Msg::WebSocketClosed)
+        .on_error(|| Msg::WebSocketFailed)
+        .build_and_open()
+        .expect("WebSocket should be created")
+}
+
+/// Same host as the page, the identity cookie goes with it
+fn web_socket_url() -> String {
+    let location = window().location();
+    let scheme = match location.protocol() {
+        Ok(protocol) if protocol == "https:" => "wss",
+        _ => "ws",
+    };
+    format!(
+        "{}://{}/ws",
+        scheme,
+        location.host().expect("The page should have a host")
+    )
+}
+
+fn fetch_presence(orders: &mut impl Orders<Msg>) {
+    orders.perform_cmd(async {
+        let response = fetch("/api/presence").await.expect("HTTP request failed");
+        if response.status().is_ok() {
+            Msg::PresenceFetched(response.json().await.unwrap())
+        } else {
+            failure(response).await
+        }
+    });
+}
+
+fn update_message(request: Request<'static>, orders: &mut impl Orders<Msg>) {
+    orders.perform_cmd(async {
+        let response = fetch(request).await.expect("HTTP request failed");
+        if response.status().is_ok() {
+            Msg::MessageUpdated(response.json().await.unwrap())
+        } else {
+            failure(response).await
+        }
+    });
+}
+
+fn fetch_receipts(room: String, orders: &mut impl Orders<Msg>) {
+    orders.perform_cmd(async move {
+        let response = fetch(format!("/api/rooms/{}/receipts", room))
+            .await
+            .expect("HTTP request failed");
+        if response.status().is_ok() {
+            Msg::ReceiptsFetched {
+                room,
+                receipts: response.json().await.unwrap(),
+            }
+        } else {
+            failure(response).await
+        }
+    });
+}
+
+fn fetch_rooms(orders: &mut impl Orders<Msg>) {
+    orders.perform_cmd(async {
+        let response = fetch("/api/rooms").await.expect("HTTP request failed");
+        if response.status().is_ok() {
+            Msg::RoomsFetched(response.json().await.unwrap())
+        } else {
+            failure(response).await
+        }
+    });
+}
+
+/// Fetch a page of history, `fetched` tells what it is for
+fn fetch_history(
+    room: String,
+    query: HistoryQuery,
+    fetched: fn(String, HistoryPage) -> Msg,
+    orders: &mut impl Orders<Msg>,
+) {
+    let url = format!("/api/rooms/{}/messages?{}", room, query.to_query_string());
+    fetch_page(url, room, fetched, orders);
+}
+
+/// Fetch a page of the replies to `root`
+fn fetch_thread(
+    room: String,
+    root: String,
+    query: HistoryQuery,
+    fetched: fn(String, HistoryPage) -> Msg,
+    orders: &mut impl Orders<Msg>,
+) {
+    let url = format!(
+        "/api/rooms/{}/messages/{}/thread?{}",
+        room,
+        root,
+        query.to_query_string()
+    );
+    fetch_page(url, root, fetched, orders);
+}
+
+/// `key` is given back to `fetched` with the page
+fn fetch_page(
+    url: String,
+    key: String,
+    fetched: fn(String, HistoryPage) -> Msg,
+    orders: &mut impl Orders<Msg>,
+) {
+    orders.perform_cmd(async move {
+        let response = fetch(url).await.expect("HTTP request failed");
+        if response.status().is_ok() {
+            fetched(key, response.json().await.unwrap())
+        } else {
+            failure(response).await
+        }
+    });
+}
+
+/// On the WebSocket when it is connected, the REST API pushes it to the other
+/// members as well
+fn post(model: &Model, room: String, message: NewMessage, orders: &mut impl Orders<Msg>) {
+    if model.status == ConnectionStatus::Connected {
+        let NewMessage { body, parent } = message;
+        send_event(model, ClientEvent::Post { room, body, parent });
+        return;
+    }
+    let request = Request::new(format!("/api/rooms/{}/messages", room))
+        .method(Method::Post)
+        .json(&message)
+        .expect("Serialization failed");
+    orders.perform_cmd(async {
+        let response = fetch(request).await.expect("HTTP request failed");
+        if response.status().is_ok() {
+            Msg::Sent(response.json().await.unwrap())
+        } else {
+            failure(response).await
+        }
+    });
+}
+
+async fn failure(response: Response) -> Msg {
+    Msg::RequestFailed {
+        code: response.status().code.to_string(),
+        message: response.text().await.unwrap_or_default(),
+    }
+}
+
+/// view of chat page
+pub fn view(model: &Model, logged_user: Option<&LoggedUser>) -> Node<Msg> {
+    let can_moderate =
+        matches!(logged_user, Some(user) if user.has_permission(Permission::ModerateMessages));
+    div![
+        C!["chat"],
+        aside![
+            C!["chat-rooms"],
+            connection_status(model.status),
+            new_room_form(&model.new_room),
+            ul![model
+                .rooms
+                .iter()
+                .filter(|summary| summary.room.kind == RoomKind::Public)
+                .map(|summary| room_item(summary, model.current.as_ref()))],
+            h3!["Direct messages"],
+            new_conversation_form(&model.new_conversation),
+            ul![model
+                .rooms
+                .iter()
+                .filter(|summary| summary.room.kind == RoomKind::Direct)
+                .map(|summary| conversation_item(summary, model))],
+        ],
+        section![
+            C!["chat-timeline"],
+            model.error.as_ref().map(|error| p![C!["centred"], error]),
+            match model
+                .rooms
+                .iter()
+                .find(|s| Some(&s.room.key) == model.current.as_ref())
+            {
+                Some(summary) => timeline(summary, model, can_moderate),
+                None => p![C!["centred"], "Pick a room to start talking"],
+            },
+        ],
+        model
+            .thread
+            .as_ref()
+            .map(|thread| thread_view(thread, model, can_moderate)),
+    ]
+}
+
+fn connection_status(status: ConnectionStatus) -> Node<Msg> {
+    let (class, text) = match status {
+        ConnectionStatus::Disconnected => ("offline", "Disconnected".to_string()),
+        ConnectionStatus::Connecting => ("pending", "Connecting…".to_string()),
+        ConnectionStatus::Connected => ("online", "Connected".to_string()),
+        ConnectionStatus::Reconnecting(attempt) => {
+            ("pending", format!("Reconnecting… (attempt {})", attempt))
+        }
+    };
+    p![C!["connection-status", class], text]
+}
+
+fn new_room_form(new_room: &NewRoom) -> Node<Msg> {
+    form![
+        ev(Ev::Submit, |event| {
+            event.prevent_default();
+            Msg::CreateRoom
+        }),
+        input![
+            attrs! {
+                At::Required => true,
+                At::Placeholder => "New room",
+                At::Value => new_room.name,
+            },
+            input_ev(Ev::Input, Msg::NewRoomNameChanged),
+        ],
+        input![
+            attrs! {
+                At::Placeholder => "Topic",
+                At::Value => new_room.topic.as_deref().unwrap_or(""),
+            },
+            input_ev(Ev::Input, Msg::NewRoomTopicChanged),
+        ],
+        button!["Create", attrs! { At::Type => "submit" }],
+    ]
+}
+
+fn new_conversation_form(participants: &str) -> Node<Msg> {
+    form![
+        ev(Ev::Submit, |event| {
+            event.prevent_default();
+            Msg::OpenConversation
+        }),
+        input![
+            attrs! {
+                At::Required => true,
+                At::Placeholder => "Usernames, separated by commas",
+                At::Value => participants,
+            },
+            input_ev(Ev::Input, Msg::NewConversationChanged),
+        ],
+        button!["Message", attrs! { At::Type => "submit" }],
+    ]
+}
+
+fn conversation_item(summary: &RoomSummary, model: &Model) -> Node<Msg> {
+    let key = summary.room.key.clone();
+    let is_current = model.current.as_ref() == Some(&key);
+    li![
+        C![IF!(is_current => "active-route")],
+        summary
+            .room
+            .participants
+            .iter()
+            .filter(|username| Some(*username) != model.me.as_ref())
+            .map(|username| presence_dot(model, username)),
+        span![
+            C!["room-name"],
+            room_title(&summary.room, model.me.as_ref()),
+            ev(Ev::Click, move |_| Msg::Enter(key)),
+        ],
+        unread_badge(summary.unread),
+    ]
+}
+
+/// A direct conversation is named after the other participants
+fn room_title(room: &Room, me: Option<&String>) -> String {
+    match room.kind {
+        RoomKind::Public => room.name.clone(),
+        RoomKind::Direct => room
+            .participants
+            .iter()
+            .filter(|username| Some(*username) != me)
+            .cloned()
+            .collect::<Vec<String>>()
+            .join(", "),
+    }
+}
+
+fn room_item(summary: &RoomSummary, current: Option<&String>) -> Node<Msg> {
+    let key = summary.room.key.clone();
+    let is_current = current == Some(&key);
+    li![
+        C![IF!(is_current => "active-route")],
+        span![
+            C!["room-name"],
+            &summary.room.name,
+            IF!(summary.joined => {
+                let key = key.clone();
+                ev(Ev::Click, move |_| Msg::Enter(key))
+            }),
+        ],
+        span![C!["room-members"], format!(" ({})", summary.members)],
+        unread_badge(summary.unread),
+        if summary.joined {
+            button!["Leave", ev(Ev::Click, move |_| Msg::Leave(key))]
+        } else {
+            button!["Join", ev(Ev::Click, move |_| Msg::Join(key))]
+        },
+    ]
+}
+
+fn timeline(summary: &RoomSummary, model: &Model, can_moderate: bool) -> Node<Msg> {
+    div![
+        h2![room_title(&summary.room, model.me.as_ref())],
+        summary
+            .room
+            .topic
+            .as_ref()
+            .map(|topic| p![C!["topic"], topic]),
+        ul![
+            C!["messages"],
+            el_ref(&model.timeline),
+            ev(Ev::Scroll, |_| Msg::TimelineScrolled),
+            IF!(model.loading_older => li![C!["centred"], "Loading older messages…"]),
+            IF!(model.older.is_none() && !model.messages.is_empty() =>
+                li![C!["centred"], "This is the start of the room"]),
+            model
+                .messages
+                .iter()
+                .map(|message| message_item(message, model, can_moderate, true))
+        ],
+        typing_line(&summary.room.key, model),
+        form![
+            C!["composer"],
+            ev(Ev::Submit, |event| {
+                event.prevent_default();
+                Msg::Send
+            }),
+            input![
+                attrs! {
+                    At::Required => true,
+                    At::Placeholder => "Write a message",
+                    At::Value => model.draft,
+                },
+                input_ev(Ev::Input, Msg::DraftChanged),
+            ],
+            button!["Send", attrs! { At::Type => "submit" }],
+        ],
+    ]
+}
+
+/// The message starting a thread above its replies
+fn thread_view(thread: &ThreadView, model: &Model, can_moderate: bool) -> Node<Msg> {
+    aside![
+        C!["chat-thread"],
+        h3![
+            "Thread ",
+            button!["Close", ev(Ev::Click, |_| Msg::CloseThread)],
+        ],
+        ul![
+            C!["messages"],
+            model
+                .messages
+                .iter()
+                .find(|m| m.key == thread.root)
+                .map(|root| message_item(root, model, can_moderate, false)),
+            IF!(thread.older.is_some() => li![
+                C!["centred"],
+                button!["Earlier replies", ev(Ev::Click, |_| Msg::OlderRepliesRequested)],
+            ]),
+            thread
+                .replies
+                .iter()
+                .map(|reply| message_item(reply, model, can_moderate, false)),
+        ],
+        form![
+            C!["composer"],
+            ev(Ev::Submit, |event| {
+                event.prevent_default();
+                Msg::SendReply
+            }),
+            input![
+                attrs! {
+                    At::Required => true,
+                    At::Placeholder => "Reply in the thread",
+                    At::Value => thread.draft,
+                },
+                input_ev(Ev::Input, Msg::ReplyChanged),
+            ],
+            button!["Reply", attrs! { At::Type => "submit" }],
+        ],
+    ]
+}
+
+/// Authors change their own messages, moderators delete any of them, the
+/// server tells when it is too late
+/// In the timeline, `threaded` messages show their thread and can be replied to
+fn message_item(message: &Message, model: &Model, can_moderate: bool, threaded: bool) -> Node<Msg> {
+    let own = Some(&message.author) == model.me.as_ref();
+    let editing = model
+        .editing
+        .as_ref()
+        .filter(|(key, _)| key == &message.key);
+    let body = match editing {
+        _ if message.deleted => span![C!["deleted"], "This message was deleted"],
+        Some((_, draft)) => span![form![
+            C!["message-edit"],
+            ev(Ev::Submit, |event| {
+                event.prevent_default();
+                Msg::SaveEdit
+            }),
+            input![
+                attrs! { At::Required => true, At::Value => draft },
+                input_ev(Ev::Input, Msg::EditChanged),
+            ],
+            button!["Save", attrs! { At::Type => "submit" }],
+            button![
+                "Cancel",
+                attrs! { At::Type => "button" },
+                ev(Ev::Click, |_| Msg::CancelEdit),
+            ],
+        ]],
+        None => span![
+            &message.body,
+            message
+                .edited_at
+                .as_ref()
+                .map(|_| span![C!["edited"], " (edited)"]),
+        ],
+    };
+    let key = message.key.clone();
+    let actions = IF!(!message.deleted && editing.is_none() => span![
+        C!["message-actions"],
+        IF!(own => {
+            let key = key.clone();
+            button!["Edit", ev(Ev::Click, move |_| Msg::StartEdit(key))]
+        }),
+        IF!(threaded => {
+            let key = key.clone();
+            button!["Reply", ev(Ev::Click, move |_| Msg::OpenThread(key))]
+        }),
+        IF!(own || can_moderate => button!["Delete", ev(Ev::Click, move |_| Msg::Delete(key))]),
+    ]);
+    li![
+        span![C!["time"], time_of(&message.created_at)],
+        presence_dot(model, &message.author),
+        strong![&message.author],
+        span![" "],
+        body,
+        actions,
+        IF!(!message.deleted => reactions(message, model)),
+        IF!(threaded => thread_summary(message)),
+        seen_by(model, &message.key),
+    ]
+}
+
+/// How many replies a message has and a preview of the last one, opening the
+/// thread
+fn thread_summary(message: &Message) -> Option<Node<Msg>> {
+    let thread = message.thread.as_ref()?;
+    let last = &thread.last_reply;
+    let key = message.key.clone();
+    Some(p![
+        C!["thread-summary"],
+        button![
+            if thread.replies == 1 {
+                "1 reply".to_string()
+            } else {
+                format!("{} replies", thread.replies)
+            },
+            ev(Ev::Click, move |_| Msg::OpenThread(key)),
+        ],
+        span![
+            C!["last-reply"],
+            format!(
+                " {} {}: {}",
+                time_of(&last.created_at),
+                last.author,
+                if last.deleted {
+                    "This message was deleted"
+                } else {
+                    last.body.as_str()
+                }
+            ),
+        ],
+    ])
+}
+
+/// The reactions of a message with how many users chose them, then the quick
+/// ones nobody chose yet
+fn reactions(message: &Message, model: &Model) -> Node<Msg> {
+    let toggle = |emoji: &str| {
+        let (message, emoji) = (message.key.clone(), emoji.to_string());
+        ev(Ev::Click, move |_| Msg::ToggleReaction { message, emoji })
+    };
+    div![
+        C!["reactions"],
+        message.reactions.iter().map(|reaction| {
+            let mine = matches!(&model.me, Some(me) if reaction.users.contains(me));
+            button![
+                C!["reaction", IF!(mine => "mine")],
+                attrs! { At::Title => reaction.users.join(", ") },
+                format!("{} {}", reaction.emoji, reaction.users.len()),
+                toggle(&reaction.emoji),
+            ]
+        }),
+        QUICK_REACTIONS
+            .iter()
+            .filter(|emoji| !message.reactions.iter().any(|r| r.emoji == **emoji))
+            .map(|emoji| button![C!["reaction", "quick"], *emoji, toggle(emoji)]),
+    ]
+}
+
+fn unread_badge(unread: u64) -> Option<Node<Msg>> {
+    if unread == 0 {
+        None
+    } else {
+        Some(span![C!["unread-badge"], unread.to_string()])
+    }
+}
+
+/// The other members whose last read message is this one
+fn seen_by(model: &Model, message: &str) -> Option<Node<Msg>> {
+    let mut readers: Vec<&str> = model
+        .receipts
+        .iter()
+        .filter(|(username, read)| read.as_str() == message && Some(*username) != model.me.as_ref())
+        .map(|(username, _)| username.as_str())
+        .collect();
+    if readers.is_empty() {
+        return None;
+    }
+    readers.sort();
+    Some(p![C!["seen-by"], format!("Seen by {}", readers.join(", "))])
+}
+
+fn presence_dot(model: &Model, username: &str) -> Node<Msg> {
+    let status = model
+        .presence
+        .get(username)
+        .copied()
+        .unwrap_or(PresenceStatus::Offline);
+    span![
+        C!["presence", status.as_str()],
+        attrs! { At::Title => status.as_str() },
+    ]
+}
+
+fn typing_line(room: &str, model: &Model) -> Node<Msg> {
+    let usernames: Vec<&str> = model
+        .typing
+        .iter()
+        .filter(|typing| typing.room == room)
+        .map(|typing| typing.username.as_str())
+        .collect();
+    let text = match usernames.as_slice() {
+        [] => String::new(),
+        [username] => format!("{} is typing…", username),
+        [first, second] => format!("{} and {} are typing…", first, second),
+        _ => "Several people are typing…".to_string(),
+    };
+    p![C!["typing"], text]
+}
+
+/// Hours and minutes of an ISO date
+fn time_of(date: &str) -> &str {
+    date.get(11..16).unwrap_or(date)
+}
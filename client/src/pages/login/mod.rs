@@ -1,11 +1,17 @@
-use crate::{request::RequestState, Msg as RootMsg};
+use crate::{field_errors, request::RequestState, Msg as RootMsg};
 use seed::{prelude::*, *};
-use shared::models::{auth::LoginCredentials, user::LoggedUser};
+use shared::{
+    i18n::Language,
+    models::{auth::LoginCredentials, policy::PasswordPolicy, user::LoggedUser},
+    validation::{validate_login, Field, FieldError, EMAIL_MAX_LENGTH},
+};
 
 #[derive(Default)]
 pub struct Model {
     credentials: LoginCredentials,
     request_state: RequestState<LoggedUser>,
+    /// Fields edited by the user, the others do not show their errors yet
+    touched: Vec<Field>,
 }
 
 pub enum Msg {
@@ -49,12 +55,22 @@ pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
             model.request_state = RequestState::Failed { message, code }
         }
         Msg::PasswordChanged(pwd) => {
+            touch(model, Field::Password);
             model.credentials.set_password(pwd);
         }
-        Msg::TargetChanged(target) => model.credentials.set_target(target),
+        Msg::TargetChanged(target) => {
+            touch(model, Field::Target);
+            model.credentials.set_target(target)
+        }
+    }
+}
+fn touch(model: &mut Model, field: Field) {
+    if !model.touched.contains(&field) {
+        model.touched.push(field);
     }
 }
-pub fn view(model: &Model) -> Node<Msg> {
+
+pub fn view(model: &Model, policy: &PasswordPolicy, language: Language) -> Node<Msg> {
     match &model.request_state {
         RequestState::Success(user) => div![p![
             C!["centred"],
@@ -63,7 +79,7 @@ pub fn view(model: &Model) -> Node<Msg> {
             user.username(),
             ". :)"
         ]],
-        RequestState::IsPending(status) => form(model, status),
+        RequestState::IsPending(status) => form(model, policy, language, status),
         RequestState::Failed { message, code } => p![
             C!["centred"],
             format!("An error happened {} with the code {}", message, code)
@@ -71,7 +87,13 @@ pub fn view(model: &Model) -> Node<Msg> {
     }
 }
 
-fn form(model: &Model, status: &bool) -> Node<Msg> {
+fn form(model: &Model, policy: &PasswordPolicy, language: Language, status: &bool) -> Node<Msg> {
+    let errors: Vec<FieldError> = validate_login(&model.credentials);
+    let valid = errors.is_empty();
+    let errors: Vec<FieldError> = errors
+        .into_iter()
+        .filter(|error| model.touched.contains(&error.field))
+        .collect();
     form![
         ev(Ev::Submit, |event| {
             event.prevent_default();
@@ -88,30 +110,32 @@ fn form(model: &Model, status: &bool) -> Node<Msg> {
                 attrs! {
                 At::Required => true,
                 At::Value=> model.credentials.target(),
-                At::MinLength=> "5",
                 At::Name => "username",
-                At::MaxLength=> "25",
+                At::MaxLength=> EMAIL_MAX_LENGTH,
                 At::Type=> "text"
                         },
                 input_ev(Ev::Input, Msg::TargetChanged),
             ],
+            field_errors::view(&errors, Field::Target, language),
             label![attrs! { At::For => "password"}, "Password"],
             input![
                 id!("password"),
                 attrs! {
                     At::Required => true,
-                    At::MinLength=> "8",
-                    At::MaxLength=> "30"
+                    At::MinLength=> policy.min_length,
+                    At::MaxLength=> policy.max_length
                     At::Value => model.credentials.password(),
                     At::Name => "password",
                     At::Type=> "password"
                 },
                 input_ev(Ev::Input, Msg::PasswordChanged),
             ],
+            field_errors::view(&errors, Field::Password, language),
         ],
         button![
             "Login",
             attrs! {
+            At::Disabled=> (!valid && !(*status)).as_at_value(),
             At::Type=> "submit"
                     },
         ],
@@ -0,0 +1,83 @@
+use seed::{prelude::*, *};
+use shared::models::login::LoginEntry;
+
+#[derive(Default)]
+pub struct Model {
+    logins: Vec<LoginEntry>,
+    is_loading: bool,
+    error: Option<String>,
+}
+
+/// Action on login history page
+pub enum Msg {
+    Fetch,
+    Fetched(Vec<LoginEntry>),
+    RequestFailed { message: String, code: String },
+}
+
+/// Update on login history page
+pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
+    match msg {
+        Msg::Fetch => {
+            model.is_loading = true;
+            orders.perform_cmd(async {
+                let response = fetch("/api/me/logins").await.expect("HTTP request failed");
+                if response.status().is_ok() {
+                    Msg::Fetched(response.json().await.unwrap())
+                } else {
+                    Msg::RequestFailed {
+                        code: response.status().code.to_string(),
+                        message: response.text().await.unwrap_or_default(),
+                    }
+                }
+            });
+        }
+        Msg::Fetched(logins) => {
+            model.is_loading = false;
+            model.error = None;
+            model.logins = logins;
+        }
+        Msg::RequestFailed { message, code } => {
+            model.is_loading = false;
+            model.error = Some(format!(
+                "An error happened {} with the code {}",
+                message, code
+            ));
+        }
+    }
+}
+
+/// view of login history page
+pub fn view(model: &Model) -> Node<Msg> {
+    div![
+        C!["logins"],
+        h2!["Where your account has been used"],
+        IF!(model.is_loading => div![C!["lds-ring"], div![], div![], div![], div![]]),
+        model.error.as_ref().map(|error| p![C!["centred"], error]),
+        table![
+            thead![tr![
+                th!["Date"],
+                th!["Browser"],
+                th!["System"],
+                th!["Network"],
+                th!["Address"],
+                th![""],
+            ]],
+            tbody![model.logins.iter().map(|login| tr![
+                C![IF!(login.current => "current-login")],
+                td![&login.created_at],
+                td![&login.device.browser],
+                td![&login.device.os],
+                td![&login.device.ip_prefix],
+                td![login.ip.as_deref().unwrap_or("-")],
+                td![if login.current {
+                    "This session"
+                } else if login.new_device {
+                    "New device"
+                } else {
+                    ""
+                }],
+            ])],
+        ],
+    ]
+}
@@ -1,3 +1,7 @@
+pub mod admin;
+pub mod chat;
 pub mod dashboard;
 pub mod login;
+pub mod logins;
+pub mod password;
 pub mod register;
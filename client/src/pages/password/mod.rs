@@ -0,0 +1,174 @@
+use crate::{policy_checklist, power_bar, request::RequestState};
+use seed::{prelude::*, *};
+use shared::{
+    i18n::Language,
+    models::{auth::PasswordChange, policy::PasswordPolicy, power::Power, user::LoggedUser},
+    strength::{estimate, feedback::Feedback},
+};
+
+#[derive(Default)]
+pub struct Model {
+    form: PasswordChange,
+    confirmation: String,
+    password_power: Power,
+    password_feedback: Feedback,
+    request_state: RequestState<String>,
+}
+
+/// Action on password change page
+pub enum Msg {
+    Change,
+    ChangeFailed { message: String, code: String },
+    ChangeSucceed(String),
+    CurrentPasswordChanged(String),
+    NewPasswordChanged(String),
+    ConfirmationChanged(String),
+}
+
+/// Update on password change page
+/// The logged user is needed to find its personal information in the password
+pub fn update(
+    msg: Msg,
+    model: &mut Model,
+    logged_user: Option<&LoggedUser>,
+    language: Language,
+    orders: &mut impl Orders<Msg>,
+) {
+    match msg {
+        Msg::Change => {
+            model.request_state = RequestState::IsPending(true);
+            let request = Request::new("/api/password/change")
+                .method(Method::Post)
+                .header(Header::custom("Accept-Language", language.code()))
+                .json(&model.form)
+                .expect("Serialization failed");
+            model.form = PasswordChange::default();
+            model.confirmation = "".to_string();
+            orders.perform_cmd(async {
+                let response = fetch(request).await.expect("HTTP request failed");
+
+                if response.status().is_ok() {
+                    Msg::ChangeSucceed(response.json().await.unwrap())
+                } else {
+                    Msg::ChangeFailed {
+                        message: response.text().await.unwrap(),
+                        code: response.status().code.to_string(),
+                    }
+                }
+            });
+        }
+        Msg::CurrentPasswordChanged(text) => model.form.set_current_password(text),
+        Msg::NewPasswordChanged(text) => {
+            let text = text.trim();
+            model.form.set_new_password(text.to_string());
+            let estimate = estimate(model.form.new_password(), &user_inputs(logged_user));
+            model.password_power = Power::from_estimate(&estimate);
+            model.password_feedback = estimate.feedback;
+        }
+        Msg::ConfirmationChanged(text) => model.confirmation = text.trim().to_string(),
+        Msg::ChangeFailed { message, code } => {
+            model.request_state = RequestState::Failed { message, code }
+        }
+        Msg::ChangeSucceed(message) => model.request_state = RequestState::Success(message),
+    }
+}
+
+/// What an attacker could know about the user
+fn user_inputs(logged_user: Option<&LoggedUser>) -> Vec<&str> {
+    logged_user.map_or(Vec::new(), |user| {
+        vec![
+            user.username(),
+            user.email(),
+            user.first_name.as_str(),
+            user.last_name.as_str(),
+        ]
+    })
+}
+
+/// view of password change page
+pub fn view(
+    model: &Model,
+    policy: &PasswordPolicy,
+    logged_user: Option<&LoggedUser>,
+    language: Language,
+) -> Node<Msg> {
+    match &model.request_state {
+        RequestState::Success(message) => p![C!["centred"], message],
+        RequestState::IsPending(status) => form(model, policy, logged_user, language, status),
+        RequestState::Failed { message, code } => p![
+            C!["centred"],
+            format!("An error happened {} with the code {}", message, code)
+        ],
+    }
+}
+
+fn form(
+    model: &Model,
+    policy: &PasswordPolicy,
+    logged_user: Option<&LoggedUser>,
+    language: Language,
+    status: &bool,
+) -> Node<Msg> {
+    let power = &model.password_power;
+    let checks = policy.check(model.form.new_password(), &user_inputs(logged_user));
+    let policy_satisfied = checks.iter().all(|check| check.passed);
+    let confirmed = model.confirmation == model.form.new_password();
+    form![
+        ev(Ev::Submit, |event| {
+            event.prevent_default();
+            Msg::Change
+        }),
+        fieldset![
+            attrs! {
+                        At::Disabled=> status.as_at_value(),
+            },
+            legend!["Change password"],
+            label![attrs! { At::For => "current_password"}, "Current password"],
+            input![
+                id!("current_password"),
+                attrs! {
+                    At::Required => true,
+                    At::Value => model.form.current_password(),
+                    At::Name => "current_password",
+                    At::Type=> "password"
+                },
+                input_ev(Ev::Input, Msg::CurrentPasswordChanged),
+            ],
+            label![attrs! { At::For => "new_password"}, "New password"],
+            input![
+                id!("new_password"),
+                attrs! {
+                    At::Required => true,
+                    At::MinLength=> policy.min_length,
+                    At::MaxLength=> policy.max_length
+                    At::Value => model.form.new_password(),
+                    At::Name => "new_password",
+                    At::Type=> "password"
+                },
+                input_ev(Ev::Input, Msg::NewPasswordChanged),
+            ],
+            power_bar::view(power, &model.password_feedback, language),
+            policy_checklist::view(&checks, language),
+            label![attrs! { At::For => "confirmation"}, "Confirm new password"],
+            input![
+                id!("confirmation"),
+                attrs! {
+                    At::Required => true,
+                    At::Value => model.confirmation,
+                    At::Name => "confirmation",
+                    At::Type=> "password"
+                },
+                input_ev(Ev::Input, Msg::ConfirmationChanged),
+            ],
+            IF!(!confirmed => p!["The passwords do not match"]),
+        ],
+        button![
+            "Change password",
+            attrs! {
+            At::Disabled=> ((!policy_satisfied || !confirmed) && !(*status)).as_at_value(),
+            At::Type=> "submit"
+                    },
+        ],
+        IF!(*status =>  div![C!["lds-ring"], div![], div![], div![], div![]] )
+    ]
+}
@@ -1,12 +1,25 @@
-use crate::request::RequestState;
+use crate::{field_errors, policy_checklist, power_bar, request::RequestState};
 use seed::{prelude::*, *};
-use shared::models::{power::Power, user::User};
+use shared::{
+    i18n::Language,
+    models::{policy::PasswordPolicy, power::Power, user::User},
+    strength::{estimate, feedback::Feedback},
+    validation::{
+        validate_user, Field, FieldError, EMAIL_MAX_LENGTH, NAME_MAX_LENGTH, USERNAME_MAX_LENGTH,
+        USERNAME_MIN_LENGTH,
+    },
+};
 
 #[derive(Default)]
 pub struct Model {
     user: User,
     password_power: Power,
+    password_feedback: Feedback,
     request_state: RequestState<User>,
+    /// Fields edited by the user, the others do not show their errors yet
+    touched: Vec<Field>,
+    /// Errors sent back by the server on the last registration
+    server_errors: Vec<FieldError>,
 }
 
 /// Action on register page
@@ -14,6 +27,7 @@ pub enum Msg {
     Register,
     RegisterFailed { message: String, code: String },
     RegisterSucceed(User),
+    RegisterRejected(Vec<FieldError>),
     PasswordChanged(String),
     UsernameChanged(String),
     EmailChanged(String),
@@ -23,12 +37,14 @@ pub enum Msg {
     Clear,
 }
 /// Update on register pages
-pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
+/// The language is sent so the server explains a rejected password in it
+pub fn update(msg: Msg, model: &mut Model, language: Language, orders: &mut impl Orders<Msg>) {
     match msg {
         Msg::Register => {
             model.request_state = RequestState::IsPending(true);
             let request = Request::new("/api/register")
                 .method(Method::Post)
+                .header(Header::custom("Accept-Language", language.code()))
                 .json(&model.user)
                 .expect("Serialization failed");
             model.user.credentials.set_password("".to_string());
@@ -37,6 +53,8 @@ pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
 
                 if response.status().is_ok() {
                     Msg::RegisterSucceed(response.json().await.unwrap())
+                } else if response.status().code == 422 {
+                    Msg::RegisterRejected(response.json().await.unwrap_or_default())
                 } else {
                     Msg::RegisterFailed {
                         message: response.text().await.unwrap(),
@@ -47,27 +65,69 @@ pub fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
         }
         Msg::Clear => {}
         Msg::PasswordChanged(text) => {
+            touch(model, Field::Password);
             let text = text.trim();
             model.user.credentials.set_password(text.to_string());
-            model.password_power = Power::rank(Power::calculate_power(
-                model.user.credentials.password().to_string(),
-            ));
+            let estimate = estimate(model.user.credentials.password(), &user_inputs(&model.user));
+            model.password_power = Power::from_estimate(&estimate);
+            model.password_feedback = estimate.feedback;
+        }
+        Msg::UsernameChanged(text) => {
+            touch(model, Field::Username);
+            model.user.credentials.set_username(text.trim().to_string())
+        }
+        Msg::FirstNameChanged(text) => {
+            touch(model, Field::FirstName);
+            model.user.first_name = text.trim().to_string()
+        }
+        Msg::LastNameChanged(text) => {
+            touch(model, Field::LastName);
+            model.user.last_name = text.trim().to_string()
         }
-        Msg::UsernameChanged(text) => model.user.credentials.set_username(text.trim().to_string()),
-        Msg::FirstNameChanged(text) => model.user.first_name = text.trim().to_string(),
-        Msg::LastNameChanged(text) => model.user.last_name = text.trim().to_string(),
         Msg::EmailChanged(text) => {
+            touch(model, Field::Email);
             model.user.credentials.set_email(text.trim().to_string());
         }
         Msg::RegisterFailed { message, code } => {
             model.request_state = RequestState::Failed { message, code }
         }
         Msg::RegisterSucceed(user) => model.request_state = RequestState::Success(user),
+        Msg::RegisterRejected(errors) => {
+            model.request_state = RequestState::IsPending(false);
+            model.server_errors = errors;
+        }
+    }
+}
+
+/// Show the errors of an edited field, the server errors are outdated now
+fn touch(model: &mut Model, field: Field) {
+    if !model.touched.contains(&field) {
+        model.touched.push(field);
     }
+    model.server_errors.retain(|error| error.field != field);
+}
+
+/// Errors to show, the ones of untouched fields are hidden
+fn visible_errors(model: &Model) -> Vec<FieldError> {
+    validate_user(&model.user)
+        .into_iter()
+        .filter(|error| model.touched.contains(&error.field))
+        .chain(model.server_errors.iter().cloned())
+        .collect()
+}
+
+/// What an attacker could know about the user
+fn user_inputs(user: &User) -> [&str; 4] {
+    [
+        user.credentials.username(),
+        user.credentials.email(),
+        &user.first_name,
+        &user.last_name,
+    ]
 }
 
 /// view of register page
-pub fn view(model: &Model) -> Node<Msg> {
+pub fn view(model: &Model, policy: &PasswordPolicy, language: Language) -> Node<Msg> {
     match &model.request_state {
         RequestState::Success(user) => div![
             C!["centred"],
@@ -87,7 +147,7 @@ pub fn view(model: &Model) -> Node<Msg> {
                 ]
             ]
         ],
-        RequestState::IsPending(status) => form(model, status),
+        RequestState::IsPending(status) => form(model, policy, language, status),
         RequestState::Failed { message, code } => p![
             C!["centred"],
             format!("An error happened {} with the code {}", message, code)
@@ -95,9 +155,13 @@ pub fn view(model: &Model) -> Node<Msg> {
     }
 }
 
-fn form(model: &Model, status: &bool) -> Node<Msg> {
+fn form(model: &Model, policy: &PasswordPolicy, language: Language, status: &bool) -> Node<Msg> {
     let user = &model.user;
     let power = &model.password_power;
+    let checks = policy.check(user.credentials.password(), &user_inputs(user));
+    let policy_satisfied = checks.iter().all(|check| check.passed);
+    let valid = validate_user(user).is_empty();
+    let errors = visible_errors(model);
     form![
         ev(Ev::Submit, |event| {
             event.prevent_default();
@@ -114,46 +178,43 @@ fn form(model: &Model, status: &bool) -> Node<Msg> {
                 attrs! {
                 At::Required => true,
                 At::Value=> user.credentials.username(),
-                At::MinLength=> "5",
+                At::MinLength=> USERNAME_MIN_LENGTH,
                 At::Name => "username",
-                At::MaxLength=> "25",
+                At::MaxLength=> USERNAME_MAX_LENGTH,
                 At::Type=> "text"
                         },
                 input_ev(Ev::Input, Msg::UsernameChanged),
             ],
+            field_errors::view(&errors, Field::Username, language),
             label![attrs! { At::For => "email"}, "Email"],
             input![
                 id!("email"),
                 attrs! {
                 At::Required => true,
                 At::Value => user.credentials.email(),
-                At::MinLength=> "5",
-                At::MaxLength=> "25"
+                At::MaxLength=> EMAIL_MAX_LENGTH,
                 At::Name => "email",
                 At::Type=> "email"
                    },
                 input_ev(Ev::Input, Msg::EmailChanged),
             ],
+            field_errors::view(&errors, Field::Email, language),
             label![attrs! { At::For => "password"}, "Password"],
             input![
                 id!("password"),
                 attrs! {
                     At::Required => true,
-                    At::MinLength=> "8",
-                    At::MaxLength=> "30"
+                    At::MinLength=> policy.min_length,
+                    At::MaxLength=> policy.max_length
                     At::Value => user.credentials.password(),
                     At::Name => "password",
                     At::Type=> "password"
                 },
                 input_ev(Ev::Input, Msg::PasswordChanged),
             ],
-            p![format!("Password power => {} ", power.display())],
-            div![
-                C![power.class(), "power"],
-                IF!(power.units().eq(&0) => i![C!["fas fa-skull-crossbones tiny−avocado-icons", power.class()]]),
-                style! {St::Width => format!("{}px", power.units())
-                },
-            ]
+            field_errors::view(&errors, Field::Password, language),
+            power_bar::view(power, &model.password_feedback, language),
+            policy_checklist::view(&checks, language),
         ],
         fieldset![
             attrs! {
@@ -165,31 +226,34 @@ fn form(model: &Model, status: &bool) -> Node<Msg> {
                 id!("first_name"),
                 attrs! {
                 At::Required => true,
+                At::MaxLength=> NAME_MAX_LENGTH,
                 At::Name => "first_name",
                 At::Type=> "text",
                 At::Value=> user.first_name,
                        },
                 input_ev(Ev::Input, Msg::FirstNameChanged),
             ],
+            field_errors::view(&errors, Field::FirstName, language),
             br![],
             label![attrs! { At::For => "last_name"}, "Last Name"],
             input![
                 id!("last_name"),
                 attrs! {
                 At::Required => true,
-                At::MaxLength=> "15"
+                At::MaxLength=> NAME_MAX_LENGTH,
                 At::Name => "last_name",
                 At::Type=> "text"
                 At::Value=> user.last_name,
                        },
                 input_ev(Ev::Input, Msg::LastNameChanged),
             ],
+            field_errors::view(&errors, Field::LastName, language),
             br![],
         ],
         button![
             "Register",
             attrs! {
-            At::Disabled=> (power.units()<&101 && !(*status)).as_at_value(),
+            At::Disabled=> ((!policy_satisfied || !valid) && !(*status)).as_at_value(),
             At::Type=> "submit"
                     },
         ],
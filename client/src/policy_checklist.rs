@@ -0,0 +1,27 @@
+use seed::{prelude::*, *};
+use shared::{i18n::Language, models::policy::RuleCheck};
+
+/// Live checklist of the password policy rules
+pub fn view<Ms: 'static>(checks: &[RuleCheck], language: Language) -> Node<Ms> {
+    ul![
+        C!["policy"],
+        checks.iter().map(|check| {
+            li![
+                C![if check.passed {
+                    "rule-passed"
+                } else {
+                    "rule-failed"
+                }],
+                i![C![
+                    "tiny−avocado-icons",
+                    if check.passed {
+                        "fas fa-check"
+                    } else {
+                        "fas fa-times"
+                    }
+                ]],
+                check.rule.message().translate(language),
+            ]
+        })
+    ]
+}
@@ -0,0 +1,32 @@
+use seed::{prelude::*, *};
+use shared::{
+    i18n::{Language, Message},
+    models::power::Power,
+    strength::feedback::Feedback,
+};
+
+/// Live strength bar for a password being typed, with advices to make it
+/// stronger
+pub fn view<Ms: 'static>(power: &Power, feedback: &Feedback, language: Language) -> Vec<Node<Ms>> {
+    let class = power_class(power);
+    let label = Message::new("power.label").with("message", power.message().translate(language));
+    vec![
+        p![label.translate(language)],
+        div![
+            C![&class, "power"],
+            IF!(power.units().eq(&0) => i![C!["fas fa-skull-crossbones tiny−avocado-icons", &class]]),
+            style! {St::Width => format!("{}px", power.units())
+            },
+        ],
+        IF!(feedback.warning.is_some() => p![C!["power-warning"], feedback.warning.map(|w| w.message().translate(language))]),
+        ul![feedback.suggestions.iter().map(|suggestion| li![
+            C!["power-suggestion"],
+            suggestion.message().translate(language)
+        ])],
+    ]
+}
+
+/// Css class of a power level, ex: `baby-power`
+fn power_class(power: &Power) -> String {
+    power.key().replace('_', "-")
+}
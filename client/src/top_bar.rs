@@ -15,6 +15,8 @@ pub struct TopBar<Ms: 'static> {
     user_logged_in: bool,
     disabled: bool,
     content: Vec<Node<Ms>>,
+    /// Unread chat messages, shown as a badge after the title
+    unread: u64,
     el_ref: ElRef<HtmlElement>,
     css: Css,
 }
@@ -80,6 +82,11 @@ impl<Ms: 'static> TopBar<Ms> {
         self
     }
 
+    pub const fn unread(mut self, unread: u64) -> Self {
+        self.unread = unread;
+        self
+    }
+
     pub fn el_ref(mut self, el_ref: &ElRef<HtmlElement>) -> Self {
         self.el_ref = el_ref.clone();
         self
@@ -89,6 +96,11 @@ impl<Ms: 'static> TopBar<Ms> {
         let tag = Tag::Div;
 
         let content = self.title.take().map(Node::new_text);
+        let badge = if self.unread > 0 {
+            Some(span![C!["unread-badge"], self.unread.to_string()])
+        } else {
+            None
+        };
 
         // if content.is_some() {
         //     let mut node = content.unwrap();
@@ -157,6 +169,7 @@ impl<Ms: 'static> TopBar<Ms> {
             attrs,
             self.attrs,
             content,
+            badge,
             self.content,
         ];
 
@@ -174,6 +187,7 @@ impl<Ms> Default for TopBar<Ms> {
             user_logged_in: false,
             disabled: false,
             content: Vec::new(),
+            unread: 0,
             el_ref: ElRef::default(),
             css: Css::empty(),
         }
@@ -0,0 +1,455 @@
+use actix::prelude::*;
+use shared::{
+    models::presence::{Presence, PresenceStatus},
+    protocol::ServerEvent,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+/// A connection without heartbeat for this long does not count for presence
+const PRESENCE_TTL: Duration = Duration::from_secs(90);
+/// How often expired presences are looked for
+const PRESENCE_SWEEP_INTERVAL: Duration = Duration::from_secs(15);
+
+/// An event to send to a connection
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Push(pub ServerEvent);
+
+/// Close a connection, the session it was opened with is revoked
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Close;
+
+/// A new connection of a user, answered with its id
+#[derive(Message)]
+#[rtype(result = "usize")]
+pub struct Connect {
+    /// Canonical username of the user behind the connection
+    pub username: String,
+    /// Key of the login session the connection was opened with
+    pub session: String,
+    /// Username as displayed
+    pub name: String,
+    /// Keys of the rooms the user is a member of, their subscribers are told
+    /// about its presence
+    pub rooms: Vec<String>,
+    pub recipient: Recipient<Push>,
+    pub closer: Recipient<Close>,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Disconnect {
+    pub id: usize,
+}
+
+/// Start sending the events of a room to a connection, the membership is
+/// checked by the caller
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Subscribe {
+    pub id: usize,
+    pub room: String,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Unsubscribe {
+    pub id: usize,
+    pub room: String,
+}
+
+/// The client of a connection is alive, `active` if its user is there
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Heartbeat {
+    pub id: usize,
+    pub active: bool,
+}
+
+/// The user of a connection is writing in a room it is subscribed to
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Typing {
+    pub id: usize,
+    pub room: String,
+}
+
+/// Presence of the users among `usernames` who are not offline
+#[derive(Message)]
+#[rtype(result = "Vec<Presence>")]
+pub struct GetPresence {
+    /// Canonical usernames
+    pub usernames: Vec<String>,
+}
+
+/// Send an event to every connection subscribed to a room
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Broadcast {
+    pub room: String,
+    pub event: ServerEvent,
+}
+
+/// Send an event to every connection of some users, subscribed or not
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Notify {
+    /// Canonical usernames
+    pub usernames: Vec<String>,
+    pub event: ServerEvent,
+}
+
+/// Which login sessions of a user were revoked
+pub enum RevokedSessions {
+    All,
+    /// Every session but this one
+    Others(String),
+    One(String),
+}
+
+impl RevokedSessions {
+    fn contains(&self, session: &str) -> bool {
+        match self {
+            RevokedSessions::All => true,
+            RevokedSessions::Others(kept) => kept != session,
+            RevokedSessions::One(revoked) => revoked == session,
+        }
+    }
+}
+
+/// Sessions of a user were revoked, the connections opened with them are
+/// closed
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct CloseSessions {
+    /// Canonical username
+    pub username: String,
+    pub revoked: RevokedSessions,
+}
+
+/// A user is not a member of a room anymore, none of its connections
+/// receive the events of the room
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RemoveMember {
+    pub room: String,
+    pub username: String,
+}
+
+struct Client {
+    username: String,
+    session: String,
+    recipient: Recipient<Push>,
+    closer: Recipient<Close>,
+    /// Last heartbeat, or the connection
+    seen_at: Instant,
+    active: bool,
+}
+
+/// What the other users were last told about a user
+struct UserPresence {
+    name: String,
+    status: PresenceStatus,
+    /// Rooms whose subscribers hear about the user
+    rooms: HashSet<String>,
+}
+
+/// Knows every WebSocket connection and the rooms they listen to, messages
+/// are fanned out from here
+#[derive(Default)]
+pub struct ChatServer {
+    clients: HashMap<usize, Client>,
+    /// Connections subscribed to each room
+    rooms: HashMap<String, HashSet<usize>>,
+    /// Users with at least one connection, or not yet announced offline
+    presence: HashMap<String, UserPresence>,
+    next_id: usize,
+}
+
+impl ChatServer {
+    fn push(&self, id: usize, event: ServerEvent) {
+        if let Some(client) = self.clients.get(&id) {
+            if client.recipient.do_send(Push(event)).is_err() {
+                eprintln!("Could not push to the connection {}", id);
+            }
+        }
+    }
+
+    /// true if the connection was subscribed
+    fn unsubscribe(&mut self, id: usize, room: &str) -> bool {
+        let subscribers = match self.rooms.get_mut(room) {
+            Some(subscribers) => subscribers,
+            None => return false,
+        };
+        let removed = subscribers.remove(&id);
+        if subscribers.is_empty() {
+            self.rooms.remove(room);
+        }
+        removed
+    }
+
+    /// Online if a live connection of the user is active, away if none is
+    fn status_of(&self, username: &str) -> PresenceStatus {
+        let now = Instant::now();
+        let live: Vec<&Client> = self
+            .clients
+            .values()
+            .filter(|client| {
+                client.username == username && now.duration_since(client.seen_at) <= PRESENCE_TTL
+            })
+            .collect();
+        if live.iter().any(|client| client.active) {
+            PresenceStatus::Online
+        } else if !live.is_empty() {
+            PresenceStatus::Away
+        } else {
+            PresenceStatus::Offline
+        }
+    }
+
+    /// Tell the subscribers of the rooms of a user when its status changed,
+    /// it is forgotten once offline
+    fn refresh_presence(&mut self, username: &str) {
+        let status = self.status_of(username);
+        let user = match self.presence.get_mut(username) {
+            Some(user) if user.status != status => user,
+            _ => return,
+        };
+        user.status = status;
+        let event = ServerEvent::PresenceChanged {
+            presence: Presence {
+                username: user.name.clone(),
+                status,
+            },
+        };
+        let (rooms, clients) = (&self.rooms, &self.clients);
+        let ids: HashSet<usize> = user
+            .rooms
+            .iter()
+            .filter_map(|room| rooms.get(room))
+            .flatten()
+            .filter(|id| matches!(clients.get(id), Some(client) if client.username != username))
+            .copied()
+            .collect();
+        for id in ids {
+            self.push(id, event.clone());
+        }
+        if status == PresenceStatus::Offline {
+            self.presence.remove(username);
+        }
+    }
+}
+
+impl Actor for ChatServer {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        ctx.run_interval(PRESENCE_SWEEP_INTERVAL, |server, _| {
+            let usernames: Vec<String> = server.presence.keys().cloned().collect();
+            for username in usernames {
+                server.refresh_presence(&username);
+            }
+        });
+    }
+}
+
+impl Handler<Connect> for ChatServer {
+    type Result = usize;
+
+    fn handle(&mut self, msg: Connect, _: &mut Context<Self>) -> usize {
+        self.next_id += 1;
+        let user = self
+            .presence
+            .entry(msg.username.clone())
+            .or_insert_with(|| UserPresence {
+                name: msg.name,
+                status: PresenceStatus::Offline,
+                rooms: HashSet::new(),
+            });
+        user.rooms.extend(msg.rooms);
+        self.clients.insert(
+            self.next_id,
+            Client {
+                username: msg.username.clone(),
+                session: msg.session,
+                recipient: msg.recipient,
+                closer: msg.closer,
+                seen_at: Instant::now(),
+                active: true,
+            },
+        );
+        self.refresh_presence(&msg.username);
+        self.next_id
+    }
+}
+
+impl Handler<Disconnect> for ChatServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: Disconnect, _: &mut Context<Self>) {
+        let client = self.clients.remove(&msg.id);
+        let rooms: Vec<String> = self.rooms.keys().cloned().collect();
+        for room in rooms {
+            self.unsubscribe(msg.id, &room);
+        }
+        if let Some(client) = client {
+            self.refresh_presence(&client.username);
+        }
+    }
+}
+
+impl Handler<CloseSessions> for ChatServer {
+    type Result = ();
+
+    /// The connections disconnect themselves once closed
+    fn handle(&mut self, msg: CloseSessions, _: &mut Context<Self>) {
+        let revoked = self.clients.values().filter(|client| {
+            client.username == msg.username && msg.revoked.contains(&client.session)
+        });
+        for client in revoked {
+            if client.closer.do_send(Close).is_err() {
+                eprintln!("Could not close a connection of {}", client.username);
+            }
+        }
+    }
+}
+
+impl Handler<Subscribe> for ChatServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: Subscribe, _: &mut Context<Self>) {
+        if let Some(client) = self.clients.get(&msg.id) {
+            if let Some(user) = self.presence.get_mut(&client.username) {
+                user.rooms.insert(msg.room.clone());
+            }
+            self.rooms.entry(msg.room).or_default().insert(msg.id);
+        }
+    }
+}
+
+impl Handler<Unsubscribe> for ChatServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: Unsubscribe, _: &mut Context<Self>) {
+        self.unsubscribe(msg.id, &msg.room);
+    }
+}
+
+impl Handler<Heartbeat> for ChatServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: Heartbeat, _: &mut Context<Self>) {
+        let username = match self.clients.get_mut(&msg.id) {
+            Some(client) => {
+                client.seen_at = Instant::now();
+                client.active = msg.active;
+                client.username.clone()
+            }
+            None => return,
+        };
+        self.refresh_presence(&username);
+    }
+}
+
+impl Handler<Typing> for ChatServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: Typing, _: &mut Context<Self>) {
+        let (username, subscribers) = match (self.clients.get(&msg.id), self.rooms.get(&msg.room)) {
+            (Some(client), Some(subscribers)) if subscribers.contains(&msg.id) => {
+                (&client.username, subscribers)
+            }
+            _ => return,
+        };
+        let name = match self.presence.get(username) {
+            Some(user) => user.name.clone(),
+            None => return,
+        };
+        let event = ServerEvent::Typing {
+            room: msg.room.clone(),
+            username: name,
+        };
+        for id in subscribers {
+            let other =
+                matches!(self.clients.get(id), Some(client) if &client.username != username);
+            if other {
+                self.push(*id, event.clone());
+            }
+        }
+    }
+}
+
+impl Handler<GetPresence> for ChatServer {
+    type Result = Vec<Presence>;
+
+    fn handle(&mut self, msg: GetPresence, _: &mut Context<Self>) -> Vec<Presence> {
+        msg.usernames
+            .iter()
+            .filter_map(|username| {
+                let user = self.presence.get(username)?;
+                let status = self.status_of(username);
+                if status == PresenceStatus::Offline {
+                    return None;
+                }
+                Some(Presence {
+                    username: user.name.clone(),
+                    status,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Handler<Broadcast> for ChatServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: Broadcast, _: &mut Context<Self>) {
+        if let Some(subscribers) = self.rooms.get(&msg.room) {
+            for id in subscribers {
+                self.push(*id, msg.event.clone());
+            }
+        }
+    }
+}
+
+impl Handler<Notify> for ChatServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: Notify, _: &mut Context<Self>) {
+        for (id, client) in &self.clients {
+            if msg.usernames.contains(&client.username) {
+                self.push(*id, msg.event.clone());
+            }
+        }
+    }
+}
+
+impl Handler<RemoveMember> for ChatServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: RemoveMember, _: &mut Context<Self>) {
+        if let Some(user) = self.presence.get_mut(&msg.username) {
+            user.rooms.remove(&msg.room);
+        }
+        let ids: Vec<usize> = self
+            .clients
+            .iter()
+            .filter(|(_, client)| client.username == msg.username)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ids {
+            if self.unsubscribe(id, &msg.room) {
+                self.push(
+                    id,
+                    ServerEvent::Unsubscribed {
+                        room: msg.room.clone(),
+                    },
+                );
+            }
+        }
+    }
+}
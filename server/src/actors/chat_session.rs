@@ -0,0 +1,258 @@
+use crate::{
+    actors::chat_server::{
+        Broadcast, ChatServer, Close, Connect, Disconnect, Heartbeat, Push, Subscribe, Typing,
+        Unsubscribe,
+    },
+    handlers::chat::{mark_read, member_rooms, require_member, store_message},
+    models::{error::ServiceError, user::FullUser},
+};
+use actix::prelude::*;
+use actix_web::web;
+use actix_web_actors::ws;
+use arangors::Connection;
+use shared::{
+    models::chat::{NewMessage, ReadReceipt},
+    protocol::{ClientEvent, Frame, ProtocolError, ServerEvent, PROTOCOL_VERSION},
+};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// How often the server pings the client
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+/// A client not answering for this long is disconnected
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// One WebSocket connection of a logged user
+pub struct ChatSession {
+    /// Given by the chat server once connected
+    id: usize,
+    user: Arc<FullUser>,
+    /// Key of the login session, the connection is closed when it is revoked
+    session: String,
+    /// Last time the client showed it is alive
+    heartbeat: Instant,
+    server: Addr<ChatServer>,
+    connection: web::Data<Arc<Connection>>,
+}
+
+impl ChatSession {
+    pub fn new(
+        user: FullUser,
+        session: String,
+        server: Addr<ChatServer>,
+        connection: web::Data<Arc<Connection>>,
+    ) -> Self {
+        ChatSession {
+            id: 0,
+            user: Arc::new(user),
+            session,
+            heartbeat: Instant::now(),
+            server,
+            connection,
+        }
+    }
+
+    fn push(&self, event: ServerEvent, ctx: &mut ws::WebsocketContext<Self>) {
+        ctx.text(Frame::new(event).to_json());
+    }
+
+    fn push_error(&self, message: String, ctx: &mut ws::WebsocketContext<Self>) {
+        self.push(ServerEvent::Error { message }, ctx);
+    }
+
+    fn start_heartbeat(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        ctx.run_interval(HEARTBEAT_INTERVAL, |session, ctx| {
+            if Instant::now().duration_since(session.heartbeat) > CLIENT_TIMEOUT {
+                ctx.stop();
+            } else {
+                ctx.ping(b"");
+            }
+        });
+    }
+
+    fn handle_frame(&mut self, text: &str, ctx: &mut ws::WebsocketContext<Self>) {
+        let frame = match Frame::<ClientEvent>::from_json(text) {
+            Ok(frame) => frame,
+            Err(ProtocolError::UnsupportedVersion(version)) => {
+                return self.push_error(
+                    format!(
+                        "Unsupported protocol version {}, the server speaks {}",
+                        version, PROTOCOL_VERSION
+                    ),
+                    ctx,
+                );
+            }
+            Err(ProtocolError::Invalid(err)) => {
+                return self.push_error(format!("Invalid frame: {}", err), ctx);
+            }
+        };
+
+        let connection = self.connection.clone();
+        let user = self.user.clone();
+        match frame.event {
+            ClientEvent::Subscribe { room } => {
+                let checked = async move {
+                    require_member(connection, &room, &user).await?;
+                    Ok(room)
+                };
+                ctx.spawn(checked.into_actor(self).map(
+                    |res: Result<String, ServiceError>, session, ctx| match res {
+                        Ok(room) => {
+                            session.server.do_send(Subscribe {
+                                id: session.id,
+                                room: room.clone(),
+                            });
+                            session.push(ServerEvent::Subscribed { room }, ctx);
+                        }
+                        Err(err) => session.push_error(err.to_string(), ctx),
+                    },
+                ));
+            }
+            ClientEvent::Unsubscribe { room } => {
+                self.server.do_send(Unsubscribe {
+                    id: self.id,
+                    room: room.clone(),
+                });
+                self.push(ServerEvent::Unsubscribed { room }, ctx);
+            }
+            ClientEvent::Post { room, body, parent } => {
+                let stored = async move {
+                    store_message(connection, &room, &NewMessage { body, parent }, &user).await
+                };
+                ctx.spawn(stored.into_actor(self).map(|res, session, ctx| match res {
+                    Ok(message) => session.server.do_send(Broadcast {
+                        room: message.room.clone(),
+                        event: ServerEvent::MessagePosted { message },
+                    }),
+                    Err(err) => session.push_error(err.to_string(), ctx),
+                }));
+            }
+            ClientEvent::Heartbeat { active } => self.server.do_send(Heartbeat {
+                id: self.id,
+                active,
+            }),
+            // Only forwarded to a room the connection is subscribed to
+            ClientEvent::Typing { room } => self.server.do_send(Typing { id: self.id, room }),
+            ClientEvent::MarkRead { room, message } => {
+                let marked = async move {
+                    let receipt = mark_read(connection, &room, &message, &user).await?;
+                    Ok((room, receipt))
+                };
+                ctx.spawn(marked.into_actor(self).map(
+                    |res: Result<(String, Option<ReadReceipt>), ServiceError>, session, ctx| {
+                        match res {
+                            Ok((room, Some(receipt))) => session.server.do_send(Broadcast {
+                                room: room.clone(),
+                                event: ServerEvent::ReadMarkerMoved { room, receipt },
+                            }),
+                            Ok((_, None)) => (),
+                            Err(err) => session.push_error(err.to_string(), ctx),
+                        }
+                    },
+                ));
+            }
+        }
+    }
+}
+
+impl Actor for ChatSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.start_heartbeat(ctx);
+        let connection = self.connection.clone();
+        let server = self.server.clone();
+        let user = self.user.clone();
+        let session = self.session.clone();
+        let recipient = ctx.address().recipient();
+        let closer = ctx.address().recipient();
+        let connected = async move {
+            // Without its rooms the user is still connected, nobody hears
+            // about its presence
+            let rooms = member_rooms(connection, &user.canonical_username)
+                .await
+                .unwrap_or_default();
+            let connect = Connect {
+                username: user.canonical_username.clone(),
+                session,
+                name: user.username.clone(),
+                rooms,
+                recipient,
+                closer,
+            };
+            server.send(connect).await
+        };
+        // Nothing else is handled until the chat server knows the connection
+        connected
+            .into_actor(self)
+            .map(|res, session, ctx| match res {
+                Ok(id) => {
+                    session.id = id;
+                    let username = session.user.username.clone();
+                    session.push(ServerEvent::Welcome { username }, ctx);
+                }
+                Err(_) => ctx.stop(),
+            })
+            .wait(ctx);
+    }
+
+    fn stopping(&mut self, _: &mut Self::Context) -> Running {
+        self.server.do_send(Disconnect { id: self.id });
+        Running::Stop
+    }
+}
+
+impl Handler<Push> for ChatSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: Push, ctx: &mut Self::Context) {
+        self.push(msg.0, ctx);
+    }
+}
+
+impl Handler<Close> for ChatSession {
+    type Result = ();
+
+    fn handle(&mut self, _: Close, ctx: &mut Self::Context) {
+        self.push_error("Your session was revoked".to_string(), ctx);
+        ctx.close(Some(ws::CloseReason {
+            code: ws::CloseCode::Policy,
+            description: Some("Session revoked".to_string()),
+        }));
+        ctx.stop();
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for ChatSession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        let msg = match msg {
+            Ok(msg) => msg,
+            Err(_) => {
+                ctx.stop();
+                return;
+            }
+        };
+        match msg {
+            ws::Message::Ping(bytes) => {
+                self.heartbeat = Instant::now();
+                ctx.pong(&bytes);
+            }
+            ws::Message::Pong(_) => self.heartbeat = Instant::now(),
+            ws::Message::Text(text) => {
+                self.heartbeat = Instant::now();
+                self.handle_frame(&text, ctx);
+            }
+            ws::Message::Binary(_) => {
+                self.push_error("Only text frames are supported".to_string(), ctx)
+            }
+            ws::Message::Close(reason) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            ws::Message::Continuation(_) => ctx.stop(),
+            ws::Message::Nop => (),
+        }
+    }
+}
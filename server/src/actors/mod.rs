@@ -0,0 +1,2 @@
+pub mod chat_server;
+pub mod chat_session;
@@ -0,0 +1,203 @@
+use crate::{
+    actors::chat_server::{ChatServer, CloseSessions, RevokedSessions},
+    handlers::{audit::record, role::set_roles, session::revoke_all_sessions, users::find_user},
+    models::{audit::RequestInfo, error::ServiceError, session::LoggedIn},
+    utils::{
+        date::iso_now,
+        password::{EncodedParameters, HashParameters},
+    },
+};
+use actix::Addr;
+use actix_web::{web, HttpResponse};
+use arangors::{ClientError, Connection};
+use serde::{Deserialize, Serialize};
+use shared::{
+    models::{
+        audit::AuditAction,
+        role::{Permission, Role, RolesChange},
+        user::{AccountState, AccountStatus, StatusTransition},
+    },
+    username::canonical,
+};
+use std::{collections::HashMap, sync::Arc};
+
+/// Number of users sharing the same argon2 parameters
+#[derive(Deserialize)]
+struct HashGroup {
+    parameters: String,
+    users: u64,
+}
+
+#[derive(Serialize)]
+struct HashReportEntry {
+    /// None when the stored hash could not be read
+    parameters: Option<EncodedParameters>,
+    users: u64,
+    /// true if these users will get a new hash on their next login
+    outdated: bool,
+}
+
+/// Report how many users are still on each set of hash parameters
+pub async fn hash_report(
+    connection: web::Data<Arc<Connection>>,
+    hash_parameters: web::Data<Arc<HashParameters>>,
+    logged: LoggedIn,
+) -> Result<HttpResponse, ServiceError> {
+    logged.require(Permission::ViewReports)?;
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    // Only the header of the hash is grouped, salt and hash are left out
+    let res: Result<Vec<HashGroup>, ClientError> = database
+        .aql_bind_vars(
+            r#"FOR u in users
+                LET parts = SPLIT(u.hash, "$")
+                COLLECT parameters = CONCAT_SEPARATOR("$", "", parts[1], parts[2], parts[3])
+                WITH COUNT INTO users
+                RETURN { parameters, users }"#,
+            HashMap::new(),
+        )
+        .await;
+
+    match res {
+        Ok(groups) => {
+            let report: Vec<HashReportEntry> = groups
+                .into_iter()
+                .map(|group| HashReportEntry {
+                    parameters: EncodedParameters::parse(&group.parameters),
+                    users: group.users,
+                    outdated: hash_parameters.needs_rehash(&group.parameters),
+                })
+                .collect();
+            Ok(HttpResponse::Ok().json(report))
+        }
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
+
+/// Move an account to another status, a suspended or deleted account loses its
+/// sessions
+pub async fn change_status(
+    username: web::Path<String>,
+    payload: web::Json<StatusTransition>,
+    connection: web::Data<Arc<Connection>>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    logged: LoggedIn,
+    info: RequestInfo,
+) -> Result<HttpResponse, ServiceError> {
+    logged.require(Permission::ManageUsers)?;
+    let payload = payload.into_inner();
+    let state = apply_status(
+        connection,
+        &chat_server,
+        &logged,
+        &info,
+        &canonical(&username),
+        payload.status,
+        payload.reason,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(state))
+}
+
+/// Check the transition is allowed, store the new status and audit it
+pub async fn apply_status(
+    connection: web::Data<Arc<Connection>>,
+    chat_server: &Addr<ChatServer>,
+    logged: &LoggedIn,
+    info: &RequestInfo,
+    username: &str,
+    status: AccountStatus,
+    reason: Option<String>,
+) -> Result<AccountState, ServiceError> {
+    if username == logged.user.canonical_username {
+        return Err(ServiceError::BadRequest(
+            "You cannot change the status of your own account".to_string(),
+        ));
+    }
+    let user = find_user(connection.clone(), username).await?;
+    let current = user.account.status;
+    if !current.can_become(status) {
+        return Err(ServiceError::BadRequest(format!(
+            "An account cannot go from {:?} to {:?}",
+            current, status
+        )));
+    }
+
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+    let state = AccountState::new(status, iso_now(), reason.clone());
+    let mut map = HashMap::new();
+    map.insert("username", serde_json::to_value(username).unwrap());
+    map.insert("account", serde_json::to_value(&state).unwrap());
+    let res: Result<Vec<serde_json::Value>, ClientError> = database
+        .aql_bind_vars(
+            "FOR u in users FILTER u.canonical_username == @username
+                UPDATE u WITH { account: @account } IN users OPTIONS { mergeObjects: false }",
+            map,
+        )
+        .await;
+    if let Err(err) = res {
+        eprintln!("Error happened :{:?}", err);
+        return Err(ServiceError::InternalServerError);
+    }
+
+    if state.status != AccountStatus::Active {
+        revoke_all_sessions(connection.clone(), username).await?;
+        chat_server.do_send(CloseSessions {
+            username: username.to_string(),
+            revoked: RevokedSessions::All,
+        });
+    }
+    let mut event = info.event(
+        AuditAction::StatusChanged,
+        Some(&logged.user.canonical_username),
+        Some(username),
+    );
+    event.details = Some(match reason {
+        Some(reason) => format!("{} -> {}: {}", current.as_str(), status.as_str(), reason),
+        None => format!("{} -> {}", current.as_str(), status.as_str()),
+    });
+    record(connection, event).await;
+    Ok(state)
+}
+
+/// Replace the roles of a user, an admin cannot remove its own admin role so
+/// there is always one left
+pub async fn change_roles(
+    username: web::Path<String>,
+    payload: web::Json<RolesChange>,
+    connection: web::Data<Arc<Connection>>,
+    logged: LoggedIn,
+    info: RequestInfo,
+) -> Result<HttpResponse, ServiceError> {
+    logged.require(Permission::ManageRoles)?;
+    let username = canonical(&username);
+    if username == logged.user.canonical_username && !payload.roles.contains(&Role::Admin) {
+        return Err(ServiceError::BadRequest(
+            "You cannot remove your own admin role".to_string(),
+        ));
+    }
+    if set_roles(connection.clone(), &username, &payload.roles).await? {
+        let mut event = info.event(
+            AuditAction::RolesChanged,
+            Some(&logged.user.canonical_username),
+            Some(&username),
+        );
+        let roles: Vec<&str> = payload.roles.iter().map(|role| role.as_str()).collect();
+        event.details = Some(roles.join(", "));
+        record(connection, event).await;
+        Ok(HttpResponse::Ok().json(&payload.roles))
+    } else {
+        Err(ServiceError::BadRequest(
+            "This user does not exist".to_string(),
+        ))
+    }
+}
@@ -0,0 +1,118 @@
+use crate::{
+    models::{audit::AuditConfig, error::ServiceError, session::LoggedIn},
+    utils::date::iso_date,
+};
+use actix_web::{web, HttpResponse};
+use arangors::{document::options::InsertOptions, ClientError, Connection};
+use serde::Deserialize;
+use shared::models::{
+    audit::{AuditEvent, AuditPage, AuditQuery},
+    role::Permission,
+};
+use std::{collections::HashMap, sync::Arc};
+
+/// Events matching the filters and how many there are on every page
+#[derive(Deserialize)]
+struct MatchingEvents {
+    total: u64,
+    events: Vec<AuditEvent>,
+}
+
+/// Append an event to the audit log
+/// A failure is only reported, it should not fail the action being audited
+pub async fn record(connection: web::Data<Arc<Connection>>, event: AuditEvent) {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+    let collection = database
+        .collection("audit_events")
+        .await
+        .expect("Should load the collection");
+
+    let res = collection
+        .create_document(event, InsertOptions::builder().silent(true).build())
+        .await;
+    if let Err(err) = res {
+        eprintln!("Could not record the audit event :{:?}", err);
+    }
+}
+
+/// Remove the events older than the retention
+pub async fn purge_expired(connection: web::Data<Arc<Connection>>, config: &AuditConfig) {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let limit = chrono::Utc::now() - chrono::Duration::days(config.retention_days);
+    let mut map = HashMap::new();
+    map.insert("limit", serde_json::to_value(iso_date(limit)).unwrap());
+    let res: Result<Vec<serde_json::Value>, ClientError> = database
+        .aql_bind_vars(
+            "FOR e in audit_events FILTER e.created_at < @limit REMOVE e IN audit_events",
+            map,
+        )
+        .await;
+    if let Err(err) = res {
+        eprintln!("Error happened :{:?}", err);
+    }
+}
+
+/// Query the audit log, most recent events first
+pub async fn list_events(
+    query: web::Query<AuditQuery>,
+    connection: web::Data<Arc<Connection>>,
+    logged: LoggedIn,
+) -> Result<HttpResponse, ServiceError> {
+    logged.require(Permission::ViewReports)?;
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let mut map = HashMap::new();
+    map.insert("action", serde_json::to_value(query.action).unwrap());
+    map.insert("actor", serde_json::to_value(&query.actor).unwrap());
+    map.insert("target", serde_json::to_value(&query.target).unwrap());
+    map.insert("ip", serde_json::to_value(&query.ip).unwrap());
+    map.insert("after", serde_json::to_value(&query.after).unwrap());
+    map.insert("before", serde_json::to_value(&query.before).unwrap());
+    map.insert("offset", serde_json::to_value(query.offset()).unwrap());
+    map.insert("limit", serde_json::to_value(query.limit()).unwrap());
+    let res: Result<Vec<MatchingEvents>, ClientError> = database
+        .aql_bind_vars(
+            "LET matching = (
+                FOR e in audit_events
+                    FILTER @action == null OR e.action == @action
+                    FILTER @actor == null OR e.actor == @actor
+                    FILTER @target == null OR e.target == @target
+                    FILTER @ip == null OR e.ip == @ip
+                    FILTER @after == null OR e.created_at >= @after
+                    FILTER @before == null OR e.created_at < @before
+                    return e
+            )
+            RETURN {
+                total: LENGTH(matching),
+                events: (FOR e in matching SORT e.created_at DESC LIMIT @offset, @limit return e)
+            }",
+            map,
+        )
+        .await;
+
+    match res {
+        Ok(mut pages) => {
+            let matching = pages.pop().ok_or(ServiceError::InternalServerError)?;
+            Ok(HttpResponse::Ok().json(AuditPage {
+                events: matching.events,
+                total: matching.total,
+                page: query.page,
+                per_page: query.limit(),
+            }))
+        }
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
@@ -1,17 +1,107 @@
 use crate::models::error::ServiceError;
 use actix_web::{web, HttpResponse, ResponseError};
 
-use crate::{handlers::secret::read_secret_key, models::user::FullUser, utils::password::verify};
+use crate::{
+    actors::chat_server::{ChatServer, CloseSessions, RevokedSessions},
+    handlers::{
+        audit::record,
+        logins::record_login,
+        password::store_hash,
+        secret::read_secret_key,
+        session::{create_session, revoke_session},
+    },
+    models::{audit::RequestInfo, language::RequestLanguage, session::LoggedIn, user::FullUser},
+    utils::{
+        mailer::Mailer,
+        password::{hash_password, verify, HashParameters},
+    },
+};
+use actix::Addr;
 use actix_identity::Identity;
 use arangors::{ClientError, Connection};
-use shared::models::{auth::LoginCredentials, user::User};
+use shared::{
+    models::{
+        audit::AuditAction,
+        auth::LoginCredentials,
+        user::{AccountStatus, User},
+    },
+    username::canonical,
+    validation::validate_login,
+};
 use std::{collections::HashMap, sync::Arc};
 
 pub async fn login(
     auth_data: web::Json<LoginCredentials>,
     connection: web::Data<Arc<Connection>>,
+    hash_parameters: web::Data<Arc<HashParameters>>,
     id: Identity,
+    info: RequestInfo,
+    mailer: web::Data<Arc<dyn Mailer>>,
+    RequestLanguage(language): RequestLanguage,
 ) -> Result<HttpResponse, ServiceError> {
+    let errors = validate_login(&auth_data);
+    if !errors.is_empty() {
+        return Err(ServiceError::InvalidFields(errors));
+    }
+    let username = canonical(auth_data.target());
+    match authenticate(&auth_data, &username, connection.clone(), &hash_parameters).await {
+        Ok(user) => {
+            let session =
+                create_session(connection.clone(), (&user.canonical_username).to_string()).await?;
+            id.remember(session.clone());
+            record_login(
+                connection.clone(),
+                &user,
+                session,
+                &info,
+                mailer.get_ref().as_ref(),
+                language,
+            )
+            .await;
+            let event = info.event(
+                AuditAction::LoginSucceeded,
+                Some(&user.canonical_username),
+                Some(&user.canonical_username),
+            );
+            record(connection, event).await;
+            Ok(HttpResponse::Ok().json(user.to_logged_user()))
+        }
+        Err(err) => {
+            let mut event = info.event(AuditAction::LoginFailed, None, Some(&username));
+            event.details = Some(err.to_string());
+            record(connection, event).await;
+            Err(err)
+        }
+    }
+}
+
+/// Revoke the current session and forget the identity cookie
+pub async fn logout(
+    connection: web::Data<Arc<Connection>>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    id: Identity,
+    logged: LoggedIn,
+    info: RequestInfo,
+) -> Result<HttpResponse, ServiceError> {
+    revoke_session(connection.clone(), &logged.session).await?;
+    chat_server.do_send(CloseSessions {
+        username: logged.user.canonical_username.clone(),
+        revoked: RevokedSessions::One(logged.session.clone()),
+    });
+    id.forget();
+    let username = logged.user.canonical_username.as_str();
+    let event = info.event(AuditAction::Logout, Some(username), Some(username));
+    record(connection, event).await;
+    Ok(HttpResponse::Ok().json("You are logged out"))
+}
+
+/// Check the credentials of an active user, its hash is upgraded if needed
+async fn authenticate(
+    auth_data: &LoginCredentials,
+    username: &str,
+    connection: web::Data<Arc<Connection>>,
+    hash_parameters: &HashParameters,
+) -> Result<FullUser, ServiceError> {
     let database = connection
         .db("tiny_avocado_tree")
         .await
@@ -22,19 +112,16 @@ pub async fn login(
         .expect("Should load the collection");
 
     let mut map = HashMap::new();
-    map.insert(
-        "username",
-        serde_json::to_value(auth_data.target()).unwrap(),
-    );
+    map.insert("username", serde_json::to_value(username).unwrap());
     let user_response: Result<Vec<FullUser>, ClientError> = collection
         .db()
         .aql_bind_vars(
-            "FOR r in users FILTER  r.username == @username return r",
+            "FOR r in users FILTER  r.canonical_username == @username return r",
             map,
         )
         .await;
 
-    let secret = read_secret_key(connection, auth_data.target().to_string()).await;
+    let secret = read_secret_key(connection.clone(), username.to_string()).await;
 
     if user_response.is_ok() && secret.is_ok() {
         let mut users = user_response.unwrap();
@@ -45,7 +132,8 @@ pub async fn login(
             )),
             1 => {
                 let user = users.pop().unwrap();
-                let check = verify(user.hash(), auth_data.password(), secret.unwrap().as_str());
+                let secret = secret.unwrap();
+                let check = verify(user.hash(), auth_data.password(), secret.as_str());
 
                 if check.is_err() {
                     Err(ServiceError::BadRequest(
@@ -54,8 +142,18 @@ pub async fn login(
                 } else {
                     let valid = check.unwrap();
                     if valid {
-                        id.remember((&user.username).to_string());
-                        Ok(HttpResponse::Ok().json(user.to_logged_user()))
+                        check_status(&user)?;
+                        if hash_parameters.needs_rehash(user.hash()) {
+                            upgrade_hash(
+                                connection.clone(),
+                                &user.canonical_username,
+                                auth_data.password(),
+                                secret.as_str(),
+                                hash_parameters,
+                            )
+                            .await;
+                        }
+                        Ok(user)
                     } else {
                         Err(ServiceError::BadRequest(
                             "Your credentials are wrong".to_string(),
@@ -74,3 +172,37 @@ pub async fn login(
         ))
     }
 }
+
+/// Only active accounts can login, the status is told only once the
+/// password is verified
+fn check_status(user: &FullUser) -> Result<(), ServiceError> {
+    match user.account.status {
+        AccountStatus::Active => Ok(()),
+        AccountStatus::Pending => Err(ServiceError::BadRequest(
+            "Your account is waiting for an approval".to_string(),
+        )),
+        AccountStatus::Suspended => Err(ServiceError::BadRequest(
+            "Your account is suspended".to_string(),
+        )),
+        AccountStatus::Deleted => Err(ServiceError::BadRequest(
+            "Your credentials are wrong".to_string(),
+        )),
+    }
+}
+
+/// Hash again a password made with outdated parameters and store it
+/// A failure here should not prevent the login, the upgrade will be retried
+/// next time
+async fn upgrade_hash(
+    connection: web::Data<Arc<Connection>>,
+    username: &str,
+    password: &str,
+    secret: &str,
+    hash_parameters: &HashParameters,
+) {
+    if let Ok(hash) = hash_password(password, secret, hash_parameters) {
+        if store_hash(connection, username, hash).await.is_err() {
+            eprintln!("Could not upgrade the password hash of {}", username);
+        }
+    }
+}
@@ -0,0 +1,1118 @@
+use crate::{
+    actors::chat_server::{Broadcast, ChatServer, Notify, RemoveMember},
+    handlers::audit::record,
+    models::{
+        audit::RequestInfo,
+        chat::{
+            ChatConfig, Membership, MessageDocument, ReactionDocument, RoomDocument, TimelineRow,
+        },
+        error::ServiceError,
+        session::LoggedIn,
+        user::FullUser,
+    },
+    utils::date::iso_now,
+};
+use actix::Addr;
+use actix_web::{web, HttpResponse};
+use arangors::{
+    document::options::InsertOptions,
+    index::{Index, IndexSettings},
+    ClientError, Connection,
+};
+use serde::Deserialize;
+use shared::{
+    models::chat::{
+        is_reaction, Cursor, HistoryPage, HistoryQuery, Message, NewConversation, NewMessage,
+        NewRoom, ReadReceipt, RoomSummary,
+    },
+    models::{audit::AuditAction, role::Permission},
+    protocol::ServerEvent,
+    username::canonical,
+    validation::{validate_conversation, validate_message, validate_room},
+};
+use std::{collections::HashMap, sync::Arc};
+
+/// ArangoDB error number of a collection created twice
+const DUPLICATE_NAME: u16 = 1207;
+/// Collections of the chat, created at startup when missing
+const CHAT_COLLECTIONS: [&str; 4] = ["rooms", "memberships", "messages", "reactions"];
+/// Persistent indexes the chat queries are read from, with their collection
+const CHAT_INDEXES: [(&str, &[&str]); 5] = [
+    ("memberships", &["room", "username"]),
+    ("memberships", &["username"]),
+    ("messages", &["room", "created_at"]),
+    ("messages", &["parent", "created_at"]),
+    ("reactions", &["message"]),
+];
+
+/// A room with what the user asking needs to know about it
+#[derive(Deserialize)]
+struct RoomRow {
+    room: RoomDocument,
+    members: u64,
+    joined: bool,
+    unread: u64,
+}
+
+impl RoomRow {
+    fn to_summary(&self) -> RoomSummary {
+        RoomSummary {
+            room: self.room.to_room(),
+            members: self.members,
+            joined: self.joined,
+            unread: self.unread,
+        }
+    }
+}
+
+/// A direct conversation and if this call created it
+#[derive(Deserialize)]
+struct OpenedRoom {
+    room: RoomDocument,
+    created: bool,
+}
+
+/// End of the timeline queries, `m` is a message returned with its reactions
+/// grouped by emoji, from the `reactions.message` index, and how many replies
+/// it has with the last one, from the `messages.parent, messages.created_at`
+/// index
+const TIMELINE_ROW: &str = "LET reactions = (
+        FOR r in reactions FILTER r.message == m._key
+            SORT r.created_at
+            COLLECT emoji = r.emoji INTO reacted = r.name
+            return { emoji, users: reacted }
+    )
+    LET replies = FIRST(
+        FOR reply in messages FILTER reply.parent == m._key
+            COLLECT WITH COUNT INTO replies
+            return replies
+    )
+    LET last_reply = FIRST(
+        FOR reply in messages FILTER reply.parent == m._key
+            SORT reply.created_at DESC, reply._key DESC LIMIT 1
+            return reply
+    )
+    return { message: m, reactions, replies, last_reply }";
+
+/// Messages of the room timeline, replies are only in their thread
+const ROOM_TIMELINE: &str = "m.room == @room AND m.parent == null";
+/// Replies to a message
+const THREAD: &str = "m.parent == @parent";
+
+/// Which way a page of history goes from its cursor
+enum Direction {
+    Older,
+    Newer,
+}
+
+impl Direction {
+    fn range(&self) -> &'static str {
+        match self {
+            Direction::Older => "<=",
+            Direction::Newer => ">=",
+        }
+    }
+
+    fn strict(&self) -> &'static str {
+        match self {
+            Direction::Older => "<",
+            Direction::Newer => ">",
+        }
+    }
+
+    fn order(&self) -> &'static str {
+        match self {
+            Direction::Older => "DESC",
+            Direction::Newer => "ASC",
+        }
+    }
+}
+
+/// A room and if the user asking is one of its members
+#[derive(Deserialize)]
+struct FoundRoom {
+    room: RoomDocument,
+    member: bool,
+}
+
+/// Every public room and the direct conversations of the user, with how
+/// many members they have, if the user is one of them and how many messages
+/// of the timeline it did not read
+pub async fn list_rooms(
+    connection: web::Data<Arc<Connection>>,
+    logged: LoggedIn,
+) -> Result<HttpResponse, ServiceError> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let mut map = HashMap::new();
+    map.insert(
+        "username",
+        serde_json::to_value(&logged.user.canonical_username).unwrap(),
+    );
+    let res: Result<Vec<RoomRow>, ClientError> = database
+        .aql_bind_vars(
+            r#"FOR r in rooms
+                LET members = (FOR m in memberships FILTER m.room == r._key return m)
+                LET mine = FIRST(FOR m in members FILTER m.username == @username return m)
+                FILTER r.kind != "direct" OR mine != null
+                LET read_at = mine.last_read_at || ""
+                LET unread = mine == null ? 0 : LENGTH(
+                    FOR message in messages
+                        FILTER message.room == r._key AND message.created_at >= read_at
+                        FILTER message.created_at > read_at OR message._key > mine.last_read
+                        FILTER message.author != @username AND message.parent == null
+                        return 1
+                )
+                SORT LOWER(r.name)
+                return { room: r, members: LENGTH(members), joined: mine != null, unread }"#,
+            map,
+        )
+        .await;
+
+    match res {
+        Ok(rows) => {
+            let rooms: Vec<RoomSummary> = rows.iter().map(RoomRow::to_summary).collect();
+            Ok(HttpResponse::Ok().json(rooms))
+        }
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
+
+/// Create a room, its creator is its first member
+pub async fn create_room(
+    payload: web::Json<NewRoom>,
+    connection: web::Data<Arc<Connection>>,
+    logged: LoggedIn,
+) -> Result<HttpResponse, ServiceError> {
+    let errors = validate_room(&payload);
+    if !errors.is_empty() {
+        return Err(ServiceError::InvalidFields(errors));
+    }
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+    let collection = database
+        .collection("rooms")
+        .await
+        .expect("Should load the collection");
+
+    let room = RoomDocument::new(&payload, &logged.user);
+    let summary = RoomSummary {
+        room: room.to_room(),
+        members: 1,
+        joined: true,
+        unread: 0,
+    };
+    let res = collection
+        .create_document(room, InsertOptions::builder().silent(true).build())
+        .await;
+    if let Err(err) = res {
+        eprintln!("Error happened :{:?}", err);
+        return Err(ServiceError::InternalServerError);
+    }
+    join(
+        connection,
+        &summary.room.key,
+        &logged.user.canonical_username,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(summary))
+}
+
+pub async fn join_room(
+    room: web::Path<String>,
+    connection: web::Data<Arc<Connection>>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    logged: LoggedIn,
+) -> Result<HttpResponse, ServiceError> {
+    let (room, member) = find_room(connection.clone(), &room, &logged.user).await?;
+    if room.is_direct() {
+        return Err(fixed_membership());
+    }
+    join(connection, room.key(), &logged.user.canonical_username).await?;
+    if !member {
+        chat_server.do_send(Broadcast {
+            room: room.key().to_string(),
+            event: ServerEvent::MemberJoined {
+                room: room.key().to_string(),
+                username: logged.user.username.clone(),
+            },
+        });
+    }
+    Ok(HttpResponse::Ok().json(room.to_room()))
+}
+
+/// Leave a room, the connections of the user stop receiving its events
+pub async fn leave_room(
+    room: web::Path<String>,
+    connection: web::Data<Arc<Connection>>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    logged: LoggedIn,
+) -> Result<HttpResponse, ServiceError> {
+    let (found, _) = find_room(connection.clone(), &room, &logged.user).await?;
+    if found.is_direct() {
+        return Err(fixed_membership());
+    }
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let mut map = HashMap::new();
+    map.insert("room", serde_json::to_value(room.as_str()).unwrap());
+    map.insert(
+        "username",
+        serde_json::to_value(&logged.user.canonical_username).unwrap(),
+    );
+    let res: Result<Vec<serde_json::Value>, ClientError> = database
+        .aql_bind_vars(
+            "FOR m in memberships FILTER m.room == @room AND m.username == @username
+                REMOVE m IN memberships return OLD",
+            map,
+        )
+        .await;
+
+    match res {
+        // Not a member, the others have nothing to hear about
+        Ok(removed) if removed.is_empty() => Ok(HttpResponse::Ok().json("You left the room")),
+        Ok(_) => {
+            chat_server.do_send(RemoveMember {
+                room: room.to_string(),
+                username: logged.user.canonical_username.clone(),
+            });
+            chat_server.do_send(Broadcast {
+                room: room.to_string(),
+                event: ServerEvent::MemberLeft {
+                    room: room.to_string(),
+                    username: logged.user.username.clone(),
+                },
+            });
+            Ok(HttpResponse::Ok().json("You left the room"))
+        }
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
+
+/// Open the direct conversation between the user and `participants`, the
+/// same participants always get the same room
+/// The other participants are told about it when it is new
+pub async fn open_conversation(
+    payload: web::Json<NewConversation>,
+    connection: web::Data<Arc<Connection>>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    logged: LoggedIn,
+) -> Result<HttpResponse, ServiceError> {
+    let errors = validate_conversation(&payload);
+    if !errors.is_empty() {
+        return Err(ServiceError::InvalidFields(errors));
+    }
+    let mut usernames: Vec<String> = payload
+        .participants
+        .iter()
+        .filter(|username| !username.trim().is_empty())
+        .map(|username| canonical(username))
+        .collect();
+    usernames.push(logged.user.canonical_username.clone());
+    usernames.sort();
+    usernames.dedup();
+    if usernames.len() < 2 {
+        return Err(ServiceError::BadRequest(
+            "A conversation needs someone else".to_string(),
+        ));
+    }
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let mut map = HashMap::new();
+    map.insert("usernames", serde_json::to_value(&usernames).unwrap());
+    let res: Result<Vec<FullUser>, ClientError> = database
+        .aql_bind_vars(
+            "FOR u in users FILTER u.canonical_username IN @usernames
+                SORT u.canonical_username return u",
+            map,
+        )
+        .await;
+    let participants = match res {
+        Ok(participants) => participants,
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            return Err(ServiceError::InternalServerError);
+        }
+    };
+    if participants.len() != usernames.len() {
+        return Err(ServiceError::BadRequest(
+            "Some of these users do not exist".to_string(),
+        ));
+    }
+
+    // The key is unique, two users opening it at once cannot make two rooms
+    let mut map = HashMap::new();
+    let room = RoomDocument::direct(&participants, &logged.user);
+    map.insert("key", serde_json::to_value(room.key()).unwrap());
+    map.insert("room", serde_json::to_value(room).unwrap());
+    let res: Result<Vec<OpenedRoom>, ClientError> = database
+        .aql_bind_vars(
+            "UPSERT { _key: @key } INSERT @room UPDATE {} IN rooms
+                return { room: NEW, created: OLD == null }",
+            map,
+        )
+        .await;
+    let opened = match res {
+        Ok(mut opened) => opened.pop().ok_or(ServiceError::InternalServerError)?,
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            return Err(ServiceError::InternalServerError);
+        }
+    };
+    for username in &usernames {
+        join(connection.clone(), opened.room.key(), username).await?;
+    }
+    let summary = RoomSummary {
+        room: opened.room.to_room(),
+        members: usernames.len() as u64,
+        joined: true,
+        unread: 0,
+    };
+    if opened.created {
+        chat_server.do_send(Notify {
+            usernames: usernames
+                .into_iter()
+                .filter(|username| username != &logged.user.canonical_username)
+                .collect(),
+            event: ServerEvent::RoomOpened {
+                room: summary.room.clone(),
+            },
+        });
+    }
+    Ok(HttpResponse::Ok().json(summary))
+}
+
+/// A page of the history of a room, oldest first
+/// Without cursor it is the last messages, with `before` the ones older than
+/// a message and with `after` the ones newer than it. Replies are left to
+/// the thread of their parent
+pub async fn history(
+    room: web::Path<String>,
+    query: web::Query<HistoryQuery>,
+    connection: web::Data<Arc<Connection>>,
+    logged: LoggedIn,
+) -> Result<HttpResponse, ServiceError> {
+    let direction = read_direction(&query)?;
+    require_member(connection.clone(), &room, &logged.user).await?;
+
+    let mut map = HashMap::new();
+    map.insert("room", serde_json::to_value(room.as_str()).unwrap());
+    let page = page_of(connection, ROOM_TIMELINE, map, direction, query.limit()).await?;
+    Ok(HttpResponse::Ok().json(page))
+}
+
+/// A page of the replies to a message, paginated like the room timeline
+pub async fn thread(
+    path: web::Path<(String, String)>,
+    query: web::Query<HistoryQuery>,
+    connection: web::Data<Arc<Connection>>,
+    logged: LoggedIn,
+) -> Result<HttpResponse, ServiceError> {
+    let (room, message) = path.into_inner();
+    let direction = read_direction(&query)?;
+    require_member(connection.clone(), &room, &logged.user).await?;
+    let parent = find_message(connection.clone(), &room, &message).await?;
+    if parent.parent.is_some() {
+        return Err(ServiceError::BadRequest(
+            "A reply has no thread of its own".to_string(),
+        ));
+    }
+
+    let mut map = HashMap::new();
+    map.insert("parent", serde_json::to_value(&message).unwrap());
+    let page = page_of(connection, THREAD, map, direction, query.limit()).await?;
+    Ok(HttpResponse::Ok().json(page))
+}
+
+/// Which way the page goes from which cursor, None for the last page
+fn read_direction(query: &HistoryQuery) -> Result<Option<(Direction, Cursor)>, ServiceError> {
+    match (&query.before, &query.after) {
+        (Some(_), Some(_)) => Err(ServiceError::BadRequest(
+            "Only one of before and after can be given".to_string(),
+        )),
+        (Some(cursor), None) => Ok(Some((Direction::Older, read_cursor(cursor)?))),
+        (None, Some(cursor)) => Ok(Some((Direction::Newer, read_cursor(cursor)?))),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Messages matching `filter`, an equality on the first fields of an index
+/// ending with `created_at` reading the bind variables of `map`
+async fn page_of(
+    connection: web::Data<Arc<Connection>>,
+    filter: &str,
+    mut map: HashMap<&str, serde_json::Value>,
+    direction: Option<(Direction, Cursor)>,
+    limit: u32,
+) -> Result<HistoryPage, ServiceError> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let limit = limit as usize;
+    // One more than asked tells if there is anything left
+    map.insert("limit", serde_json::to_value(limit + 1).unwrap());
+    // The first filter is a range on the index, the second one only drops
+    // the messages posted at the same time as the cursor
+    let selection = match &direction {
+        None => format!(
+            "FOR m in messages FILTER {}
+                SORT m.created_at DESC, m._key DESC LIMIT @limit",
+            filter
+        ),
+        Some((direction, cursor)) => {
+            map.insert("date", serde_json::to_value(&cursor.created_at).unwrap());
+            map.insert("key", serde_json::to_value(&cursor.key).unwrap());
+            format!(
+                "FOR m in messages FILTER {filter} AND m.created_at {range} @date
+                    FILTER m.created_at {strict} @date OR m._key {strict} @key
+                    SORT m.created_at {order}, m._key {order} LIMIT @limit",
+                filter = filter,
+                range = direction.range(),
+                strict = direction.strict(),
+                order = direction.order(),
+            )
+        }
+    };
+    let aql = format!("{} {}", selection, TIMELINE_ROW);
+    let res: Result<Vec<TimelineRow>, ClientError> = database.aql_bind_vars(&aql, map).await;
+
+    match res {
+        Ok(mut rows) => {
+            let more = rows.len() > limit;
+            rows.truncate(limit);
+            let mut messages: Vec<Message> = rows.iter().map(TimelineRow::to_message).collect();
+            if let Some((Direction::Older, _)) | None = direction {
+                messages.reverse();
+            }
+            let first = messages.first().map(|m| Cursor::of(m).encode());
+            let last = messages.last().map(|m| Cursor::of(m).encode());
+            Ok(match direction {
+                None => HistoryPage {
+                    older: first.filter(|_| more),
+                    newer: None,
+                    messages,
+                },
+                Some((Direction::Older, _)) => HistoryPage {
+                    older: first.filter(|_| more),
+                    newer: last,
+                    messages,
+                },
+                Some((Direction::Newer, _)) => HistoryPage {
+                    older: first,
+                    newer: last.filter(|_| more),
+                    messages,
+                },
+            })
+        }
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
+
+fn read_cursor(cursor: &str) -> Result<Cursor, ServiceError> {
+    Cursor::decode(cursor).ok_or_else(|| ServiceError::BadRequest("Invalid cursor".to_string()))
+}
+
+/// Post a message, it is pushed to the connected members too
+pub async fn post_message(
+    room: web::Path<String>,
+    payload: web::Json<NewMessage>,
+    connection: web::Data<Arc<Connection>>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    logged: LoggedIn,
+) -> Result<HttpResponse, ServiceError> {
+    let message = store_message(connection, &room, &payload, &logged.user).await?;
+    chat_server.do_send(Broadcast {
+        room: message.room.clone(),
+        event: ServerEvent::MessagePosted {
+            message: message.clone(),
+        },
+    });
+    Ok(HttpResponse::Ok().json(message))
+}
+
+/// Change the body of a message, the previous one goes to its edit history
+/// Only its author can, for a while after posting it
+pub async fn edit_message(
+    path: web::Path<(String, String)>,
+    payload: web::Json<NewMessage>,
+    connection: web::Data<Arc<Connection>>,
+    config: web::Data<Arc<ChatConfig>>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    logged: LoggedIn,
+) -> Result<HttpResponse, ServiceError> {
+    let errors = validate_message(&payload);
+    if !errors.is_empty() {
+        return Err(ServiceError::InvalidFields(errors));
+    }
+    let (room, message) = path.into_inner();
+    require_member(connection.clone(), &room, &logged.user).await?;
+    let document = find_message(connection.clone(), &room, &message).await?;
+    if document.author != logged.user.canonical_username {
+        return Err(ServiceError::Forbidden);
+    }
+    if document.deleted.is_some() {
+        return Err(ServiceError::BadRequest(
+            "This message was deleted".to_string(),
+        ));
+    }
+    if !document.editable(&config) {
+        return Err(ServiceError::BadRequest(
+            "This message can no longer be edited".to_string(),
+        ));
+    }
+
+    let mut changes = HashMap::new();
+    changes.insert("body", serde_json::to_value(payload.body.trim()).unwrap());
+    changes.insert("edited_at", serde_json::to_value(iso_now()).unwrap());
+    let edited = update_message(
+        connection,
+        &message,
+        "{ body: @body, edited_at: @edited_at,
+            edits: APPEND(m.edits || [], [{ body: m.body, written_at: m.edited_at || m.created_at }]) }",
+        changes,
+    )
+    .await?;
+    chat_server.do_send(Broadcast {
+        room,
+        event: ServerEvent::MessageEdited {
+            message: edited.clone(),
+        },
+    });
+    Ok(HttpResponse::Ok().json(edited))
+}
+
+/// Replace a message with a tombstone, by its author for a while after
+/// posting it or by a moderator at any time, both members of the room
+pub async fn delete_message(
+    path: web::Path<(String, String)>,
+    connection: web::Data<Arc<Connection>>,
+    config: web::Data<Arc<ChatConfig>>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    logged: LoggedIn,
+    info: RequestInfo,
+) -> Result<HttpResponse, ServiceError> {
+    let (room, message) = path.into_inner();
+    require_member(connection.clone(), &room, &logged.user).await?;
+    let document = find_message(connection.clone(), &room, &message).await?;
+    if document.deleted.is_some() {
+        return Ok(HttpResponse::Ok().json(document.to_message()));
+    }
+    let is_author = document.author == logged.user.canonical_username;
+    if !is_author {
+        logged.require(Permission::ModerateMessages)?;
+    } else if logged.require(Permission::ModerateMessages).is_err() && !document.editable(&config) {
+        return Err(ServiceError::BadRequest(
+            "This message can no longer be deleted".to_string(),
+        ));
+    }
+
+    let mut changes = HashMap::new();
+    changes.insert(
+        "by",
+        serde_json::to_value(&logged.user.canonical_username).unwrap(),
+    );
+    changes.insert("at", serde_json::to_value(iso_now()).unwrap());
+    let tombstone = update_message(
+        connection.clone(),
+        &message,
+        "{ deleted: { by: @by, at: @at } }",
+        changes,
+    )
+    .await?;
+    if !is_author {
+        let mut event = info.event(
+            AuditAction::MessageDeleted,
+            Some(&logged.user.canonical_username),
+            Some(&document.author),
+        );
+        event.details = Some(format!("message {} in room {}", message, room));
+        record(connection, event).await;
+    }
+    chat_server.do_send(Broadcast {
+        room,
+        event: ServerEvent::MessageDeleted {
+            message: tombstone.clone(),
+        },
+    });
+    Ok(HttpResponse::Ok().json(tombstone))
+}
+
+/// React to a message, reacting twice with the same emoji does nothing
+pub async fn add_reaction(
+    path: web::Path<(String, String, String)>,
+    connection: web::Data<Arc<Connection>>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    logged: LoggedIn,
+) -> Result<HttpResponse, ServiceError> {
+    let (room, message, emoji) = path.into_inner();
+    check_reaction(connection.clone(), &room, &message, &emoji, &logged.user).await?;
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let reaction =
+        ReactionDocument::new(room.clone(), message.clone(), emoji.clone(), &logged.user);
+    let mut map = HashMap::new();
+    map.insert("key", serde_json::to_value(reaction.key()).unwrap());
+    map.insert("reaction", serde_json::to_value(reaction).unwrap());
+    let res: Result<Vec<bool>, ClientError> = database
+        .aql_bind_vars(
+            "UPSERT { _key: @key } INSERT @reaction UPDATE {} IN reactions return OLD == null",
+            map,
+        )
+        .await;
+
+    match res {
+        Ok(added) => {
+            if added.contains(&true) {
+                chat_server.do_send(Broadcast {
+                    room: room.clone(),
+                    event: ServerEvent::ReactionAdded {
+                        room,
+                        message,
+                        emoji,
+                        username: logged.user.username.clone(),
+                    },
+                });
+            }
+            Ok(HttpResponse::Ok().json("You reacted"))
+        }
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
+
+/// Take back a reaction, removing a missing one does nothing
+pub async fn remove_reaction(
+    path: web::Path<(String, String, String)>,
+    connection: web::Data<Arc<Connection>>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    logged: LoggedIn,
+) -> Result<HttpResponse, ServiceError> {
+    let (room, message, emoji) = path.into_inner();
+    check_reaction(connection.clone(), &room, &message, &emoji, &logged.user).await?;
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let reaction =
+        ReactionDocument::new(room.clone(), message.clone(), emoji.clone(), &logged.user);
+    let mut map = HashMap::new();
+    map.insert("key", serde_json::to_value(reaction.key()).unwrap());
+    let res: Result<Vec<serde_json::Value>, ClientError> = database
+        .aql_bind_vars(
+            "FOR r in reactions FILTER r._key == @key REMOVE r IN reactions return OLD",
+            map,
+        )
+        .await;
+
+    match res {
+        Ok(removed) => {
+            if !removed.is_empty() {
+                chat_server.do_send(Broadcast {
+                    room: room.clone(),
+                    event: ServerEvent::ReactionRemoved {
+                        room,
+                        message,
+                        emoji,
+                        username: logged.user.username.clone(),
+                    },
+                });
+            }
+            Ok(HttpResponse::Ok().json("Your reaction was removed"))
+        }
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
+
+/// Members react to the messages still in the timeline
+async fn check_reaction(
+    connection: web::Data<Arc<Connection>>,
+    room: &str,
+    message: &str,
+    emoji: &str,
+    user: &FullUser,
+) -> Result<(), ServiceError> {
+    if !is_reaction(emoji) {
+        return Err(ServiceError::BadRequest(
+            "A reaction is a single emoji".to_string(),
+        ));
+    }
+    require_member(connection.clone(), room, user).await?;
+    match find_message(connection, room, message).await? {
+        document if document.deleted.is_some() => Err(ServiceError::BadRequest(
+            "This message was deleted".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Where the members of a room are in their reading
+pub async fn receipts(
+    room: web::Path<String>,
+    connection: web::Data<Arc<Connection>>,
+    logged: LoggedIn,
+) -> Result<HttpResponse, ServiceError> {
+    require_member(connection.clone(), &room, &logged.user).await?;
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let mut map = HashMap::new();
+    map.insert("room", serde_json::to_value(room.as_str()).unwrap());
+    let res: Result<Vec<ReadReceipt>, ClientError> = database
+        .aql_bind_vars(
+            "FOR m in memberships FILTER m.room == @room AND m.last_read != null
+                FOR u in users FILTER u.canonical_username == m.username
+                return { username: u.username, message: m.last_read }",
+            map,
+        )
+        .await;
+
+    match res {
+        Ok(receipts) => Ok(HttpResponse::Ok().json(receipts)),
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
+
+/// Move the read marker of a member up to `message`, None when the marker
+/// was already there or further
+/// Only the membership of the reader matches, the others read nothing
+pub async fn mark_read(
+    connection: web::Data<Arc<Connection>>,
+    room: &str,
+    message: &str,
+    reader: &FullUser,
+) -> Result<Option<ReadReceipt>, ServiceError> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let mut map = HashMap::new();
+    map.insert("room", serde_json::to_value(room).unwrap());
+    map.insert("message", serde_json::to_value(message).unwrap());
+    map.insert(
+        "username",
+        serde_json::to_value(&reader.canonical_username).unwrap(),
+    );
+    let res: Result<Vec<serde_json::Value>, ClientError> = database
+        .aql_bind_vars(
+            r#"LET target = DOCUMENT("messages", @message)
+                FILTER target != null AND target.room == @room
+                FOR m in memberships FILTER m.room == @room AND m.username == @username
+                    FILTER m.last_read_at == null OR m.last_read_at < target.created_at
+                        OR (m.last_read_at == target.created_at AND m.last_read < target._key)
+                    UPDATE m WITH { last_read: target._key, last_read_at: target.created_at }
+                        IN memberships
+                    return 1"#,
+            map,
+        )
+        .await;
+
+    match res {
+        Ok(moved) if moved.is_empty() => Ok(None),
+        Ok(_) => Ok(Some(ReadReceipt {
+            username: reader.username.clone(),
+            message: message.to_string(),
+        })),
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
+
+/// Check the author can post in the room and keep its message
+pub async fn store_message(
+    connection: web::Data<Arc<Connection>>,
+    room: &str,
+    payload: &NewMessage,
+    author: &FullUser,
+) -> Result<Message, ServiceError> {
+    let errors = validate_message(payload);
+    if !errors.is_empty() {
+        return Err(ServiceError::InvalidFields(errors));
+    }
+    require_member(connection.clone(), room, author).await?;
+    // Threads are one level deep, under a message of the timeline
+    if let Some(parent) = &payload.parent {
+        let parent = find_message(connection.clone(), room, parent).await?;
+        if parent.parent.is_some() {
+            return Err(ServiceError::BadRequest(
+                "Reply to the message starting the thread".to_string(),
+            ));
+        }
+        if parent.deleted.is_some() {
+            return Err(ServiceError::BadRequest(
+                "This message was deleted".to_string(),
+            ));
+        }
+    }
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+    let collection = database
+        .collection("messages")
+        .await
+        .expect("Should load the collection");
+
+    let document = MessageDocument::new(room.to_string(), payload, author);
+    let message = document.to_message();
+    let res = collection
+        .create_document(document, InsertOptions::builder().silent(true).build())
+        .await;
+    match res {
+        Ok(_) => Ok(message),
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
+
+/// A message of a room, deleted or not
+async fn find_message(
+    connection: web::Data<Arc<Connection>>,
+    room: &str,
+    message: &str,
+) -> Result<MessageDocument, ServiceError> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let mut map = HashMap::new();
+    map.insert("room", serde_json::to_value(room).unwrap());
+    map.insert("message", serde_json::to_value(message).unwrap());
+    let res: Result<Vec<MessageDocument>, ClientError> = database
+        .aql_bind_vars(
+            "FOR m in messages FILTER m._key == @message AND m.room == @room return m",
+            map,
+        )
+        .await;
+
+    match res {
+        Ok(mut messages) => messages
+            .pop()
+            .ok_or_else(|| ServiceError::BadRequest("This message does not exist".to_string())),
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
+
+/// Apply `changes`, an AQL object reading the bind variables, to a message
+async fn update_message(
+    connection: web::Data<Arc<Connection>>,
+    message: &str,
+    changes: &str,
+    mut map: HashMap<&str, serde_json::Value>,
+) -> Result<Message, ServiceError> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    map.insert("message", serde_json::to_value(message).unwrap());
+    let aql = format!(
+        "FOR m in messages FILTER m._key == @message
+            UPDATE m WITH {} IN messages return NEW",
+        changes
+    );
+    let res: Result<Vec<MessageDocument>, ClientError> = database.aql_bind_vars(&aql, map).await;
+
+    match res {
+        Ok(mut messages) => messages
+            .pop()
+            .map(|document| document.to_message())
+            .ok_or(ServiceError::InternalServerError),
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
+
+/// Find a room and tell if the user is one of its members
+pub async fn find_room(
+    connection: web::Data<Arc<Connection>>,
+    room: &str,
+    user: &FullUser,
+) -> Result<(RoomDocument, bool), ServiceError> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let mut map = HashMap::new();
+    map.insert("room", serde_json::to_value(room).unwrap());
+    map.insert(
+        "username",
+        serde_json::to_value(&user.canonical_username).unwrap(),
+    );
+    let res: Result<Vec<FoundRoom>, ClientError> = database
+        .aql_bind_vars(
+            "FOR r in rooms FILTER r._key == @room
+                LET member = LENGTH(
+                    FOR m in memberships FILTER m.room == r._key AND m.username == @username
+                    LIMIT 1 return 1
+                ) > 0
+                return { room: r, member }",
+            map,
+        )
+        .await;
+
+    match res {
+        Ok(mut rooms) => rooms
+            .pop()
+            .map(|found| (found.room, found.member))
+            .ok_or_else(|| ServiceError::BadRequest("This room does not exist".to_string())),
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
+
+/// Only the members of a room can read and post in it
+pub async fn require_member(
+    connection: web::Data<Arc<Connection>>,
+    room: &str,
+    user: &FullUser,
+) -> Result<RoomDocument, ServiceError> {
+    match find_room(connection, room, user).await? {
+        (room, true) => Ok(room),
+        (_, false) => Err(ServiceError::Forbidden),
+    }
+}
+
+/// Keys of the rooms a user is a member of
+pub async fn member_rooms(
+    connection: web::Data<Arc<Connection>>,
+    username: &str,
+) -> Result<Vec<String>, ServiceError> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let mut map = HashMap::new();
+    map.insert("username", serde_json::to_value(username).unwrap());
+    let res: Result<Vec<String>, ClientError> = database
+        .aql_bind_vars(
+            "FOR m in memberships FILTER m.username == @username return m.room",
+            map,
+        )
+        .await;
+
+    res.map_err(|err| {
+        eprintln!("Error happened :{:?}", err);
+        ServiceError::InternalServerError
+    })
+}
+
+/// Nobody joins or leaves a direct conversation
+fn fixed_membership() -> ServiceError {
+    ServiceError::BadRequest("The members of a direct conversation cannot change".to_string())
+}
+
+/// Add a member to a room, joining twice does nothing
+async fn join(
+    connection: web::Data<Arc<Connection>>,
+    room: &str,
+    username: &str,
+) -> Result<(), ServiceError> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let membership = Membership::new(room.to_string(), username.to_string());
+    let mut map = HashMap::new();
+    map.insert("room", serde_json::to_value(room).unwrap());
+    map.insert("username", serde_json::to_value(username).unwrap());
+    map.insert("membership", serde_json::to_value(membership).unwrap());
+    let res: Result<Vec<serde_json::Value>, ClientError> = database
+        .aql_bind_vars(
+            "UPSERT { room: @room, username: @username }
+                INSERT @membership UPDATE {} IN memberships",
+            map,
+        )
+        .await;
+
+    res.map(|_| ()).map_err(|err| {
+        eprintln!("Error happened :{:?}", err);
+        ServiceError::InternalServerError
+    })
+}
+
+/// Create the chat collections and their indexes when they are missing
+/// Run at startup, the history pages and unread counts are only read from the
+/// indexes
+pub async fn ensure_storage(connection: web::Data<Arc<Connection>>) -> Result<(), String> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    for collection in CHAT_COLLECTIONS.iter() {
+        match database.create_collection(collection).await {
+            Ok(_) => {}
+            Err(ClientError::Arango(err)) if err.error_num() == DUPLICATE_NAME => {}
+            Err(err) => return Err(format!("{}: {:?}", collection, err)),
+        }
+    }
+
+    // Creating one again gives the existing one
+    for (collection, fields) in CHAT_INDEXES.iter() {
+        let index = Index::builder()
+            .fields(fields.iter().map(|field| field.to_string()).collect())
+            .settings(IndexSettings::Persistent {
+                unique: false,
+                sparse: false,
+                deduplicate: false,
+            })
+            .build();
+        database
+            .create_index(collection, &index)
+            .await
+            .map_err(|err| format!("{}({}): {:?}", collection, fields.join(", "), err))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use crate::{
+    models::{
+        error::ServiceError,
+        history::{HistoryConfig, HistoryEntry},
+    },
+    utils::password::verify,
+};
+use actix_web::web;
+use arangors::{document::options::InsertOptions, ClientError, Connection};
+use std::{collections::HashMap, sync::Arc};
+
+/// Remember a new password of the user and forget the ones beyond the
+/// configured size
+pub async fn push_password_history(
+    connection: web::Data<Arc<Connection>>,
+    entry: HistoryEntry,
+    config: &HistoryConfig,
+) -> Result<(), ServiceError> {
+    let database = connection
+        .db("avocado_trunk")
+        .await
+        .expect("Should load the db");
+    let collection = database
+        .collection("history")
+        .await
+        .expect("Should load the collection");
+
+    let mut map = HashMap::new();
+    map.insert("username", serde_json::to_value(entry.username()).unwrap());
+    map.insert("size", serde_json::to_value(config.size).unwrap());
+
+    let new_entry = collection
+        .create_document(entry, InsertOptions::builder().silent(true).build())
+        .await;
+    if new_entry.is_err() {
+        return Err(ServiceError::InternalServerError);
+    }
+
+    let res: Result<Vec<serde_json::Value>, ClientError> = database
+        .aql_bind_vars(
+            "FOR h in history FILTER h.username == @username
+                SORT h.created_at DESC LIMIT @size, 1000000
+                REMOVE h IN history",
+            map,
+        )
+        .await;
+
+    res.map(|_| ()).map_err(|err| {
+        eprintln!("Error happened :{:?}", err);
+        ServiceError::InternalServerError
+    })
+}
+
+/// Reject a password matching one of the last ones of the user
+pub async fn check_password_history(
+    connection: web::Data<Arc<Connection>>,
+    username: &str,
+    password: &str,
+    config: &HistoryConfig,
+) -> Result<(), ServiceError> {
+    let database = connection
+        .db("avocado_trunk")
+        .await
+        .expect("Should load the db");
+
+    let mut map = HashMap::new();
+    map.insert("username", serde_json::to_value(username).unwrap());
+    map.insert("size", serde_json::to_value(config.size).unwrap());
+    let res: Result<Vec<HistoryEntry>, ClientError> = database
+        .aql_bind_vars(
+            "FOR h in history FILTER h.username == @username
+                SORT h.created_at DESC LIMIT @size return h",
+            map,
+        )
+        .await;
+
+    match res {
+        Ok(entries) => {
+            for entry in entries {
+                if verify(entry.hash(), password, entry.main()).unwrap_or(false) {
+                    return Err(ServiceError::BadRequest(
+                        "You have already used this password recently".to_string(),
+                    ));
+                }
+            }
+            Ok(())
+        }
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use crate::{
+    models::{
+        audit::RequestInfo, error::ServiceError, login::LoginRecord, session::LoggedIn,
+        user::FullUser,
+    },
+    utils::mailer::{Mail, Mailer},
+};
+use actix_web::{web, HttpResponse};
+use arangors::{document::options::InsertOptions, ClientError, Connection};
+use serde::Deserialize;
+use shared::{
+    i18n::{Language, Message},
+    models::login::LoginEntry,
+};
+use std::{collections::HashMap, sync::Arc};
+
+/// How many logins are shown to the user
+const LOGIN_HISTORY_LIMIT: u32 = 50;
+
+/// What we already know about the devices of a user
+#[derive(Deserialize)]
+struct KnownDevices {
+    /// The user logged in before
+    any: bool,
+    /// The user logged in before from this device
+    this_one: bool,
+}
+
+/// Keep a successful login and tell the user when it comes from a new device
+/// The first login is not notified, nothing is known about the user yet
+/// A failure is only reported, it should not fail the login
+pub async fn record_login(
+    connection: web::Data<Arc<Connection>>,
+    user: &FullUser,
+    session: String,
+    info: &RequestInfo,
+    mailer: &dyn Mailer,
+    language: Language,
+) {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+    let mut record = LoginRecord::new(user.canonical_username.clone(), session, info);
+
+    let mut map = HashMap::new();
+    map.insert(
+        "username",
+        serde_json::to_value(&user.canonical_username).unwrap(),
+    );
+    map.insert("device", serde_json::to_value(record.device()).unwrap());
+    let res: Result<Vec<KnownDevices>, ClientError> = database
+        .aql_bind_vars(
+            "RETURN {
+                any: LENGTH(FOR l in logins FILTER l.username == @username LIMIT 1 return 1) > 0,
+                this_one: LENGTH(
+                    FOR l in logins FILTER l.username == @username AND l.device == @device
+                    LIMIT 1 return 1
+                ) > 0
+            }",
+            map,
+        )
+        .await;
+    let known = match res {
+        Ok(mut known) => known.pop(),
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            None
+        }
+    };
+    if let Some(known) = known {
+        record.new_device = known.any && !known.this_one;
+    }
+    if record.new_device {
+        notify_new_device(user, &record, mailer, language);
+    }
+
+    let collection = database
+        .collection("logins")
+        .await
+        .expect("Should load the collection");
+    let res = collection
+        .create_document(record, InsertOptions::builder().silent(true).build())
+        .await;
+    if let Err(err) = res {
+        eprintln!("Could not record the login :{:?}", err);
+    }
+}
+
+fn notify_new_device(
+    user: &FullUser,
+    record: &LoginRecord,
+    mailer: &dyn Mailer,
+    language: Language,
+) {
+    let to = match user.emails.first() {
+        Some(email) => email.clone(),
+        None => return,
+    };
+    let mail = Mail {
+        to,
+        subject: Message::new("mail.new_device.subject").translate(language),
+        body: Message::new("mail.new_device.body")
+            .with("username", &user.username)
+            .with("device", record.device().describe())
+            .with("date", &record.created_at)
+            .translate(language),
+    };
+    if let Err(err) = mailer.send(&mail) {
+        eprintln!(
+            "Could not notify {} of a new device :{}",
+            user.canonical_username, err
+        );
+    }
+}
+
+/// The last logins of the logged user, most recent first
+pub async fn list_logins(
+    connection: web::Data<Arc<Connection>>,
+    logged: LoggedIn,
+) -> Result<HttpResponse, ServiceError> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let mut map = HashMap::new();
+    map.insert(
+        "username",
+        serde_json::to_value(&logged.user.canonical_username).unwrap(),
+    );
+    map.insert("limit", serde_json::to_value(LOGIN_HISTORY_LIMIT).unwrap());
+    let res: Result<Vec<LoginRecord>, ClientError> = database
+        .aql_bind_vars(
+            "FOR l in logins FILTER l.username == @username
+                SORT l.created_at DESC LIMIT @limit return l",
+            map,
+        )
+        .await;
+
+    match res {
+        Ok(records) => {
+            let entries: Vec<LoginEntry> = records
+                .iter()
+                .map(|record| record.to_entry(&logged.session))
+                .collect();
+            Ok(HttpResponse::Ok().json(entries))
+        }
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
@@ -1,3 +1,15 @@
+pub mod admin;
+pub mod audit;
 pub mod auth;
+pub mod chat;
+pub mod history;
+pub mod logins;
+pub mod password;
+pub mod policy;
+pub mod presence;
 pub mod register;
+pub mod role;
 pub mod secret;
+pub mod session;
+pub mod users;
+pub mod ws;
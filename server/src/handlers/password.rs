@@ -0,0 +1,130 @@
+use crate::{
+    actors::chat_server::{ChatServer, CloseSessions, RevokedSessions},
+    handlers::{
+        audit::record,
+        history::{check_password_history, push_password_history},
+        register::{check_breached, validate_password},
+        secret::{generate_key, read_secret_key, replace_secret_key},
+        session::revoke_other_sessions,
+        users::update_user,
+    },
+    models::{
+        audit::RequestInfo,
+        error::ServiceError,
+        history::{HistoryConfig, HistoryEntry},
+        language::RequestLanguage,
+        session::LoggedIn,
+    },
+    utils::{
+        breach::BreachCorpus,
+        password::{hash_password, verify, HashParameters},
+    },
+};
+use actix::Addr;
+use actix_web::{web, HttpResponse};
+use arangors::{ClientError, Connection};
+use serde_json::Value;
+use shared::models::{audit::AuditAction, auth::PasswordChange, policy::PasswordPolicy};
+use std::{collections::HashMap, sync::Arc};
+
+/// Change the password of the logged user
+/// The secret of the user is rotated and the other sessions are revoked
+pub async fn change_password(
+    payload: web::Json<PasswordChange>,
+    logged: LoggedIn,
+    connection: web::Data<Arc<Connection>>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    hash_parameters: web::Data<Arc<HashParameters>>,
+    history: web::Data<Arc<HistoryConfig>>,
+    policy: web::Data<Arc<PasswordPolicy>>,
+    breach: web::Data<Arc<BreachCorpus>>,
+    RequestLanguage(language): RequestLanguage,
+    info: RequestInfo,
+) -> Result<HttpResponse, ServiceError> {
+    let username = logged.user.canonical_username.clone();
+    let secret = read_secret_key(connection.clone(), username.clone()).await?;
+
+    let valid = verify(logged.user.hash(), payload.current_password(), &secret)?;
+    if !valid {
+        return Err(ServiceError::BadRequest(
+            "Your current password is wrong".to_string(),
+        ));
+    }
+    let user_inputs = [
+        logged.user.username.as_str(),
+        logged.user.emails.first().map_or("", String::as_str),
+        logged.user.first_name.as_str(),
+        logged.user.last_name.as_str(),
+    ];
+    validate_password(payload.new_password(), &user_inputs, &policy, language)?;
+    check_breached(payload.new_password(), breach, language).await?;
+    if verify(logged.user.hash(), payload.new_password(), &secret)? {
+        return Err(ServiceError::BadRequest(
+            "Your new password must be different".to_string(),
+        ));
+    }
+    check_password_history(
+        connection.clone(),
+        &username,
+        payload.new_password(),
+        &history,
+    )
+    .await?;
+
+    let new_secret = generate_key();
+    let hash = hash_password(payload.new_password(), &new_secret, &hash_parameters)?;
+
+    let entry = HistoryEntry::new(username.clone(), hash.clone(), new_secret.clone());
+    replace_secret_key(connection.clone(), username.clone(), new_secret).await?;
+    if let Err(err) = store_hash(connection.clone(), &username, hash).await {
+        // Put back the previous secret so the current password still works
+        replace_secret_key(connection, username, secret).await?;
+        return Err(err);
+    }
+    push_password_history(connection.clone(), entry, &history).await?;
+    if logged.user.password_reset_required {
+        let mut changes = HashMap::new();
+        changes.insert("password_reset_required", Value::from(false));
+        update_user(connection.clone(), &username, changes).await?;
+    }
+
+    revoke_other_sessions(connection.clone(), &username, &logged.session).await?;
+    chat_server.do_send(CloseSessions {
+        username: username.clone(),
+        revoked: RevokedSessions::Others(logged.session.clone()),
+    });
+    let event = info.event(
+        AuditAction::PasswordChanged,
+        Some(&username),
+        Some(&username),
+    );
+    record(connection, event).await;
+    Ok(HttpResponse::Ok().json("Your password has been changed"))
+}
+
+/// Replace the password hash of the user
+pub async fn store_hash(
+    connection: web::Data<Arc<Connection>>,
+    username: &str,
+    hash: String,
+) -> Result<(), ServiceError> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let mut map = HashMap::new();
+    map.insert("username", serde_json::to_value(username).unwrap());
+    map.insert("hash", serde_json::to_value(hash).unwrap());
+    let res: Result<Vec<serde_json::Value>, ClientError> = database
+        .aql_bind_vars(
+            "FOR u in users FILTER u.canonical_username == @username UPDATE u WITH { hash: @hash } IN users",
+            map,
+        )
+        .await;
+
+    res.map(|_| ()).map_err(|err| {
+        eprintln!("Error happened :{:?}", err);
+        ServiceError::InternalServerError
+    })
+}
@@ -0,0 +1,9 @@
+use actix_web::{web, HttpResponse};
+use shared::models::policy::PasswordPolicy;
+use std::sync::Arc;
+
+/// Give the password policy so the client can check passwords as we do
+pub async fn get_policy(policy: web::Data<Arc<PasswordPolicy>>) -> HttpResponse {
+    let policy: &PasswordPolicy = &policy;
+    HttpResponse::Ok().json(policy)
+}
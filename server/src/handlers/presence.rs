@@ -0,0 +1,51 @@
+use crate::{
+    actors::chat_server::{ChatServer, GetPresence},
+    models::{error::ServiceError, session::LoggedIn},
+};
+use actix::Addr;
+use actix_web::{web, HttpResponse};
+use arangors::{ClientError, Connection};
+use std::{collections::HashMap, sync::Arc};
+
+/// Who is online or away among the users sharing a room with the user asking,
+/// the others are offline
+/// Changes are pushed on the WebSocket afterwards
+pub async fn snapshot(
+    connection: web::Data<Arc<Connection>>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    logged: LoggedIn,
+) -> Result<HttpResponse, ServiceError> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let mut map = HashMap::new();
+    map.insert(
+        "username",
+        serde_json::to_value(&logged.user.canonical_username).unwrap(),
+    );
+    let res: Result<Vec<String>, ClientError> = database
+        .aql_bind_vars(
+            "FOR m in memberships FILTER m.username == @username
+                FOR o in memberships FILTER o.room == m.room AND o.username != @username
+                return DISTINCT o.username",
+            map,
+        )
+        .await;
+    let usernames = match res {
+        Ok(usernames) => usernames,
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            return Err(ServiceError::InternalServerError);
+        }
+    };
+
+    match chat_server.send(GetPresence { usernames }).await {
+        Ok(presence) => Ok(HttpResponse::Ok().json(presence)),
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
@@ -1,28 +1,78 @@
 use crate::{
-    handlers::secret::create_secret_key,
+    handlers::{
+        audit::record,
+        history::push_password_history,
+        secret::{create_secret_key, remove_secret_key},
+    },
     models::{
+        audit::RequestInfo,
         error::{ServiceError, ServiceError::BadRequest},
-        user::FullUser,
+        history::{HistoryConfig, HistoryEntry},
+        language::RequestLanguage,
+        user::{FullUser, RegistrationConfig},
     },
+    utils::{breach::BreachCorpus, password::HashParameters},
 };
 use actix_web::{web, HttpResponse};
-use arangors::{document::options::InsertOptions, Connection};
-use shared::models::{power::Power, user::User};
-use std::sync::Arc;
+use arangors::{
+    document::options::InsertOptions,
+    index::{Index, IndexSettings},
+    ClientError, Connection,
+};
+use serde::Deserialize;
+use shared::{
+    i18n::{Language, Message},
+    models::{
+        audit::AuditAction,
+        policy::PasswordPolicy,
+        user::{AccountStatus, User},
+    },
+    username::{canonical, confusable_skeleton},
+    validation::{validate_user, Field, FieldError},
+};
+use std::{collections::HashMap, sync::Arc};
+
+/// ArangoDB error number of an insert breaking a unique index
+const UNIQUE_CONSTRAINT_VIOLATED: u16 = 1210;
+/// Collections outside of `users` linking their documents to a user by its
+/// canonical username, with their database
+const LINKED_TO_USERNAME: [(&str, &str); 3] = [
+    ("tiny_avocado_tree", "sessions"),
+    ("avocado_trunk", "roots"),
+    ("avocado_trunk", "history"),
+];
+
+/// A user stored before usernames had a canonical form
+#[derive(Deserialize)]
+struct LegacyUser {
+    #[serde(rename = "_key")]
+    key: String,
+    username: String,
+}
 
 /// Register a user on the db
 pub async fn register_user(
     user_payload: web::Json<User>,
     connection: web::Data<Arc<Connection>>,
+    hash_parameters: web::Data<Arc<HashParameters>>,
+    history: web::Data<Arc<HistoryConfig>>,
+    policy: web::Data<Arc<PasswordPolicy>>,
+    breach: web::Data<Arc<BreachCorpus>>,
+    registration: web::Data<Arc<RegistrationConfig>>,
+    RequestLanguage(language): RequestLanguage,
+    info: RequestInfo,
 ) -> Result<HttpResponse, ServiceError> {
     // todo make different db name for environment
 
-    let user = validate_and_unwrap(user_payload);
+    let user = validate_and_unwrap(user_payload, &policy, language);
 
     if user.is_err() {
         return Err(user.unwrap_err());
     }
     let user = user.unwrap();
+    check_breached(user.credentials.password(), breach, language).await?;
+    let canonical_username = canonical(user.credentials.username());
+    check_username_available(connection.clone(), user.credentials.username()).await?;
 
     let database = connection
         .db("tiny_avocado_tree")
@@ -35,45 +85,324 @@ pub async fn register_user(
         .expect("Should load the collection");
 
     // todo add global secret so to have control to validate or invalidate
-    let secret = create_secret_key(connection, user.credentials.username().to_string()).await;
+    let secret = create_secret_key(connection.clone(), canonical_username.clone()).await;
 
     if secret.is_err() {
         return Err(ServiceError::InternalServerError);
     }
-    let full_user = FullUser::create_new_from_user_with_hash(user, secret.unwrap().as_str());
+    let secret = secret.unwrap();
+    let status = if registration.requires_approval {
+        AccountStatus::Pending
+    } else {
+        AccountStatus::Active
+    };
+    let full_user =
+        FullUser::create_new_from_user_with_hash(user, secret.as_str(), &hash_parameters, status);
+    let entry = HistoryEntry::new(
+        full_user.canonical_username.clone(),
+        full_user.hash().to_string(),
+        secret.clone(),
+    );
 
     let new_user = collection
         .create_document(full_user, InsertOptions::builder().return_new(true).build())
         .await;
 
-    if new_user.is_ok() {
-        let full_user_doc = new_user.unwrap();
-        let full_user = full_user_doc.new_doc().unwrap();
-        Ok(HttpResponse::Ok().json(full_user.map_to_info()))
-    } else {
-        Err(ServiceError::InternalServerError)
+    match new_user {
+        Ok(full_user_doc) => {
+            // Undo the registration, a retry would find the username taken
+            if let Err(err) = push_password_history(connection.clone(), entry, &history).await {
+                remove_user(connection.clone(), &canonical_username).await?;
+                remove_secret_key(connection, &canonical_username, &secret).await?;
+                return Err(err);
+            }
+            let event = info.event(
+                AuditAction::Registration,
+                Some(&canonical_username),
+                Some(&canonical_username),
+            );
+            record(connection, event).await;
+            let full_user = full_user_doc.new_doc().unwrap();
+            Ok(HttpResponse::Ok().json(full_user.map_to_info()))
+        }
+        // Someone registered the same canonical username since the check,
+        // the secret of the existing account must stay the only one
+        Err(ClientError::Arango(err)) if err.error_num() == UNIQUE_CONSTRAINT_VIOLATED => {
+            remove_secret_key(connection, &canonical_username, &secret).await?;
+            Err(username_taken())
+        }
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
+
+/// Reject a username already used in its canonical form or looking like an
+/// existing one
+async fn check_username_available(
+    connection: web::Data<Arc<Connection>>,
+    username: &str,
+) -> Result<(), ServiceError> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let mut map = HashMap::new();
+    map.insert(
+        "canonical",
+        serde_json::to_value(canonical(username)).unwrap(),
+    );
+    map.insert(
+        "skeleton",
+        serde_json::to_value(confusable_skeleton(username)).unwrap(),
+    );
+    let res: Result<Vec<String>, ClientError> = database
+        .aql_bind_vars(
+            "FOR u in users
+                FILTER u.canonical_username == @canonical OR u.username_skeleton == @skeleton
+                LIMIT 1 return u.username",
+            map,
+        )
+        .await;
+
+    match res {
+        Ok(users) if users.is_empty() => Ok(()),
+        Ok(_) => Err(username_taken()),
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
+
+/// Remove a user that was just inserted
+async fn remove_user(
+    connection: web::Data<Arc<Connection>>,
+    canonical_username: &str,
+) -> Result<(), ServiceError> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let mut map = HashMap::new();
+    map.insert(
+        "username",
+        serde_json::to_value(canonical_username).unwrap(),
+    );
+    let res: Result<Vec<serde_json::Value>, ClientError> = database
+        .aql_bind_vars(
+            "FOR u in users FILTER u.canonical_username == @username REMOVE u IN users",
+            map,
+        )
+        .await;
+
+    res.map(|_| ()).map_err(|err| {
+        eprintln!("Error happened :{:?}", err);
+        ServiceError::InternalServerError
+    })
+}
+
+fn username_taken() -> ServiceError {
+    ServiceError::InvalidFields(vec![FieldError {
+        field: Field::Username,
+        message: Message::new("validation.username_taken"),
+    }])
+}
+
+/// Give the users stored before canonical usernames their canonical form and
+/// skeleton, moving their secret, sessions and password history to it, then
+/// make sure the unique index on the canonical form exists
+/// Run at startup, it does nothing once every user is migrated
+pub async fn migrate_usernames(connection: web::Data<Arc<Connection>>) -> Result<(), String> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let legacy: Vec<LegacyUser> = database
+        .aql_bind_vars(
+            "FOR u in users FILTER u.canonical_username == null
+                return { _key: u._key, username: u.username }",
+            HashMap::new(),
+        )
+        .await
+        .map_err(|err| format!("{:?}", err))?;
+
+    // Nothing is written while two users would share a canonical username,
+    // their secrets, sessions and history would be merged
+    let clashing = clashing_usernames(connection.clone(), &legacy).await?;
+    if !clashing.is_empty() {
+        let clashing: Vec<String> = clashing.iter().map(|group| group.join(", ")).collect();
+        return Err(format!(
+            "users sharing a canonical username must be renamed first: {}",
+            clashing.join("; ")
+        ));
     }
+
+    for user in legacy {
+        let canonical_username = canonical(&user.username);
+        // The user goes last, an interrupted migration is done again
+        for (db, collection) in LINKED_TO_USERNAME.iter() {
+            let linked = connection.db(db).await.expect("Should load the db");
+            let mut map = HashMap::new();
+            map.insert("username", serde_json::to_value(&user.username).unwrap());
+            map.insert(
+                "canonical",
+                serde_json::to_value(&canonical_username).unwrap(),
+            );
+            let aql = format!(
+                "FOR d in {collection} FILTER d.username == @username
+                    UPDATE d WITH {{ username: @canonical }} IN {collection}",
+                collection = collection
+            );
+            let _: Vec<serde_json::Value> = linked
+                .aql_bind_vars(&aql, map)
+                .await
+                .map_err(|err| format!("{:?}", err))?;
+        }
+        let mut map = HashMap::new();
+        map.insert("key", serde_json::to_value(&user.key).unwrap());
+        map.insert(
+            "canonical",
+            serde_json::to_value(&canonical_username).unwrap(),
+        );
+        map.insert(
+            "skeleton",
+            serde_json::to_value(confusable_skeleton(&user.username)).unwrap(),
+        );
+        let _: Vec<serde_json::Value> = database
+            .aql_bind_vars(
+                "UPDATE @key WITH { canonical_username: @canonical, username_skeleton: @skeleton }
+                    IN users",
+                map,
+            )
+            .await
+            .map_err(|err| format!("{:?}", err))?;
+    }
+
+    // Creating it again gives the existing one
+    let index = Index::builder()
+        .fields(vec!["canonical_username".to_string()])
+        .settings(IndexSettings::Persistent {
+            unique: true,
+            sparse: false,
+            deduplicate: false,
+        })
+        .build();
+    database
+        .create_index("users", &index)
+        .await
+        .map(|_| ())
+        .map_err(|err| format!("{:?}", err))
+}
+
+/// Groups of usernames sharing a canonical form, once the legacy users get
+/// theirs
+async fn clashing_usernames(
+    connection: web::Data<Arc<Connection>>,
+    legacy: &[LegacyUser],
+) -> Result<Vec<Vec<String>>, String> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+    let legacy: Vec<serde_json::Value> = legacy
+        .iter()
+        .map(|user| {
+            serde_json::json!({
+                "username": user.username,
+                "canonical": canonical(&user.username),
+            })
+        })
+        .collect();
+    let mut map = HashMap::new();
+    map.insert("legacy", serde_json::to_value(legacy).unwrap());
+    database
+        .aql_bind_vars(
+            "LET migrated = (FOR u in users FILTER u.canonical_username != null
+                    return { username: u.username, canonical: u.canonical_username })
+                FOR u in APPEND(migrated, @legacy)
+                    COLLECT canonical = u.canonical INTO usernames = u.username
+                    FILTER LENGTH(usernames) > 1
+                    return usernames",
+            map,
+        )
+        .await
+        .map_err(|err| format!("{:?}", err))
 }
 
 /// Check the user input on the user object
-fn validate_and_unwrap(user: web::Json<User>) -> Result<User, ServiceError> {
-    if user.last_name.is_empty() {
-        Err(BadRequest("Last name cannot be empty".to_string()))
-    } else if user.first_name.is_empty() {
-        Err(BadRequest("First name cannot be empty".to_string()))
-    } else if user.credentials.username().is_empty() {
-        // todo add check that the username does not already exist
-        Err(BadRequest("Username cannot be empty".to_string()))
-    } else if user.credentials.email().is_empty() {
-        //todo add better validation for email
-        //todo check that email is not already taken
-        Err(BadRequest("Email cannot be empty".to_string()))
-    } else if user.credentials.password().is_empty() {
-        //todo add better validation for password as well
-        Err(BadRequest("Password cannot be empty".to_string()))
-    } else if Power::calculate_power(user.credentials.password().to_string()) < 101 {
-        Err(BadRequest("Password is too wek".to_string()))
+fn validate_and_unwrap(
+    user: web::Json<User>,
+    policy: &PasswordPolicy,
+    language: Language,
+) -> Result<User, ServiceError> {
+    let errors = validate_user(&user);
+    if !errors.is_empty() {
+        return Err(ServiceError::InvalidFields(errors));
+    }
+    //todo check that email is not already taken
+    let user_inputs = [
+        user.credentials.username(),
+        user.credentials.email(),
+        user.first_name.as_str(),
+        user.last_name.as_str(),
+    ];
+    validate_password(user.credentials.password(), &user_inputs, policy, language)?;
+    Ok(user.into_inner())
+}
+
+/// Check a new password against our password policy
+/// User inputs are what an attacker could know about the user, the errors are
+/// written in the language of the request
+pub fn validate_password(
+    password: &str,
+    user_inputs: &[&str],
+    policy: &PasswordPolicy,
+    language: Language,
+) -> Result<(), ServiceError> {
+    let failed: Vec<String> = policy
+        .check(password, user_inputs)
+        .into_iter()
+        .filter(|check| !check.passed)
+        .map(|check| check.rule.message().translate(language))
+        .collect();
+
+    if password.is_empty() {
+        Err(BadRequest(
+            Message::new("error.empty_password").translate(language),
+        ))
+    } else if !failed.is_empty() {
+        Err(BadRequest(
+            Message::new("error.policy")
+                .with("rules", failed.join(", "))
+                .translate(language),
+        ))
     } else {
-        Ok(user.into_inner())
+        Ok(())
+    }
+}
+
+/// Reject a password found in the breached passwords corpus
+/// The ranges are read from disk, away from the async workers
+pub async fn check_breached(
+    password: &str,
+    breach: web::Data<Arc<BreachCorpus>>,
+    language: Language,
+) -> Result<(), ServiceError> {
+    let password = password.to_string();
+    let breach = breach.get_ref().clone();
+    match web::block(move || breach.occurrences(&password)).await {
+        Ok(0) => Ok(()),
+        Ok(_) => Err(BadRequest(
+            Message::new("error.breached").translate(language),
+        )),
+        Err(err) => {
+            eprintln!("Could not check the breached passwords corpus :{:?}", err);
+            Err(ServiceError::InternalServerError)
+        }
     }
 }
@@ -0,0 +1,101 @@
+use crate::models::error::ServiceError;
+use actix_web::web;
+use arangors::{ClientError, Connection};
+use shared::{models::role::Role, username::canonical};
+use std::{collections::HashMap, sync::Arc};
+
+/// Replace the roles of a user, the user role is always kept and each role
+/// is stored once
+/// Return false if the user does not exist
+pub async fn set_roles(
+    connection: web::Data<Arc<Connection>>,
+    username: &str,
+    roles: &[Role],
+) -> Result<bool, ServiceError> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let mut unique = vec![Role::User];
+    for role in roles {
+        if !unique.contains(role) {
+            unique.push(role.clone());
+        }
+    }
+    let mut map = HashMap::new();
+    map.insert("username", serde_json::to_value(username).unwrap());
+    map.insert("roles", serde_json::to_value(&unique).unwrap());
+    let res: Result<Vec<String>, ClientError> = database
+        .aql_bind_vars(
+            "FOR u in users FILTER u.canonical_username == @username
+                UPDATE u WITH { roles: @roles } IN users OPTIONS { mergeObjects: false }
+                RETURN NEW.username",
+            map,
+        )
+        .await;
+
+    match res {
+        Ok(updated) => Ok(!updated.is_empty()),
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
+
+/// Give the admin role to a user, keeping its other roles
+pub async fn grant_admin(
+    connection: web::Data<Arc<Connection>>,
+    username: &str,
+) -> Result<(), String> {
+    let username = canonical(username);
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let mut map = HashMap::new();
+    map.insert("username", serde_json::to_value(&username).unwrap());
+    let res: Result<Vec<String>, ClientError> = database
+        .aql_bind_vars(
+            r#"FOR u in users FILTER u.canonical_username == @username
+                UPDATE u WITH { roles: UNION_DISTINCT(u.roles || ["user"], ["admin"]) } IN users
+                RETURN NEW.username"#,
+            map,
+        )
+        .await;
+
+    match res {
+        Ok(updated) if updated.is_empty() => Err(format!(
+            "{} is not registered, create the account first",
+            username
+        )),
+        Ok(_) => Ok(()),
+        Err(err) => Err(format!("{:?}", err)),
+    }
+}
+
+/// Make the configured user admin if nobody is admin yet
+pub async fn bootstrap_admin(
+    connection: web::Data<Arc<Connection>>,
+    username: &str,
+) -> Result<(), String> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let res: Result<Vec<u64>, ClientError> = database
+        .aql_bind_vars(
+            r#"RETURN LENGTH(FOR u in users FILTER "admin" IN u.roles return 1)"#,
+            HashMap::new(),
+        )
+        .await;
+
+    match res {
+        Ok(counts) if matches!(counts.first(), Some(count) if *count > 0) => Ok(()),
+        Ok(_) => grant_admin(connection, username).await,
+        Err(err) => Err(format!("{:?}", err)),
+    }
+}
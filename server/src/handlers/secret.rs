@@ -29,7 +29,7 @@ pub async fn create_secret_key(
         Err(ServiceError::InternalServerError)
     }
 }
-fn generate_key() -> String {
+pub fn generate_key() -> String {
     let mut rng = thread_rng();
     let chars: String = iter::repeat(())
         .map(|()| rng.sample(Alphanumeric))
@@ -73,3 +73,59 @@ pub async fn read_secret_key(
         Err(ServiceError::InternalServerError)
     }
 }
+
+/// Replace the secret of the user, every hash made with the previous one
+/// cannot be verified anymore
+pub async fn replace_secret_key(
+    connection: web::Data<Arc<Connection>>,
+    username: String,
+    secret: String,
+) -> Result<(), ServiceError> {
+    let database = connection.db("avocado_trunk").await.unwrap();
+
+    let roots = Roots::new(secret, username);
+    let mut map = HashMap::new();
+    map.insert("username", serde_json::to_value(roots.username()).unwrap());
+    map.insert("main", serde_json::to_value(roots.main()).unwrap());
+    map.insert(
+        "created_at",
+        serde_json::to_value(&roots.created_at).unwrap(),
+    );
+    let res: Result<Vec<serde_json::Value>, ClientError> = database
+        .aql_bind_vars(
+            "FOR r in roots FILTER r.username == @username
+                UPDATE r WITH { main: @main, created_at: @created_at } IN roots",
+            map,
+        )
+        .await;
+
+    res.map(|_| ()).map_err(|err| {
+        eprintln!("Error happened :{:?}", err);
+        ServiceError::InternalServerError
+    })
+}
+
+/// Forget a secret created for an account which could not be stored
+pub async fn remove_secret_key(
+    connection: web::Data<Arc<Connection>>,
+    username: &str,
+    secret: &str,
+) -> Result<(), ServiceError> {
+    let database = connection.db("avocado_trunk").await.unwrap();
+
+    let mut map = HashMap::new();
+    map.insert("username", serde_json::to_value(username).unwrap());
+    map.insert("main", serde_json::to_value(secret).unwrap());
+    let res: Result<Vec<serde_json::Value>, ClientError> = database
+        .aql_bind_vars(
+            "FOR r in roots FILTER r.username == @username AND r.main == @main
+                REMOVE r IN roots",
+            map,
+        )
+        .await;
+
+    res.map(|_| ()).map_err(|err| {
+        eprintln!("Error happened :{:?}", err);
+        ServiceError::InternalServerError
+    })
+}
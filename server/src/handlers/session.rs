@@ -0,0 +1,145 @@
+use crate::models::{error::ServiceError, session::Session, user::FullUser};
+use actix_web::web;
+use arangors::{document::options::InsertOptions, ClientError, Connection};
+use shared::models::user::AccountStatus;
+use std::{collections::HashMap, sync::Arc};
+
+/// Create a new session for the user and return its key
+pub async fn create_session(
+    connection: web::Data<Arc<Connection>>,
+    username: String,
+) -> Result<String, ServiceError> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+    let collection = database
+        .collection("sessions")
+        .await
+        .expect("Should load the collection");
+    let session = Session::new(username);
+    let key = session.key().to_string();
+    let new_session = collection
+        .create_document(session, InsertOptions::builder().silent(true).build())
+        .await;
+
+    if new_session.is_ok() {
+        Ok(key)
+    } else {
+        Err(ServiceError::InternalServerError)
+    }
+}
+
+/// Get the user behind a session which is not revoked
+pub async fn read_session_user(
+    connection: web::Data<Arc<Connection>>,
+    session: String,
+) -> Result<FullUser, ServiceError> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let mut map = HashMap::new();
+    map.insert("session", serde_json::to_value(session).unwrap());
+    let res: Result<Vec<FullUser>, ClientError> = database
+        .aql_bind_vars(
+            "FOR s in sessions FILTER s._key == @session AND s.revoked == false
+                FOR u in users FILTER u.canonical_username == s.username return u",
+            map,
+        )
+        .await;
+
+    // A suspended or deleted account keeps its sessions unusable
+    match res {
+        Ok(mut users) => users
+            .pop()
+            .filter(|user| user.account.status == AccountStatus::Active)
+            .ok_or(ServiceError::Unauthorized),
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
+
+/// Revoke every session of the user except the one given
+pub async fn revoke_other_sessions(
+    connection: web::Data<Arc<Connection>>,
+    username: &str,
+    current_session: &str,
+) -> Result<(), ServiceError> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let mut map = HashMap::new();
+    map.insert("username", serde_json::to_value(username).unwrap());
+    map.insert("session", serde_json::to_value(current_session).unwrap());
+    let res: Result<Vec<serde_json::Value>, ClientError> = database
+        .aql_bind_vars(
+            "FOR s in sessions FILTER s.username == @username AND s._key != @session
+                UPDATE s WITH { revoked: true } IN sessions",
+            map,
+        )
+        .await;
+
+    res.map(|_| ()).map_err(|err| {
+        eprintln!("Error happened :{:?}", err);
+        ServiceError::InternalServerError
+    })
+}
+
+/// Revoke every session of the user, used when the account cannot login
+/// anymore
+pub async fn revoke_all_sessions(
+    connection: web::Data<Arc<Connection>>,
+    username: &str,
+) -> Result<(), ServiceError> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let mut map = HashMap::new();
+    map.insert("username", serde_json::to_value(username).unwrap());
+    let res: Result<Vec<serde_json::Value>, ClientError> = database
+        .aql_bind_vars(
+            "FOR s in sessions FILTER s.username == @username
+                UPDATE s WITH { revoked: true } IN sessions",
+            map,
+        )
+        .await;
+
+    res.map(|_| ()).map_err(|err| {
+        eprintln!("Error happened :{:?}", err);
+        ServiceError::InternalServerError
+    })
+}
+
+/// Revoke a single session, used to logout
+pub async fn revoke_session(
+    connection: web::Data<Arc<Connection>>,
+    session: &str,
+) -> Result<(), ServiceError> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let mut map = HashMap::new();
+    map.insert("session", serde_json::to_value(session).unwrap());
+    let res: Result<Vec<serde_json::Value>, ClientError> = database
+        .aql_bind_vars(
+            "FOR s in sessions FILTER s._key == @session
+                UPDATE s WITH { revoked: true } IN sessions",
+            map,
+        )
+        .await;
+
+    res.map(|_| ()).map_err(|err| {
+        eprintln!("Error happened :{:?}", err);
+        ServiceError::InternalServerError
+    })
+}
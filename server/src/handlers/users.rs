@@ -0,0 +1,271 @@
+use crate::{
+    actors::chat_server::{ChatServer, CloseSessions, RevokedSessions},
+    handlers::{admin::apply_status, audit::record, session::revoke_all_sessions},
+    models::{audit::RequestInfo, error::ServiceError, session::LoggedIn, user::FullUser},
+};
+use actix::Addr;
+use actix_web::{web, HttpResponse};
+use arangors::{ClientError, Connection};
+use serde::Deserialize;
+use serde_json::Value;
+use shared::{
+    models::{
+        admin::{NamesChange, SortOrder, UserPage, UserQuery, UserSort},
+        audit::AuditAction,
+        role::Permission,
+        user::AccountStatus,
+    },
+    username::canonical,
+    validation::{validate_name, Field, FieldError},
+};
+use std::{collections::HashMap, sync::Arc};
+
+/// Users matching the filters and how many there are on every page
+#[derive(Deserialize)]
+struct MatchingUsers {
+    total: u64,
+    users: Vec<FullUser>,
+}
+
+/// Find a user by its canonical username
+pub async fn find_user(
+    connection: web::Data<Arc<Connection>>,
+    username: &str,
+) -> Result<FullUser, ServiceError> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let mut map = HashMap::new();
+    map.insert("username", serde_json::to_value(username).unwrap());
+    let res: Result<Vec<FullUser>, ClientError> = database
+        .aql_bind_vars(
+            "FOR u in users FILTER u.canonical_username == @username return u",
+            map,
+        )
+        .await;
+    match res {
+        Ok(mut users) => users
+            .pop()
+            .ok_or_else(|| ServiceError::BadRequest("This user does not exist".to_string())),
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
+
+/// List the users page by page, filtered and sorted
+pub async fn list_users(
+    query: web::Query<UserQuery>,
+    connection: web::Data<Arc<Connection>>,
+    logged: LoggedIn,
+) -> Result<HttpResponse, ServiceError> {
+    logged.require(Permission::ManageUsers)?;
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    // Sort attribute and direction cannot be bind parameters, they come from
+    // our enums only
+    let sort = match query.sort {
+        UserSort::Username => "u.canonical_username",
+        UserSort::LastName => "LOWER(u.last_name)",
+        UserSort::RegisteredAt => "u.registered_at",
+        UserSort::Status => "u.account.status",
+    };
+    let order = match query.order {
+        SortOrder::Asc => "ASC",
+        SortOrder::Desc => "DESC",
+    };
+    let aql = format!(
+        r#"LET matching = (
+            FOR u in users
+                FILTER @status == null OR (u.account.status || "active") == @status
+                FILTER @role == null OR @role IN (u.roles || ["user"])
+                FILTER @registered_after == null OR u.registered_at >= @registered_after
+                FILTER @registered_before == null OR u.registered_at < @registered_before
+                FILTER @search == null OR CONTAINS(
+                    LOWER(CONCAT_SEPARATOR(" ", u.username, u.first_name, u.last_name, u.emails)),
+                    LOWER(@search))
+                return u
+        )
+        RETURN {{
+            total: LENGTH(matching),
+            users: (FOR u in matching SORT {} {} LIMIT @offset, @limit return u)
+        }}"#,
+        sort, order
+    );
+
+    let mut map = HashMap::new();
+    map.insert("status", serde_json::to_value(query.status).unwrap());
+    map.insert("role", serde_json::to_value(query.role).unwrap());
+    map.insert(
+        "registered_after",
+        serde_json::to_value(&query.registered_after).unwrap(),
+    );
+    map.insert(
+        "registered_before",
+        serde_json::to_value(&query.registered_before).unwrap(),
+    );
+    let search = query.search.as_ref().filter(|search| !search.is_empty());
+    map.insert("search", serde_json::to_value(search).unwrap());
+    map.insert("offset", serde_json::to_value(query.offset()).unwrap());
+    map.insert("limit", serde_json::to_value(query.limit()).unwrap());
+    let res: Result<Vec<MatchingUsers>, ClientError> =
+        database.aql_bind_vars(aql.as_str(), map).await;
+
+    match res {
+        Ok(mut pages) => {
+            let matching = pages.pop().ok_or(ServiceError::InternalServerError)?;
+            Ok(HttpResponse::Ok().json(UserPage {
+                users: matching
+                    .users
+                    .iter()
+                    .map(FullUser::map_to_managed)
+                    .collect(),
+                total: matching.total,
+                page: query.page,
+                per_page: query.limit(),
+            }))
+        }
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
+
+pub async fn get_user(
+    username: web::Path<String>,
+    connection: web::Data<Arc<Connection>>,
+    logged: LoggedIn,
+) -> Result<HttpResponse, ServiceError> {
+    logged.require(Permission::ManageUsers)?;
+    let user = find_user(connection, &canonical(&username)).await?;
+    Ok(HttpResponse::Ok().json(user.map_to_managed()))
+}
+
+/// Edit the first and last names of a user
+pub async fn edit_user(
+    username: web::Path<String>,
+    payload: web::Json<NamesChange>,
+    connection: web::Data<Arc<Connection>>,
+    logged: LoggedIn,
+    info: RequestInfo,
+) -> Result<HttpResponse, ServiceError> {
+    logged.require(Permission::ManageUsers)?;
+    let mut errors = Vec::new();
+    if let Err(message) = validate_name(&payload.first_name) {
+        errors.push(FieldError {
+            field: Field::FirstName,
+            message,
+        });
+    }
+    if let Err(message) = validate_name(&payload.last_name) {
+        errors.push(FieldError {
+            field: Field::LastName,
+            message,
+        });
+    }
+    if !errors.is_empty() {
+        return Err(ServiceError::InvalidFields(errors));
+    }
+
+    let mut changes = HashMap::new();
+    changes.insert("first_name", Value::from(payload.first_name.trim()));
+    changes.insert("last_name", Value::from(payload.last_name.trim()));
+    let username = canonical(&username);
+    let user = update_user(connection.clone(), &username, changes).await?;
+    let event = info.event(
+        AuditAction::UserEdited,
+        Some(&logged.user.canonical_username),
+        Some(&username),
+    );
+    record(connection, event).await;
+    Ok(HttpResponse::Ok().json(user.map_to_managed()))
+}
+
+/// Make the user choose a new password, its sessions are revoked so it has to
+/// login again
+pub async fn force_password_reset(
+    username: web::Path<String>,
+    connection: web::Data<Arc<Connection>>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    logged: LoggedIn,
+    info: RequestInfo,
+) -> Result<HttpResponse, ServiceError> {
+    logged.require(Permission::ManageUsers)?;
+    let username = canonical(&username);
+    let mut changes = HashMap::new();
+    changes.insert("password_reset_required", Value::from(true));
+    let user = update_user(connection.clone(), &username, changes).await?;
+    revoke_all_sessions(connection.clone(), &username).await?;
+    chat_server.do_send(CloseSessions {
+        username: username.clone(),
+        revoked: RevokedSessions::All,
+    });
+    let event = info.event(
+        AuditAction::PasswordResetForced,
+        Some(&logged.user.canonical_username),
+        Some(&username),
+    );
+    record(connection, event).await;
+    Ok(HttpResponse::Ok().json(user.map_to_managed()))
+}
+
+/// Soft delete, the account stays with the deleted status
+pub async fn delete_user(
+    username: web::Path<String>,
+    connection: web::Data<Arc<Connection>>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    logged: LoggedIn,
+    info: RequestInfo,
+) -> Result<HttpResponse, ServiceError> {
+    logged.require(Permission::ManageUsers)?;
+    let state = apply_status(
+        connection,
+        &chat_server,
+        &logged,
+        &info,
+        &canonical(&username),
+        AccountStatus::Deleted,
+        None,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(state))
+}
+
+/// Merge changes into a user and return it updated
+pub async fn update_user(
+    connection: web::Data<Arc<Connection>>,
+    username: &str,
+    changes: HashMap<&str, Value>,
+) -> Result<FullUser, ServiceError> {
+    let database = connection
+        .db("tiny_avocado_tree")
+        .await
+        .expect("Should load the db");
+
+    let mut map = HashMap::new();
+    map.insert("username", serde_json::to_value(username).unwrap());
+    map.insert("changes", serde_json::to_value(changes).unwrap());
+    let res: Result<Vec<FullUser>, ClientError> = database
+        .aql_bind_vars(
+            "FOR u in users FILTER u.canonical_username == @username
+                UPDATE u WITH @changes IN users RETURN NEW",
+            map,
+        )
+        .await;
+    match res {
+        Ok(mut users) => users
+            .pop()
+            .ok_or_else(|| ServiceError::BadRequest("This user does not exist".to_string())),
+        Err(err) => {
+            eprintln!("Error happened :{:?}", err);
+            Err(ServiceError::InternalServerError)
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use crate::{
+    actors::{chat_server::ChatServer, chat_session::ChatSession},
+    models::session::LoggedIn,
+};
+use actix::Addr;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+use arangors::Connection;
+use std::sync::Arc;
+
+/// Upgrade to the chat WebSocket, authenticated by the identity cookie like
+/// the REST handlers
+pub async fn connect(
+    req: HttpRequest,
+    stream: web::Payload,
+    logged: LoggedIn,
+    connection: web::Data<Arc<Connection>>,
+    chat_server: web::Data<Addr<ChatServer>>,
+) -> Result<HttpResponse, Error> {
+    let session = ChatSession::new(
+        logged.user,
+        logged.session,
+        chat_server.get_ref().clone(),
+        connection,
+    );
+    ws::start(session, &req, stream)
+}
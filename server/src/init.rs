@@ -1,3 +1,13 @@
+use crate::{
+    models::{
+        audit::AuditConfig, chat::ChatConfig, history::HistoryConfig, user::RegistrationConfig,
+    },
+    utils::{
+        breach::BreachCorpus,
+        mailer::{LogMailer, Mailer, OutboxMailer},
+        password::HashParameters,
+    },
+};
 use arangors::Connection;
 use env_logger::Env;
 use rustls::{
@@ -5,7 +15,8 @@ use rustls::{
     NoClientAuth, ServerConfig,
 };
 use serde::Deserialize;
-use std::{fs::File, io::BufReader};
+use shared::models::policy::{CharacterClass, PasswordPolicy};
+use std::{fs::File, io::BufReader, sync::Arc};
 /// Struct to init variable for config for server
 #[derive(Default, Deserialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
@@ -32,6 +43,69 @@ pub struct Init {
     /// data in staging, we have production data, & production build
     /// in production,  the code is actually used by our customers
     mode: String,
+    /// argon2 memory cost in kibibytes for new password hashes
+    #[serde(default = "default_password_memory_size")]
+    password_memory_size: u32,
+    /// argon2 number of iterations for new password hashes
+    #[serde(default = "default_password_iterations")]
+    password_iterations: u32,
+    /// argon2 lanes for new password hashes
+    #[serde(default = "default_password_lanes")]
+    password_lanes: u32,
+    /// argon2 variant for new password hashes -> argon2d, argon2i, argon2id
+    #[serde(default = "default_password_variant")]
+    password_variant: String,
+    /// how many previous passwords of a user cannot be used again
+    #[serde(default = "default_password_history")]
+    password_history: usize,
+    /// directory of the offline breached passwords ranges, no check if missing
+    #[serde(default)]
+    breached_passwords_path: Option<String>,
+    /// minimum number of characters of a password
+    #[serde(default = "default_password_min_length")]
+    password_min_length: usize,
+    /// maximum number of characters of a password
+    #[serde(default = "default_password_max_length")]
+    password_max_length: usize,
+    /// classes a password must contain -> lowercase, uppercase, digit, symbol
+    #[serde(default)]
+    password_required_classes: Vec<CharacterClass>,
+    /// minimum power of a password, from 0 to 255
+    #[serde(default = "default_password_min_power")]
+    password_min_power: u8,
+    /// words a password cannot contain
+    #[serde(default)]
+    password_banned_substrings: Vec<String>,
+    /// new accounts wait for an admin approval before they can login
+    #[serde(default)]
+    registration_requires_approval: bool,
+    /// registered user made admin on startup when there is no admin yet
+    #[serde(default)]
+    admin_username: Option<String>,
+    /// days an audit event is kept
+    #[serde(default = "default_audit_retention_days")]
+    audit_retention_days: i64,
+    /// minutes during which authors can edit and delete their chat messages
+    #[serde(default = "default_message_edit_window_minutes")]
+    message_edit_window_minutes: i64,
+    /// sender of the emails
+    #[serde(default = "default_mail_from")]
+    mail_from: String,
+    /// pickup directory where the emails are written, printed if missing
+    #[serde(default)]
+    mail_outbox_path: Option<String>,
+}
+
+fn default_audit_retention_days() -> i64 {
+    365
+}
+
+fn default_message_edit_window_minutes() -> i64 {
+    15
+}
+
+fn default_mail_from() -> String {
+    "no-reply@localhost".to_string()
 }
 
 fn default_domain() -> String {
@@ -42,6 +116,38 @@ fn default_workers() -> usize {
     1
 }
 
+fn default_password_memory_size() -> u32 {
+    4096
+}
+
+fn default_password_iterations() -> u32 {
+    192
+}
+
+fn default_password_lanes() -> u32 {
+    1
+}
+
+fn default_password_variant() -> String {
+    "argon2id".to_string()
+}
+
+fn default_password_history() -> usize {
+    5
+}
+
+fn default_password_min_length() -> usize {
+    PasswordPolicy::default().min_length
+}
+
+fn default_password_max_length() -> usize {
+    PasswordPolicy::default().max_length
+}
+
+fn default_password_min_power() -> u8 {
+    PasswordPolicy::default().min_power
+}
+
 /// Init fails if one fails
 impl Init {
     /// Instantiate init with value from .env file
@@ -54,12 +160,74 @@ impl Init {
         serde_json::from_reader(reader).unwrap()
     }
 
+    pub fn audit_config(&self) -> AuditConfig {
+        AuditConfig {
+            retention_days: self.audit_retention_days,
+        }
+    }
+
+    pub fn chat_config(&self) -> ChatConfig {
+        ChatConfig {
+            edit_window_minutes: self.message_edit_window_minutes,
+        }
+    }
+
+    pub fn mailer(&self) -> Arc<dyn Mailer> {
+        match &self.mail_outbox_path {
+            Some(directory) => {
+                Arc::new(OutboxMailer::new(directory.clone(), self.mail_from.clone()))
+            }
+            None => {
+                eprintln!("No mail outbox, the emails will be printed");
+                Arc::new(LogMailer)
+            }
+        }
+    }
+
+    pub fn admin_username(&self) -> Option<&str> {
+        self.admin_username.as_deref()
+    }
     pub fn workers(&self) -> &usize {
         &self.workers
     }
     pub fn domain(&self) -> &str {
         &self.domain
     }
+    /// Build the argon2 parameters, panic if the variant is unknown
+    pub fn hash_parameters(&self) -> HashParameters {
+        HashParameters::new(
+            self.password_memory_size,
+            self.password_iterations,
+            self.password_lanes,
+            &self.password_variant,
+        )
+        .expect("Should have a valid argon2 variant")
+    }
+    pub fn breach_corpus(&self) -> BreachCorpus {
+        if self.breached_passwords_path.is_none() {
+            eprintln!("No breached passwords corpus, passwords will not be checked against it");
+        }
+        BreachCorpus::new(self.breached_passwords_path.clone())
+    }
+    pub fn password_policy(&self) -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: self.password_min_length,
+            max_length: self.password_max_length,
+            required_classes: self.password_required_classes.clone(),
+            min_power: self.password_min_power,
+            banned_substrings: self.password_banned_substrings.clone(),
+        }
+    }
+    pub fn history_config(&self) -> HistoryConfig {
+        HistoryConfig {
+            size: self.password_history,
+        }
+    }
+    pub fn registration_config(&self) -> RegistrationConfig {
+        RegistrationConfig {
+            requires_approval: self.registration_requires_approval,
+        }
+    }
     pub fn build_ssl_config(&self) -> ServerConfig {
         // todo this looks like good stuff but need to spend more time to make better
         // config
@@ -1,21 +1,29 @@
+mod actors;
 mod handlers;
 mod init;
 
 use crate::{
-    handlers::{auth, register},
+    actors::chat_server::ChatServer,
+    handlers::{
+        admin, audit, auth, chat, logins, password, policy, presence, register, role, users, ws,
+    },
     init::Init,
 };
+use actix::Actor;
 use actix_files::{Files, NamedFile};
 use actix_identity::{CookieIdentityPolicy, IdentityService};
 use actix_web::{
     middleware::Logger, web, App, FromRequest, HttpRequest, HttpServer, Responder, Result,
 };
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 mod models;
 
 mod utils;
 
+/// How often the expired audit events are removed
+const AUDIT_PURGE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
 async fn index() -> Result<NamedFile> {
     Ok(NamedFile::open("../client/index.html")?)
 }
@@ -28,10 +36,61 @@ async fn main() -> std::io::Result<()> {
 
     let conn = init.connect_db().await;
     let conn = Arc::new(conn);
+    if let Err(err) = register::migrate_usernames(web::Data::new(conn.clone())).await {
+        eprintln!("Could not migrate the usernames: {}", err);
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, err));
+    }
+    if let Err(err) = chat::ensure_storage(web::Data::new(conn.clone())).await {
+        eprintln!("Could not prepare the chat collections: {}", err);
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, err));
+    }
+
+    // `server grant-admin <username>` gives the admin role and exits
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let [command, username] = args.as_slice() {
+        if command == "grant-admin" {
+            match role::grant_admin(web::Data::new(conn.clone()), username).await {
+                Ok(()) => println!("{} is now admin", username),
+                Err(err) => eprintln!("Could not make {} admin: {}", username, err),
+            }
+            return Ok(());
+        }
+    }
+    if let Some(username) = init.admin_username() {
+        if let Err(err) = role::bootstrap_admin(web::Data::new(conn.clone()), username).await {
+            eprintln!("Could not make {} admin: {}", username, err);
+        }
+    }
+    let hash_parameters = Arc::new(init.hash_parameters());
+    let history = Arc::new(init.history_config());
+    let breach = Arc::new(init.breach_corpus());
+    let policy = Arc::new(init.password_policy());
+    let registration = Arc::new(init.registration_config());
+    let audit_config = init.audit_config();
+    let chat_config = Arc::new(init.chat_config());
+    let mailer = init.mailer();
+    let chat_server = ChatServer::default().start();
+
+    let purge_connection = web::Data::new(conn.clone());
+    actix_rt::spawn(async move {
+        let mut interval = actix_rt::time::interval(AUDIT_PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            audit::purge_expired(purge_connection.clone(), &audit_config).await;
+        }
+    });
 
     let server = HttpServer::new(move || {
         App::new()
             .data(conn.clone())
+            .data(hash_parameters.clone())
+            .data(history.clone())
+            .data(breach.clone())
+            .data(policy.clone())
+            .data(registration.clone())
+            .data(mailer.clone())
+            .data(chat_server.clone())
+            .data(chat_config.clone())
             .wrap(IdentityService::new(
                 CookieIdentityPolicy::new(&[0; 32])
                     .name("auth")
@@ -54,8 +113,87 @@ async fn main() -> std::io::Result<()> {
                         web::resource("/register").route(web::post().to(register::register_user)),
                     )
                     .service(web::resource("/auth").route(web::post().to(auth::login)))
+                    .service(web::resource("/logout").route(web::post().to(auth::logout)))
+                    .service(web::resource("/me/logins").route(web::get().to(logins::list_logins)))
+                    .service(
+                        web::resource("/rooms")
+                            .route(web::get().to(chat::list_rooms))
+                            .route(web::post().to(chat::create_room)),
+                    )
+                    .service(web::resource("/presence").route(web::get().to(presence::snapshot)))
+                    .service(
+                        web::resource("/conversations")
+                            .route(web::post().to(chat::open_conversation)),
+                    )
+                    .service(
+                        web::resource("/rooms/{room}/join").route(web::post().to(chat::join_room)),
+                    )
+                    .service(
+                        web::resource("/rooms/{room}/leave")
+                            .route(web::post().to(chat::leave_room)),
+                    )
+                    .service(
+                        web::resource("/rooms/{room}/receipts")
+                            .route(web::get().to(chat::receipts)),
+                    )
+                    .service(
+                        web::resource("/rooms/{room}/messages")
+                            .route(web::get().to(chat::history))
+                            .route(web::post().to(chat::post_message)),
+                    )
+                    .service(
+                        web::resource("/rooms/{room}/messages/{message}")
+                            .route(web::put().to(chat::edit_message))
+                            .route(web::delete().to(chat::delete_message)),
+                    )
+                    .service(
+                        web::resource("/rooms/{room}/messages/{message}/thread")
+                            .route(web::get().to(chat::thread)),
+                    )
+                    .service(
+                        web::resource("/rooms/{room}/messages/{message}/reactions/{emoji}")
+                            .route(web::put().to(chat::add_reaction))
+                            .route(web::delete().to(chat::remove_reaction)),
+                    )
+                    .service(web::resource("/policy").route(web::get().to(policy::get_policy)))
+                    .service(
+                        web::resource("/password/change")
+                            .route(web::post().to(password::change_password)),
+                    )
+                    .service(
+                        web::scope("/admin")
+                            .service(
+                                web::resource("/hash_report")
+                                    .route(web::get().to(admin::hash_report)),
+                            )
+                            .service(
+                                web::resource("/audit").route(web::get().to(audit::list_events)),
+                            )
+                            .service(
+                                web::resource("/users").route(web::get().to(users::list_users)),
+                            )
+                            .service(
+                                web::resource("/users/{username}")
+                                    .route(web::get().to(users::get_user))
+                                    .route(web::put().to(users::edit_user))
+                                    .route(web::delete().to(users::delete_user)),
+                            )
+                            .service(
+                                web::resource("/users/{username}/password_reset")
+                                    .route(web::post().to(users::force_password_reset)),
+                            )
+                            .service(
+                                web::resource("/users/{username}/status")
+                                    .route(web::post().to(admin::change_status)),
+                            )
+                            .service(
+                                web::resource("/users/{username}/roles")
+                                    .route(web::post().to(admin::change_roles)),
+                            ),
+                    )
                     .default_service(web::route().to(web::HttpResponse::NotFound)),
             )
+            .service(web::resource("/ws").route(web::get().to(ws::connect)))
             .service(Files::new("/pkg", "../client/pkg"))
             .default_service(web::get().to(index))
     })
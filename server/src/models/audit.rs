@@ -0,0 +1,51 @@
+use crate::utils::date::iso_now;
+use actix_web::{dev::Payload, http::header::USER_AGENT, FromRequest, HttpRequest};
+use shared::models::audit::{AuditAction, AuditEvent};
+use std::future::{ready, Ready};
+
+/// Where a request comes from, kept in the audit log
+pub struct RequestInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl RequestInfo {
+    pub fn event(
+        &self,
+        action: AuditAction,
+        actor: Option<&str>,
+        target: Option<&str>,
+    ) -> AuditEvent {
+        AuditEvent {
+            action,
+            actor: actor.map(str::to_string),
+            target: target.map(str::to_string),
+            ip: self.ip.clone(),
+            user_agent: self.user_agent.clone(),
+            created_at: iso_now(),
+            details: None,
+        }
+    }
+}
+
+impl FromRequest for RequestInfo {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let ip = req.peer_addr().map(|address| address.ip().to_string());
+        let user_agent = req
+            .headers()
+            .get(USER_AGENT)
+            .and_then(|header| header.to_str().ok())
+            .map(str::to_string);
+        ready(Ok(RequestInfo { ip, user_agent }))
+    }
+}
+
+/// How long audit events are kept, loaded from the config
+#[derive(Clone)]
+pub struct AuditConfig {
+    pub retention_days: i64,
+}
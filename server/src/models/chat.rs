@@ -0,0 +1,276 @@
+use crate::{models::user::FullUser, utils::date::iso_now};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use shared::models::chat::{Message, NewMessage, NewRoom, Reaction, Room, RoomKind, ThreadSummary};
+
+/// How long authors can change their messages, loaded from the config
+#[derive(Clone)]
+pub struct ChatConfig {
+    pub edit_window_minutes: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RoomDocument {
+    #[serde(rename = "_key")]
+    key: String,
+    name: String,
+    topic: Option<String>,
+    /// Rooms created before direct conversations are public
+    #[serde(default)]
+    kind: RoomKind,
+    /// Usernames of the members of a direct conversation as displayed
+    #[serde(default)]
+    participants: Vec<String>,
+    /// Username of the creator as displayed
+    created_by: String,
+    /// Should be ISO date
+    pub created_at: String,
+}
+
+impl RoomDocument {
+    pub fn new(room: &NewRoom, creator: &FullUser) -> Self {
+        RoomDocument {
+            key: uuid::Uuid::new_v4().to_string(),
+            name: room.name.trim().to_string(),
+            topic: room
+                .topic
+                .as_ref()
+                .map(|topic| topic.trim().to_string())
+                .filter(|topic| !topic.is_empty()),
+            kind: RoomKind::Public,
+            participants: Vec::new(),
+            created_by: creator.username.clone(),
+            created_at: iso_now(),
+        }
+    }
+
+    /// A direct conversation between `participants`, sorted by canonical
+    /// username without duplicates
+    /// Its key only depends on who takes part so opening it twice finds the
+    /// same room
+    pub fn direct(participants: &[FullUser], opener: &FullUser) -> Self {
+        let usernames: Vec<&str> = participants
+            .iter()
+            .map(|user| user.canonical_username.as_str())
+            .collect();
+        let names: Vec<String> = participants
+            .iter()
+            .map(|user| user.username.clone())
+            .collect();
+        RoomDocument {
+            key: hashed_key("direct", &usernames),
+            name: names.join(", "),
+            topic: None,
+            kind: RoomKind::Direct,
+            participants: names,
+            created_by: opener.username.clone(),
+            created_at: iso_now(),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn is_direct(&self) -> bool {
+        self.kind == RoomKind::Direct
+    }
+
+    pub fn to_room(&self) -> Room {
+        Room {
+            key: self.key.clone(),
+            name: self.name.clone(),
+            topic: self.topic.clone(),
+            kind: self.kind,
+            participants: self.participants.clone(),
+            created_by: self.created_by.clone(),
+            created_at: self.created_at.clone(),
+        }
+    }
+}
+
+/// Key only depending on `parts`, inserting the same document twice fails
+fn hashed_key(prefix: &str, parts: &[&str]) -> String {
+    let hash = Sha1::digest(parts.join("\n").as_bytes());
+    format!("{}-{}", prefix, hex::encode(hash))
+}
+
+/// A user being in a room
+#[derive(Serialize, Deserialize)]
+pub struct Membership {
+    /// Key of the room
+    pub room: String,
+    /// Canonical username of the member
+    pub username: String,
+    /// Should be ISO date
+    pub joined_at: String,
+    /// Key of the last message read
+    #[serde(default)]
+    pub last_read: Option<String>,
+    /// Date of the last message read, unread messages are the ones after it
+    #[serde(default)]
+    pub last_read_at: Option<String>,
+}
+
+impl Membership {
+    pub fn new(room: String, username: String) -> Self {
+        Membership {
+            room,
+            username,
+            joined_at: iso_now(),
+            last_read: None,
+            last_read_at: None,
+        }
+    }
+}
+
+/// A previous version of an edited message
+#[derive(Serialize, Deserialize)]
+pub struct Revision {
+    pub body: String,
+    /// When this version was written, should be ISO date
+    pub written_at: String,
+}
+
+/// Who deleted a message, its body stays in the document but is never served
+/// again
+#[derive(Serialize, Deserialize)]
+pub struct Deletion {
+    /// Canonical username
+    pub by: String,
+    /// Should be ISO date
+    pub at: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MessageDocument {
+    #[serde(rename = "_key")]
+    key: String,
+    /// Key of the room
+    room: String,
+    /// Canonical username of the author
+    pub author: String,
+    /// Username of the author as displayed
+    author_name: String,
+    body: String,
+    /// Should be ISO date
+    pub created_at: String,
+    /// Should be ISO date
+    #[serde(default)]
+    edited_at: Option<String>,
+    /// Previous versions, oldest first
+    #[serde(default)]
+    edits: Vec<Revision>,
+    #[serde(default)]
+    pub deleted: Option<Deletion>,
+    /// Key of the message replied to, in the (parent, created_at) index
+    #[serde(default)]
+    pub parent: Option<String>,
+}
+
+impl MessageDocument {
+    pub fn new(room: String, message: &NewMessage, author: &FullUser) -> Self {
+        MessageDocument {
+            key: uuid::Uuid::new_v4().to_string(),
+            room,
+            author: author.canonical_username.clone(),
+            author_name: author.username.clone(),
+            body: message.body.trim().to_string(),
+            created_at: iso_now(),
+            edited_at: None,
+            edits: Vec::new(),
+            deleted: None,
+            parent: message.parent.clone(),
+        }
+    }
+
+    /// Authors change their messages for a while after posting them
+    pub fn editable(&self, config: &ChatConfig) -> bool {
+        match chrono::DateTime::parse_from_rfc3339(&self.created_at) {
+            Ok(created_at) => {
+                chrono::Utc::now().signed_duration_since(created_at)
+                    <= chrono::Duration::minutes(config.edit_window_minutes)
+            }
+            Err(_) => false,
+        }
+    }
+
+    pub fn to_message(&self) -> Message {
+        Message {
+            key: self.key.clone(),
+            room: self.room.clone(),
+            author: self.author_name.clone(),
+            body: if self.deleted.is_some() {
+                String::new()
+            } else {
+                self.body.clone()
+            },
+            created_at: self.created_at.clone(),
+            edited_at: self.edited_at.clone(),
+            deleted: self.deleted.is_some(),
+            reactions: Vec::new(),
+            parent: self.parent.clone(),
+            thread: None,
+        }
+    }
+}
+
+/// A message of the timeline with its reactions and its thread, aggregated
+/// by the query
+#[derive(Deserialize)]
+pub struct TimelineRow {
+    pub message: MessageDocument,
+    pub reactions: Vec<Reaction>,
+    pub replies: u64,
+    pub last_reply: Option<MessageDocument>,
+}
+
+impl TimelineRow {
+    pub fn to_message(&self) -> Message {
+        let mut message = self.message.to_message();
+        if !message.deleted {
+            message.reactions = self.reactions.clone();
+        }
+        message.thread = self.last_reply.as_ref().map(|last_reply| ThreadSummary {
+            replies: self.replies,
+            last_reply: Box::new(last_reply.to_message()),
+        });
+        message
+    }
+}
+
+/// A user reacting to a message, once for each emoji
+#[derive(Serialize, Deserialize)]
+pub struct ReactionDocument {
+    #[serde(rename = "_key")]
+    key: String,
+    /// Key of the room
+    room: String,
+    /// Key of the message
+    message: String,
+    /// Canonical username
+    username: String,
+    /// Username as displayed
+    name: String,
+    emoji: String,
+    /// Should be ISO date
+    created_at: String,
+}
+
+impl ReactionDocument {
+    pub fn new(room: String, message: String, emoji: String, user: &FullUser) -> Self {
+        ReactionDocument {
+            key: hashed_key("reaction", &[&message, &user.canonical_username, &emoji]),
+            room,
+            message,
+            username: user.canonical_username.clone(),
+            name: user.username.clone(),
+            emoji,
+            created_at: iso_now(),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
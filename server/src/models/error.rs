@@ -1,5 +1,6 @@
 use actix_web::{error::ResponseError, HttpResponse};
 use derive_more::Display;
+use shared::validation::FieldError;
 
 #[derive(Debug, Display)]
 pub enum ServiceError {
@@ -11,6 +12,14 @@ pub enum ServiceError {
 
     #[display(fmt = "Unauthorized")]
     Unauthorized,
+
+    /// Logged but without the permission needed
+    #[display(fmt = "Forbidden")]
+    Forbidden,
+
+    /// Every invalid field of the payload, sent back with their message keys
+    #[display(fmt = "Invalid fields: {:?}", _0)]
+    InvalidFields(Vec<FieldError>),
 }
 
 // impl ResponseError trait allows to convert our errors into http responses
@@ -23,6 +32,10 @@ impl ResponseError for ServiceError {
             }
             ServiceError::BadRequest(ref message) => HttpResponse::BadRequest().json(message),
             ServiceError::Unauthorized => HttpResponse::Unauthorized().json("Unauthorized"),
+            ServiceError::Forbidden => HttpResponse::Forbidden().json("Forbidden"),
+            ServiceError::InvalidFields(ref errors) => {
+                HttpResponse::UnprocessableEntity().json(errors)
+            }
         }
     }
 }
@@ -0,0 +1,41 @@
+use crate::utils::date::iso_now;
+use serde::{Deserialize, Serialize};
+
+/// A password hash the user had, with the secret it was hashed under
+/// Kept in the trunk so old passwords cannot be used again
+#[derive(Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Canonical username of the user owning this password
+    username: String,
+    hash: String,
+    /// Secret Key used for this hash, it may have been rotated since
+    main: String,
+    /// Should be ISO date
+    pub created_at: String,
+}
+
+impl HistoryEntry {
+    pub fn new(username: String, hash: String, main: String) -> Self {
+        HistoryEntry {
+            username,
+            hash,
+            main,
+            created_at: iso_now(),
+        }
+    }
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+    pub fn main(&self) -> &str {
+        &self.main
+    }
+}
+
+/// How many previous passwords we remember per user
+#[derive(Clone)]
+pub struct HistoryConfig {
+    pub size: usize,
+}
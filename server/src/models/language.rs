@@ -0,0 +1,22 @@
+use actix_web::{dev::Payload, http::header::ACCEPT_LANGUAGE, FromRequest, HttpRequest};
+use shared::i18n::Language;
+use std::future::{ready, Ready};
+
+/// Language picked from the Accept-Language header of the request, English
+/// when the header is missing or nothing in it is supported
+pub struct RequestLanguage(pub Language);
+
+impl FromRequest for RequestLanguage {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let language = req
+            .headers()
+            .get(ACCEPT_LANGUAGE)
+            .and_then(|header| header.to_str().ok())
+            .map_or_else(Language::default, Language::from_accept_language);
+        ready(Ok(RequestLanguage(language)))
+    }
+}
@@ -0,0 +1,47 @@
+use crate::{models::audit::RequestInfo, utils::date::iso_now};
+use serde::{Deserialize, Serialize};
+use shared::models::login::{DeviceFingerprint, LoginEntry};
+
+/// A successful login, kept so the user can see where its account is used
+#[derive(Serialize, Deserialize)]
+pub struct LoginRecord {
+    /// Canonical username of the user who logged in
+    username: String,
+    /// Key of the session created by this login
+    session: String,
+    device: DeviceFingerprint,
+    ip: Option<String>,
+    user_agent: Option<String>,
+    /// Should be ISO date
+    pub created_at: String,
+    /// First login of the user from this device
+    pub new_device: bool,
+}
+
+impl LoginRecord {
+    pub fn new(username: String, session: String, info: &RequestInfo) -> Self {
+        LoginRecord {
+            username,
+            session,
+            device: DeviceFingerprint::new(info.user_agent.as_deref(), info.ip.as_deref()),
+            ip: info.ip.clone(),
+            user_agent: info.user_agent.clone(),
+            created_at: iso_now(),
+            new_device: false,
+        }
+    }
+    pub fn device(&self) -> &DeviceFingerprint {
+        &self.device
+    }
+
+    /// Shown to the user, the session key stays on the server
+    pub fn to_entry(&self, current_session: &str) -> LoginEntry {
+        LoginEntry {
+            device: self.device.clone(),
+            ip: self.ip.clone(),
+            created_at: self.created_at.clone(),
+            new_device: self.new_device,
+            current: self.session == current_session,
+        }
+    }
+}
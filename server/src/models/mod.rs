@@ -1,3 +1,9 @@
+pub mod audit;
+pub mod chat;
 pub mod error;
+pub mod history;
+pub mod language;
+pub mod login;
 pub mod roots;
+pub mod session;
 pub mod user;
@@ -5,7 +5,7 @@ use serde::{Deserialize, Serialize};
 pub struct Roots {
     /// Main Secret Key for hashing password
     main: String,
-    /// The user owning this secret Key
+    /// Canonical username of the user owning this secret Key
     username: String,
     /// Should be ISO date
     pub created_at: String,
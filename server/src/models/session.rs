@@ -0,0 +1,111 @@
+use crate::{
+    handlers::session::read_session_user, models::error::ServiceError, models::user::FullUser,
+};
+use actix_identity::RequestIdentity;
+use actix_web::{dev::Payload, web, FromRequest, HttpRequest};
+use arangors::Connection;
+use serde::{Deserialize, Serialize};
+use shared::models::role::{has_permission, Permission};
+use std::{future::Future, pin::Pin, sync::Arc};
+
+/// A login of a user, the key is what we remember in the identity cookie
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    #[serde(rename = "_key")]
+    key: String,
+    /// Canonical username of the user owning this session
+    username: String,
+    /// Should be ISO date
+    pub created_at: String,
+    /// A revoked session cannot be used anymore even if the cookie is still
+    /// valid
+    pub revoked: bool,
+}
+
+impl Session {
+    pub fn new(username: String) -> Self {
+        Session {
+            key: uuid::Uuid::new_v4().to_string(),
+            username,
+            created_at: chrono::Utc::now().to_string(),
+            revoked: false,
+        }
+    }
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+}
+
+/// Routes a user asked by an admin to choose a new password can still call
+const PASSWORD_RESET_ROUTES: [&str; 2] = ["/api/password/change", "/api/logout"];
+
+/// Extractor for handlers which need an authenticated user
+/// Fails with Unauthorized if there is no identity or the session is revoked,
+/// with Forbidden if the user must choose a new password first
+pub struct LoggedIn {
+    /// The key of the current session
+    pub session: String,
+    pub user: FullUser,
+}
+
+impl LoggedIn {
+    /// Guard for handlers restricted to some roles
+    pub fn require(&self, permission: Permission) -> Result<(), ServiceError> {
+        if has_permission(&self.user.roles, permission) {
+            Ok(())
+        } else {
+            Err(ServiceError::Forbidden)
+        }
+    }
+}
+
+impl FromRequest for LoggedIn {
+    type Error = ServiceError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let session = req.get_identity();
+        let connection = req.app_data::<web::Data<Arc<Connection>>>().cloned();
+        let path = req.path().to_string();
+
+        Box::pin(async move {
+            match (session, connection) {
+                (Some(session), Some(connection)) => {
+                    let user = read_session_user(connection, session.clone()).await?;
+                    if !reachable(user.password_reset_required, &path) {
+                        return Err(ServiceError::Forbidden);
+                    }
+                    Ok(LoggedIn { session, user })
+                }
+                _ => Err(ServiceError::Unauthorized),
+            }
+        })
+    }
+}
+
+/// Until the forced password change is done only the routes to do it or to
+/// leave are reachable, the client hides the rest but the API must refuse it
+fn reachable(password_reset_required: bool, path: &str) -> bool {
+    !password_reset_required || PASSWORD_RESET_ROUTES.contains(&path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::reachable;
+
+    #[test]
+    fn forced_password_reset_only_reaches_the_change() {
+        assert!(reachable(false, "/api/rooms"));
+        assert!(reachable(false, "/ws"));
+
+        assert!(reachable(true, "/api/password/change"));
+        assert!(reachable(true, "/api/logout"));
+        assert!(!reachable(true, "/api/rooms"));
+        assert!(!reachable(true, "/api/me/logins"));
+        assert!(!reachable(true, "/ws"));
+    }
+}
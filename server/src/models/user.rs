@@ -1,8 +1,16 @@
-use crate::utils::password::hash_password;
+use crate::utils::{
+    date::iso_now,
+    password::{hash_password, HashParameters},
+};
 use serde::{Deserialize, Serialize};
-use shared::models::{
-    auth::AuthData,
-    user::{LoggedUser, User},
+use shared::{
+    models::{
+        admin::ManagedUser,
+        auth::AuthData,
+        role::{default_roles, Role},
+        user::{AccountState, AccountStatus, LoggedUser, User},
+    },
+    username::{canonical, confusable_skeleton},
 };
 
 /// Full user model exclusive to back end
@@ -12,13 +20,43 @@ pub struct FullUser {
     pub last_name: String,
     hash: String,
     pub emails: Vec<String>,
+    /// Username as typed by the user, only used for display
     pub username: String,
+    /// Unique form of the username, used for lookups and to link the user
+    /// to its secret, sessions and password history
+    pub canonical_username: String,
+    /// Usernames looking alike share the same skeleton
+    pub username_skeleton: String,
+    /// Accounts created before the lifecycle are active
+    #[serde(default)]
+    pub account: AccountState,
+    #[serde(default = "default_roles")]
+    pub roles: Vec<Role>,
+    /// Should be ISO date
+    #[serde(default)]
+    pub registered_at: Option<String>,
+    /// Set by an admin, cleared once the user changed its password
+    #[serde(default)]
+    pub password_reset_required: bool,
+}
+
+/// How new accounts are created, loaded from the config
+#[derive(Clone)]
+pub struct RegistrationConfig {
+    /// New accounts stay pending until an admin activates them
+    pub requires_approval: bool,
 }
 
 impl FullUser {
     /// Map to a full User and use secret Key to create salty stuff
-    pub fn create_new_from_user_with_hash(user: User, secret_key: &str) -> FullUser {
-        let hash = hash_password(user.credentials.password(), secret_key).unwrap();
+    pub fn create_new_from_user_with_hash(
+        user: User,
+        secret_key: &str,
+        parameters: &HashParameters,
+        status: AccountStatus,
+    ) -> FullUser {
+        let hash = hash_password(user.credentials.password(), secret_key, parameters).unwrap();
+        let now = iso_now();
 
         FullUser {
             first_name: user.first_name,
@@ -26,6 +64,12 @@ impl FullUser {
             hash,
             emails: vec![user.credentials.email().to_string()],
             username: user.credentials.username().to_string(),
+            canonical_username: canonical(user.credentials.username()),
+            username_skeleton: confusable_skeleton(user.credentials.username()),
+            account: AccountState::new(status, now.clone(), None),
+            roles: default_roles(),
+            registered_at: Some(now),
+            password_reset_required: false,
         }
     }
 
@@ -42,16 +86,30 @@ impl FullUser {
         }
     }
 
+    /// Information shown to admins
+    pub fn map_to_managed(&self) -> ManagedUser {
+        ManagedUser {
+            info: self.map_to_info(),
+            account: self.account.clone(),
+            roles: self.roles.clone(),
+            registered_at: self.registered_at.clone(),
+            password_reset_required: self.password_reset_required,
+        }
+    }
+
     pub fn hash(&self) -> &str {
         &self.hash
     }
 
     pub fn to_logged_user(&self) -> LoggedUser {
-        LoggedUser::new(
+        let mut logged_user = LoggedUser::new(
             (&self.first_name).to_string(),
             (&self.last_name).to_string(),
             (&self.username).to_string(),
             (&self.emails.first().unwrap()).to_string(),
-        )
+            self.roles.clone(),
+        );
+        logged_user.password_reset_required = self.password_reset_required;
+        logged_user
     }
 }
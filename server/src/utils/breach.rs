@@ -0,0 +1,85 @@
+use sha1::{Digest, Sha1};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::PathBuf,
+};
+
+/// Offline corpus of breached passwords split in ranges like the
+/// k-anonymity API of haveibeenpwned
+/// The directory has one file per 5 hex characters prefix of the SHA-1, ex:
+/// `21BD1.txt`, each line is the rest of the hash and a count
+/// `0018A45C4D1DEF81644B54AB7F969B88D65:10`
+/// Ops ship or download it, we never call the network at runtime
+#[derive(Clone, Default)]
+pub struct BreachCorpus {
+    /// None disables the check
+    directory: Option<PathBuf>,
+}
+
+impl BreachCorpus {
+    pub fn new(directory: Option<String>) -> Self {
+        BreachCorpus {
+            directory: directory.map(PathBuf::from),
+        }
+    }
+
+    /// How many times the password appears in the corpus, 0 if never or if
+    /// the check is disabled
+    /// A missing or unreadable range is an error, the corpus is incomplete
+    pub fn occurrences(&self, password: &str) -> io::Result<u64> {
+        let directory = match &self.directory {
+            Some(directory) => directory,
+            None => return Ok(0),
+        };
+        let hash = hex::encode_upper(Sha1::digest(password.as_bytes()));
+        let (prefix, suffix) = hash.split_at(5);
+
+        let path = directory.join(format!("{}.txt", prefix));
+        let file = File::open(&path)
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))?;
+
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let mut parts = line.trim().splitn(2, ':');
+            if let (Some(range_suffix), Some(count)) = (parts.next(), parts.next()) {
+                if range_suffix.eq_ignore_ascii_case(suffix) {
+                    return Ok(count.parse().unwrap_or(0));
+                }
+            }
+        }
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BreachCorpus;
+    use std::fs;
+
+    #[test]
+    fn find_password_in_range() {
+        let directory = std::env::temp_dir().join("tiny_avocado_breach_test");
+        fs::create_dir_all(&directory).unwrap();
+        // SHA-1 of "password" is 5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8
+        fs::write(
+            directory.join("5BAA6.txt"),
+            "003D68EB55068C33ACE09247EE4C639306B:3\r\n\
+             1E4C9B93F3F0682250B6CF8331B7EE68FD8:9545824\r\n",
+        )
+        .unwrap();
+        // SHA-1 of "tiny avocado tree" is 4906A222A264BB415D279E8391A35C791B0EC0B2
+        fs::write(
+            directory.join("4906A.txt"),
+            "003D68EB55068C33ACE09247EE4C639306B:3\r\n",
+        )
+        .unwrap();
+
+        let corpus = BreachCorpus::new(Some(directory.to_string_lossy().to_string()));
+        assert_eq!(corpus.occurrences("password").unwrap(), 9_545_824);
+        assert_eq!(corpus.occurrences("tiny avocado tree").unwrap(), 0);
+        // The range of "walrus lantern meadow" is not in the directory
+        assert!(corpus.occurrences("walrus lantern meadow").is_err());
+        assert_eq!(BreachCorpus::default().occurrences("password").unwrap(), 0);
+    }
+}
@@ -0,0 +1,11 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+
+/// Current date as RFC 3339 with microseconds in UTC, always of the same
+/// width so stored dates sort as strings
+pub fn iso_now() -> String {
+    iso_date(Utc::now())
+}
+
+pub fn iso_date(date: DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
@@ -0,0 +1,88 @@
+use std::{fs, path::PathBuf};
+
+/// A plain text email
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Where the emails go, handlers only know this trait so the delivery can
+/// change without touching them
+pub trait Mailer: Send + Sync {
+    fn send(&self, mail: &Mail) -> Result<(), String>;
+}
+
+/// Print the emails in the logs, used when no outbox is configured
+pub struct LogMailer;
+
+impl Mailer for LogMailer {
+    fn send(&self, mail: &Mail) -> Result<(), String> {
+        println!(
+            "Mail to {}\nSubject: {}\n\n{}",
+            mail.to, mail.subject, mail.body
+        );
+        Ok(())
+    }
+}
+
+/// Write every email as a `.eml` file in a pickup directory, a local MTA or a
+/// relay is in charge of the delivery
+pub struct OutboxMailer {
+    directory: PathBuf,
+    from: String,
+}
+
+impl OutboxMailer {
+    pub fn new(directory: String, from: String) -> Self {
+        OutboxMailer {
+            directory: PathBuf::from(directory),
+            from,
+        }
+    }
+}
+
+impl Mailer for OutboxMailer {
+    fn send(&self, mail: &Mail) -> Result<(), String> {
+        let content = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}\r\n",
+            self.from,
+            mail.to,
+            mail.subject,
+            chrono::Utc::now().to_rfc2822(),
+            mail.body.replace('\n', "\r\n")
+        );
+        let path = self.directory.join(format!("{}.eml", uuid::Uuid::new_v4()));
+        fs::write(&path, content).map_err(|err| format!("{:?}", err))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Mail, Mailer, OutboxMailer};
+    use std::fs;
+
+    #[test]
+    fn write_mail_in_outbox() {
+        let directory = std::env::temp_dir().join("tiny_avocado_outbox_test");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+
+        let mailer = OutboxMailer::new(
+            directory.to_string_lossy().to_string(),
+            "no-reply@localhost".to_string(),
+        );
+        let mail = Mail {
+            to: "jane@example.com".to_string(),
+            subject: "Hello".to_string(),
+            body: "First line\nSecond line".to_string(),
+        };
+        assert!(mailer.send(&mail).is_ok());
+
+        let files: Vec<_> = fs::read_dir(&directory).unwrap().collect();
+        assert_eq!(files.len(), 1);
+        let content = fs::read_to_string(files[0].as_ref().unwrap().path()).unwrap();
+        assert!(content.contains("To: jane@example.com\r\n"));
+        assert!(content.ends_with("First line\r\nSecond line\r\n"));
+    }
+}
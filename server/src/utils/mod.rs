@@ -1 +1,4 @@
+pub mod breach;
+pub mod date;
+pub mod mailer;
 pub mod password;
@@ -1,11 +1,104 @@
-use argonautica::{Hasher, Verifier};
+use argonautica::{config::Variant, Hasher, Verifier};
+use serde::Serialize;
+use std::str::FromStr;
 
 use crate::models::error::ServiceError;
 
-// todo make code for production because of warning bellow
-// WARNING THIS IS ONLY FOR DEMO PLEASE DO MORE RESEARCH FOR PRODUCTION USE
-pub fn hash_password(password: &str, secret: &str) -> Result<String, ServiceError> {
+/// Argon2 parameters used to hash new passwords, loaded from the config
+#[derive(Clone, Debug)]
+pub struct HashParameters {
+    /// Memory cost in kibibytes
+    pub memory_size: u32,
+    /// Number of passes over the memory
+    pub iterations: u32,
+    /// Degree of parallelism, also used as the number of threads
+    pub lanes: u32,
+    pub variant: Variant,
+}
+
+impl HashParameters {
+    pub fn new(
+        memory_size: u32,
+        iterations: u32,
+        lanes: u32,
+        variant: &str,
+    ) -> Result<Self, String> {
+        let variant = Variant::from_str(variant)
+            .map_err(|_| format!("Unknown argon2 variant {}", variant))?;
+        Ok(HashParameters {
+            memory_size,
+            iterations,
+            lanes,
+            variant,
+        })
+    }
+
+    /// Tell if a hash has been made with parameters weaker or different than
+    /// ours and should be hashed again on the next successful login
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        match EncodedParameters::parse(hash) {
+            Some(encoded) => {
+                encoded.variant != self.variant.as_str()
+                    || encoded.memory_size < self.memory_size
+                    || encoded.iterations < self.iterations
+                    || encoded.lanes < self.lanes
+            }
+            None => true,
+        }
+    }
+}
+
+/// Parameters read back from an encoded argon2 hash
+/// ex: `$argon2id$v=19$m=4096,t=192,p=4$salt$hash`
+#[derive(Debug, Serialize, PartialEq)]
+pub struct EncodedParameters {
+    pub variant: String,
+    pub version: u32,
+    pub memory_size: u32,
+    pub iterations: u32,
+    pub lanes: u32,
+}
+
+impl EncodedParameters {
+    pub fn parse(hash: &str) -> Option<Self> {
+        let mut parts = hash.split('$').skip(1);
+        let variant = parts.next()?.to_string();
+        let version = parts.next()?.strip_prefix("v=")?.parse().ok()?;
+
+        let mut memory_size = None;
+        let mut iterations = None;
+        let mut lanes = None;
+        for param in parts.next()?.split(',') {
+            let value = param.splitn(2, '=').nth(1).and_then(|v| v.parse().ok());
+            match param.chars().next() {
+                Some('m') => memory_size = value,
+                Some('t') => iterations = value,
+                Some('p') => lanes = value,
+                _ => {}
+            }
+        }
+
+        Some(EncodedParameters {
+            variant,
+            version,
+            memory_size: memory_size?,
+            iterations: iterations?,
+            lanes: lanes?,
+        })
+    }
+}
+
+pub fn hash_password(
+    password: &str,
+    secret: &str,
+    parameters: &HashParameters,
+) -> Result<String, ServiceError> {
     Hasher::default()
+        .configure_memory_size(parameters.memory_size)
+        .configure_iterations(parameters.iterations)
+        .configure_lanes(parameters.lanes)
+        .configure_threads(parameters.lanes)
+        .configure_variant(parameters.variant)
         .with_password(password)
         .with_secret_key(secret)
         .hash()
@@ -15,6 +108,8 @@ pub fn hash_password(password: &str, secret: &str) -> Result<String, ServiceErro
         })
 }
 
+/// Verify a password, the parameters are read from the hash itself so old
+/// hashes still verify after the config changed
 pub fn verify(hash: &str, password: &str, secret: &str) -> Result<bool, ServiceError> {
     Verifier::default()
         .with_hash(hash)
@@ -26,3 +121,33 @@ pub fn verify(hash: &str, password: &str, secret: &str) -> Result<bool, ServiceE
             ServiceError::Unauthorized
         })
 }
+
+#[cfg(test)]
+mod test {
+    use super::{EncodedParameters, HashParameters};
+
+    #[test]
+    fn parse_encoded_hash() {
+        let parsed = EncodedParameters::parse("$argon2id$v=19$m=4096,t=192,p=4$c2FsdA$aGFzaA");
+        assert_eq!(
+            parsed,
+            Some(EncodedParameters {
+                variant: "argon2id".to_string(),
+                version: 19,
+                memory_size: 4096,
+                iterations: 192,
+                lanes: 4,
+            })
+        );
+        assert_eq!(EncodedParameters::parse("not a hash"), None);
+    }
+
+    #[test]
+    fn weaker_hash_needs_rehash() {
+        let parameters = HashParameters::new(8192, 192, 4, "argon2id").unwrap();
+
+        assert!(parameters.needs_rehash("$argon2id$v=19$m=4096,t=192,p=4$c2FsdA$aGFzaA"));
+        assert!(parameters.needs_rehash("$argon2i$v=19$m=8192,t=192,p=4$c2FsdA$aGFzaA"));
+        assert!(!parameters.needs_rehash("$argon2id$v=19$m=8192,t=192,p=4$c2FsdA$aGFzaA"));
+    }
+}
@@ -0,0 +1,409 @@
+//! Messages shown to the user, identified by stable keys so the client and
+//! the server translate them the same way
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Default)]
+pub enum Language {
+    #[default]
+    English,
+    French,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::French];
+
+    /// Language tag as used in the Accept-Language header
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::French => "fr",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Language> {
+        let primary = code.trim().split(['-', '_']).next()?;
+        Language::ALL
+            .iter()
+            .find(|language| language.code().eq_ignore_ascii_case(primary))
+            .cloned()
+    }
+
+    /// The supported language with the best quality in an Accept-Language
+    /// header, ex: `fr-CH, fr;q=0.9, en;q=0.8`
+    pub fn from_accept_language(header: &str) -> Language {
+        let mut best: Option<(Language, f32)> = None;
+        for part in header.split(',') {
+            let mut pieces = part.split(';');
+            let language = match pieces.next().and_then(Language::from_code) {
+                Some(language) => language,
+                None => continue,
+            };
+            let quality = pieces
+                .find_map(|piece| piece.trim().strip_prefix("q="))
+                .and_then(|q| q.parse().ok())
+                .unwrap_or(1.0);
+            let better = match best {
+                Some((_, best_quality)) => quality > best_quality,
+                None => true,
+            };
+            if better {
+                best = Some((language, quality));
+            }
+        }
+        best.map_or_else(Language::default, |(language, _)| language)
+    }
+
+    /// Name of the language in itself
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::French => "Français",
+        }
+    }
+}
+
+/// A message key with its named parameters
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Message {
+    pub key: String,
+    pub params: Vec<(String, String)>,
+}
+
+impl Message {
+    pub fn new(key: &str) -> Self {
+        Message {
+            key: key.to_string(),
+            params: Vec::new(),
+        }
+    }
+
+    pub fn with(mut self, name: &str, value: impl ToString) -> Self {
+        self.params.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Translate the message, fallback to English then to the key itself
+    pub fn translate(&self, language: Language) -> String {
+        let template =
+            CATALOG
+                .iter()
+                .find(|(key, _, _)| *key == self.key)
+                .map(|(_, english, french)| match language {
+                    Language::English => *english,
+                    Language::French => *french,
+                });
+        let mut text = match template {
+            Some(template) => template.to_string(),
+            None => return self.key.clone(),
+        };
+        for (name, value) in &self.params {
+            text = text.replace(&format!("{{{}}}", name), value);
+        }
+        text
+    }
+}
+
+/// Key, English, French
+/// Every entry carries both languages so a translation cannot be forgotten
+const CATALOG: &[(&str, &str, &str)] = &[
+    (
+        "power.label",
+        "Password power => {message}",
+        "Puissance du mot de passe => {message}",
+    ),
+    ("power.no_power", "Cannot live", "Ne peut pas vivre"),
+    (
+        "power.baby_power",
+        "I am a baby and I cry a lot",
+        "Je suis un bébé et je pleure beaucoup",
+    ),
+    ("power.low_power", "I am so weak", "Je suis si faible"),
+    ("power.medium_power", "I am ok", "Je vais bien"),
+    ("power.high_power", "I feel so good", "Je me sens si bien"),
+    (
+        "power.max_power",
+        "Mouahaha I am so OP",
+        "Mouahaha je suis trop fort",
+    ),
+    (
+        "warning.top_ten_password",
+        "This is a top-10 common password",
+        "C'est un des 10 mots de passe les plus courants",
+    ),
+    (
+        "warning.top_hundred_password",
+        "This is a top-100 common password",
+        "C'est un des 100 mots de passe les plus courants",
+    ),
+    (
+        "warning.common_password",
+        "This is a very common password",
+        "C'est un mot de passe très courant",
+    ),
+    (
+        "warning.similar_to_common_password",
+        "This is similar to a commonly used password",
+        "Cela ressemble à un mot de passe courant",
+    ),
+    (
+        "warning.word_by_itself",
+        "A word by itself is easy to guess",
+        "Un mot seul est facile à deviner",
+    ),
+    (
+        "warning.names_by_themselves",
+        "Names and surnames by themselves are easy to guess",
+        "Les prénoms et noms seuls sont faciles à deviner",
+    ),
+    (
+        "warning.common_names",
+        "Common names and surnames are easy to guess",
+        "Les prénoms et noms courants sont faciles à deviner",
+    ),
+    (
+        "warning.personal_information",
+        "This is based on your personal information",
+        "Cela contient vos informations personnelles",
+    ),
+    (
+        "warning.straight_row",
+        "Straight rows of keys are easy to guess",
+        "Les rangées de touches sont faciles à deviner",
+    ),
+    (
+        "warning.short_keyboard_pattern",
+        "Short keyboard patterns are easy to guess",
+        "Les motifs courts sur le clavier sont faciles à deviner",
+    ),
+    (
+        "warning.repeated_characters",
+        "Repeats like \"aaa\" are easy to guess",
+        "Les répétitions comme \"aaa\" sont faciles à deviner",
+    ),
+    (
+        "warning.repeated_pattern",
+        "Repeats like \"abcabcabc\" are only slightly harder to guess than \"abc\"",
+        "Les répétitions comme \"abcabcabc\" sont à peine plus dures à deviner que \"abc\"",
+    ),
+    (
+        "warning.sequence",
+        "Sequences like abc or 6543 are easy to guess",
+        "Les suites comme abc ou 6543 sont faciles à deviner",
+    ),
+    (
+        "warning.recent_year",
+        "Recent years are easy to guess",
+        "Les années récentes sont faciles à deviner",
+    ),
+    (
+        "warning.date",
+        "Dates are often easy to guess",
+        "Les dates sont souvent faciles à deviner",
+    ),
+    (
+        "suggestion.use_few_words",
+        "Use a few words, avoid common phrases",
+        "Utilisez quelques mots, évitez les phrases courantes",
+    ),
+    (
+        "suggestion.no_need_for_symbols",
+        "No need for symbols, digits, or uppercase letters",
+        "Pas besoin de symboles, de chiffres ou de majuscules",
+    ),
+    (
+        "suggestion.add_another_word",
+        "Add another word or two. Uncommon words are better",
+        "Ajoutez un mot ou deux. Les mots rares sont meilleurs",
+    ),
+    (
+        "suggestion.capitalization_does_not_help",
+        "Capitalization doesn't help very much",
+        "Une majuscule n'aide pas beaucoup",
+    ),
+    (
+        "suggestion.all_uppercase_does_not_help",
+        "All-uppercase is almost as easy to guess as all-lowercase",
+        "Tout en majuscules est presque aussi facile à deviner que tout en minuscules",
+    ),
+    (
+        "suggestion.reversed_words_do_not_help",
+        "Reversed words aren't much harder to guess",
+        "Les mots à l'envers ne sont pas beaucoup plus durs à deviner",
+    ),
+    (
+        "suggestion.predictable_substitutions",
+        "Predictable substitutions like '@' instead of 'a' don't help very much",
+        "Les substitutions prévisibles comme '@' au lieu de 'a' n'aident pas beaucoup",
+    ),
+    (
+        "suggestion.longer_keyboard_pattern",
+        "Use a longer keyboard pattern with more turns",
+        "Utilisez un motif de clavier plus long avec plus de changements de direction",
+    ),
+    (
+        "suggestion.avoid_repeats",
+        "Avoid repeated words and characters",
+        "Évitez les mots et caractères répétés",
+    ),
+    (
+        "suggestion.avoid_sequences",
+        "Avoid sequences",
+        "Évitez les suites",
+    ),
+    (
+        "suggestion.avoid_recent_years",
+        "Avoid recent years",
+        "Évitez les années récentes",
+    ),
+    (
+        "suggestion.avoid_personal_years",
+        "Avoid years that are associated with you",
+        "Évitez les années qui vous sont associées",
+    ),
+    (
+        "suggestion.avoid_personal_dates",
+        "Avoid dates and years that are associated with you",
+        "Évitez les dates et années qui vous sont associées",
+    ),
+    (
+        "suggestion.avoid_personal_information",
+        "Avoid your name, username or email in your password",
+        "Évitez votre nom, pseudo ou email dans votre mot de passe",
+    ),
+    (
+        "rule.min_length",
+        "At least {length} characters",
+        "Au moins {length} caractères",
+    ),
+    (
+        "rule.max_length",
+        "At most {length} characters",
+        "Au plus {length} caractères",
+    ),
+    (
+        "rule.lowercase",
+        "A lowercase letter",
+        "Une lettre minuscule",
+    ),
+    (
+        "rule.uppercase",
+        "An uppercase letter",
+        "Une lettre majuscule",
+    ),
+    ("rule.digit", "A digit", "Un chiffre"),
+    ("rule.symbol", "A symbol", "Un symbole"),
+    (
+        "rule.min_power",
+        "A power of at least {power}",
+        "Une puissance d'au moins {power}",
+    ),
+    (
+        "rule.no_banned_substring",
+        "No username, email, name or banned word",
+        "Ni pseudo, ni email, ni nom, ni mot interdit",
+    ),
+    (
+        "validation.required",
+        "This field is required",
+        "Ce champ est obligatoire",
+    ),
+    (
+        "validation.too_short",
+        "At least {min} characters",
+        "Au moins {min} caractères",
+    ),
+    (
+        "validation.too_long",
+        "At most {max} characters",
+        "Au plus {max} caractères",
+    ),
+    (
+        "validation.too_many_participants",
+        "At most {max} participants",
+        "Au plus {max} participants",
+    ),
+    (
+        "validation.username_charset",
+        "Only letters, digits, '.', '_' and '-', starting with a letter or a digit",
+        "Seulement des lettres, chiffres, '.', '_' et '-', en commençant par une lettre ou un chiffre",
+    ),
+    (
+        "validation.username_mixed_scripts",
+        "Do not mix letters of different alphabets",
+        "Ne mélangez pas les lettres de différents alphabets",
+    ),
+    (
+        "validation.username_taken",
+        "This username is taken or looks too much like another one",
+        "Ce pseudo est pris ou ressemble trop à un autre",
+    ),
+    (
+        "validation.email_syntax",
+        "This is not a valid email address",
+        "Ce n'est pas une adresse email valide",
+    ),
+    (
+        "error.empty_password",
+        "Password cannot be empty",
+        "Le mot de passe ne peut pas être vide",
+    ),
+    (
+        "error.policy",
+        "Password does not follow the policy: {rules}",
+        "Le mot de passe ne respecte pas la politique : {rules}",
+    ),
+    (
+        "error.breached",
+        "This password appeared in a data breach, please choose another one",
+        "Ce mot de passe est apparu dans une fuite de données, choisissez-en un autre",
+    ),
+    (
+        "mail.new_device.subject",
+        "New login to your account",
+        "Nouvelle connexion à votre compte",
+    ),
+    (
+        "mail.new_device.body",
+        "Hello {username},\n\nYour account was used from a new device: {device}, on {date}.\n\nIf it was not you, change your password now.",
+        "Bonjour {username},\n\nVotre compte a été utilisé depuis un nouvel appareil : {device}, le {date}.\n\nSi ce n'était pas vous, changez votre mot de passe maintenant.",
+    ),
+];
+
+#[cfg(test)]
+mod test {
+    use super::{Language, Message};
+
+    #[test]
+    fn translate_with_params() {
+        let message = Message::new("rule.min_length").with("length", 8);
+        assert_eq!(
+            message.translate(Language::English),
+            "At least 8 characters"
+        );
+        assert_eq!(message.translate(Language::French), "Au moins 8 caractères");
+        assert_eq!(
+            Message::new("unknown.key").translate(Language::French),
+            "unknown.key"
+        );
+    }
+
+    #[test]
+    fn validation_messages_are_translated() {
+        let message = Message::new("validation.too_short").with("min", 5);
+        assert_eq!(message.translate(Language::French), "Au moins 5 caractères");
+    }
+
+    #[test]
+    fn pick_language_from_header() {
+        assert_eq!(
+            Language::from_accept_language("fr-CH, fr;q=0.9, en;q=0.8"),
+            Language::French
+        );
+        assert_eq!(
+            Language::from_accept_language("de-DE, en;q=0.5, fr;q=0.7"),
+            Language::French
+        );
+        assert_eq!(Language::from_accept_language("de"), Language::English);
+    }
+}
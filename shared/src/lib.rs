@@ -1 +1,6 @@
+pub mod i18n;
 pub mod models;
+pub mod protocol;
+pub mod strength;
+pub mod username;
+pub mod validation;
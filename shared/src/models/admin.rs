@@ -0,0 +1,189 @@
+use crate::models::{
+    role::Role,
+    user::{AccountState, AccountStatus, User},
+};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_PER_PAGE: u32 = 20;
+pub const MAX_PER_PAGE: u32 = 100;
+
+/// An account as shown to admins
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+pub struct ManagedUser {
+    #[serde(flatten)]
+    pub info: User,
+    pub account: AccountState,
+    pub roles: Vec<Role>,
+    /// Should be ISO date, none for accounts older than this field
+    pub registered_at: Option<String>,
+    /// The user has to change its password on its next login
+    pub password_reset_required: bool,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UserSort {
+    #[default]
+    Username,
+    LastName,
+    RegisteredAt,
+    Status,
+}
+
+impl UserSort {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UserSort::Username => "username",
+            UserSort::LastName => "last_name",
+            UserSort::RegisteredAt => "registered_at",
+            UserSort::Status => "status",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        }
+    }
+}
+
+/// Query string of the admin user list, every filter is optional
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct UserQuery {
+    /// Starts at 0
+    #[serde(default)]
+    pub page: u32,
+    #[serde(default = "default_per_page")]
+    pub per_page: u32,
+    #[serde(default)]
+    pub sort: UserSort,
+    #[serde(default)]
+    pub order: SortOrder,
+    pub status: Option<AccountStatus>,
+    pub role: Option<Role>,
+    /// Dates as `2020-10-01`, compared with the registration date
+    pub registered_after: Option<String>,
+    pub registered_before: Option<String>,
+    /// Part of the username, names or email
+    pub search: Option<String>,
+}
+
+fn default_per_page() -> u32 {
+    DEFAULT_PER_PAGE
+}
+
+impl Default for UserQuery {
+    fn default() -> Self {
+        UserQuery {
+            page: 0,
+            per_page: DEFAULT_PER_PAGE,
+            sort: UserSort::default(),
+            order: SortOrder::default(),
+            status: None,
+            role: None,
+            registered_after: None,
+            registered_before: None,
+            search: None,
+        }
+    }
+}
+
+impl UserQuery {
+    /// Number of users per page, never more than the maximum
+    pub fn limit(&self) -> u32 {
+        self.per_page.clamp(1, MAX_PER_PAGE)
+    }
+
+    pub fn offset(&self) -> u32 {
+        self.page.saturating_mul(self.limit())
+    }
+
+    pub fn to_query_string(&self) -> String {
+        let mut params = vec![
+            format!("page={}", self.page),
+            format!("per_page={}", self.limit()),
+            format!("sort={}", self.sort.as_str()),
+            format!("order={}", self.order.as_str()),
+        ];
+        if let Some(status) = self.status {
+            params.push(format!("status={}", status.as_str()));
+        }
+        if let Some(role) = self.role {
+            params.push(format!("role={}", role.as_str()));
+        }
+        let texts = [
+            ("registered_after", &self.registered_after),
+            ("registered_before", &self.registered_before),
+            ("search", &self.search),
+        ];
+        for (name, value) in texts.iter() {
+            if let Some(value) = value.as_ref().filter(|value| !value.is_empty()) {
+                params.push(format!("{}={}", name, encode_component(value)));
+            }
+        }
+        params.join("&")
+    }
+}
+
+/// Percent encoding of everything but the unreserved characters
+fn encode_component(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// One page of the admin user list
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+pub struct UserPage {
+    pub users: Vec<ManagedUser>,
+    /// Number of users matching the filters on every page
+    pub total: u64,
+    pub page: u32,
+    pub per_page: u32,
+}
+
+/// Payload of an admin editing the names of a user
+#[derive(Debug, Deserialize, Serialize, Default)]
+pub struct NamesChange {
+    pub first_name: String,
+    pub last_name: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::{UserQuery, UserSort};
+    use crate::models::user::AccountStatus;
+
+    #[test]
+    fn build_query_string() {
+        let query = UserQuery {
+            page: 2,
+            sort: UserSort::RegisteredAt,
+            status: Some(AccountStatus::Suspended),
+            search: Some("élo die".to_string()),
+            ..UserQuery::default()
+        };
+        assert_eq!(
+            query.to_query_string(),
+            "page=2&per_page=20&sort=registered_at&order=asc&status=suspended&search=%C3%A9lo%20die"
+        );
+        assert_eq!(query.offset(), 40);
+    }
+}
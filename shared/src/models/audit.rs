@@ -0,0 +1,103 @@
+use crate::models::admin::{DEFAULT_PER_PAGE, MAX_PER_PAGE};
+use serde::{Deserialize, Serialize};
+
+/// What happened, events are only appended and never edited
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Registration,
+    LoginSucceeded,
+    LoginFailed,
+    Logout,
+    PasswordChanged,
+    RolesChanged,
+    StatusChanged,
+    UserEdited,
+    PasswordResetForced,
+    /// A moderator deleted the chat message of someone else
+    MessageDeleted,
+}
+
+impl AuditAction {
+    pub const ALL: [AuditAction; 10] = [
+        AuditAction::Registration,
+        AuditAction::LoginSucceeded,
+        AuditAction::LoginFailed,
+        AuditAction::Logout,
+        AuditAction::PasswordChanged,
+        AuditAction::RolesChanged,
+        AuditAction::StatusChanged,
+        AuditAction::UserEdited,
+        AuditAction::PasswordResetForced,
+        AuditAction::MessageDeleted,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuditAction::Registration => "registration",
+            AuditAction::LoginSucceeded => "login_succeeded",
+            AuditAction::LoginFailed => "login_failed",
+            AuditAction::Logout => "logout",
+            AuditAction::PasswordChanged => "password_changed",
+            AuditAction::RolesChanged => "roles_changed",
+            AuditAction::StatusChanged => "status_changed",
+            AuditAction::UserEdited => "user_edited",
+            AuditAction::PasswordResetForced => "password_reset_forced",
+            AuditAction::MessageDeleted => "message_deleted",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AuditEvent {
+    pub action: AuditAction,
+    /// Canonical username of who did it, none when not logged
+    pub actor: Option<String>,
+    /// Canonical username of the user it was done to
+    pub target: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    /// Should be ISO date
+    pub created_at: String,
+    /// What changed or why it failed
+    pub details: Option<String>,
+}
+
+/// Query string of the audit log, every filter is optional
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AuditQuery {
+    pub action: Option<AuditAction>,
+    pub actor: Option<String>,
+    pub target: Option<String>,
+    pub ip: Option<String>,
+    /// Dates as `2020-10-01`, compared with the date of the event
+    pub after: Option<String>,
+    pub before: Option<String>,
+    /// Starts at 0, most recent events first
+    #[serde(default)]
+    pub page: u32,
+    #[serde(default = "default_per_page")]
+    pub per_page: u32,
+}
+
+fn default_per_page() -> u32 {
+    DEFAULT_PER_PAGE
+}
+
+impl AuditQuery {
+    pub fn limit(&self) -> u32 {
+        self.per_page.clamp(1, MAX_PER_PAGE)
+    }
+
+    pub fn offset(&self) -> u32 {
+        self.page.saturating_mul(self.limit())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct AuditPage {
+    pub events: Vec<AuditEvent>,
+    pub total: u64,
+    pub page: u32,
+    pub per_page: u32,
+}
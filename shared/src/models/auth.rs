@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-#[derive(Debug, Deserialize, Serialize, Default)]
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
 /// Base Credential used for user authentication
 pub struct AuthData {
     email: String,
@@ -55,3 +55,28 @@ impl LoginCredentials {
         self.password = password;
     }
 }
+
+/// Payload to change the password of the logged user
+#[derive(Debug, Deserialize, Serialize, Default)]
+pub struct PasswordChange {
+    current_password: String,
+    new_password: String,
+}
+
+impl PasswordChange {
+    pub fn current_password(&self) -> &str {
+        &self.current_password
+    }
+
+    pub fn new_password(&self) -> &str {
+        &self.new_password
+    }
+
+    pub fn set_current_password(&mut self, password: String) {
+        self.current_password = password;
+    }
+
+    pub fn set_new_password(&mut self, password: String) {
+        self.new_password = password;
+    }
+}
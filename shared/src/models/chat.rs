@@ -0,0 +1,257 @@
+use serde::{Deserialize, Serialize};
+
+/// How many messages are sent at once when no limit is asked
+pub const DEFAULT_HISTORY_LIMIT: u32 = 50;
+pub const MAX_HISTORY_LIMIT: u32 = 200;
+/// Longest reaction in characters, an emoji can be made of several
+const REACTION_MAX_LENGTH: usize = 8;
+
+/// Anyone joins and leaves a public room, the members of a direct
+/// conversation are fixed when it is opened
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RoomKind {
+    #[default]
+    Public,
+    Direct,
+}
+
+/// A place where its members talk
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Room {
+    pub key: String,
+    pub name: String,
+    pub topic: Option<String>,
+    #[serde(default)]
+    pub kind: RoomKind,
+    /// Usernames of the members of a direct conversation, empty for a public
+    /// room
+    #[serde(default)]
+    pub participants: Vec<String>,
+    /// Username of the user who created it
+    pub created_by: String,
+    /// Should be ISO date
+    pub created_at: String,
+}
+
+/// A room as listed to a user
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RoomSummary {
+    #[serde(flatten)]
+    pub room: Room,
+    pub members: u64,
+    /// The user asking is a member
+    pub joined: bool,
+    /// Messages of the others posted after the last one the user read
+    #[serde(default)]
+    pub unread: u64,
+}
+
+/// Payload to create a room
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct NewRoom {
+    pub name: String,
+    pub topic: Option<String>,
+}
+
+/// Last message a member of a room read
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ReadReceipt {
+    /// Username of the member as displayed
+    pub username: String,
+    /// Key of the message
+    pub message: String,
+}
+
+/// Payload to open a direct conversation, opening it again with the same
+/// participants gives the same room
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct NewConversation {
+    /// Usernames of the other participants, the user opening it is added
+    pub participants: Vec<String>,
+}
+
+/// A message posted in a room
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Message {
+    pub key: String,
+    /// Key of the room
+    pub room: String,
+    /// Username of the author as displayed
+    pub author: String,
+    /// Empty once deleted
+    pub body: String,
+    /// Should be ISO date
+    pub created_at: String,
+    /// Date of the last edit, should be ISO date
+    #[serde(default)]
+    pub edited_at: Option<String>,
+    /// A deleted message stays in the timeline as a tombstone
+    #[serde(default)]
+    pub deleted: bool,
+    #[serde(default)]
+    pub reactions: Vec<Reaction>,
+    /// Key of the message this one replies to, replies are only shown in
+    /// the thread of their parent
+    #[serde(default)]
+    pub parent: Option<String>,
+    /// Replies of a message of the timeline, None when it has none
+    #[serde(default)]
+    pub thread: Option<ThreadSummary>,
+}
+
+/// What the timeline shows of the thread of a message
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ThreadSummary {
+    pub replies: u64,
+    pub last_reply: Box<Message>,
+}
+
+/// Everyone who reacted to a message with the same emoji
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Reaction {
+    pub emoji: String,
+    /// Usernames as displayed, a user reacts at most once with each emoji
+    pub users: Vec<String>,
+}
+
+/// An emoji, without letters, digits nor spaces, short enough
+pub fn is_reaction(emoji: &str) -> bool {
+    !emoji.is_empty()
+        && emoji.chars().count() <= REACTION_MAX_LENGTH
+        && emoji
+            .chars()
+            .all(|c| !c.is_ascii() && !c.is_alphanumeric() && !c.is_whitespace())
+}
+
+/// Payload to post a message
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct NewMessage {
+    pub body: String,
+    /// Key of the message replied to, it can not be a reply itself
+    #[serde(default)]
+    pub parent: Option<String>,
+}
+
+/// Position of a message in the history of its room, messages posted at the
+/// same time are ordered by their key
+/// Clients get it encoded and give it back as it is
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cursor {
+    pub created_at: String,
+    pub key: String,
+}
+
+impl Cursor {
+    pub fn of(message: &Message) -> Self {
+        Cursor {
+            created_at: message.created_at.clone(),
+            key: message.key.clone(),
+        }
+    }
+
+    /// Hex of `created_at|key`, safe in a query string
+    pub fn encode(&self) -> String {
+        format!("{}|{}", self.created_at, self.key)
+            .bytes()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    pub fn decode(cursor: &str) -> Option<Cursor> {
+        if !cursor.len().is_multiple_of(2) || !cursor.is_ascii() {
+            return None;
+        }
+        let bytes = (0..cursor.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&cursor[i..i + 2], 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        let text = String::from_utf8(bytes).ok()?;
+        let mut parts = text.splitn(2, '|');
+        let created_at = parts.next()?.to_string();
+        let key = parts.next()?.to_string();
+        Some(Cursor { created_at, key })
+    }
+}
+
+/// Query string of the history of a room, the last messages without cursor
+/// At most one of the cursors is given
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HistoryQuery {
+    /// Messages older than this cursor
+    pub before: Option<String>,
+    /// Messages newer than this cursor
+    pub after: Option<String>,
+    #[serde(default = "default_history_limit")]
+    pub limit: u32,
+}
+
+fn default_history_limit() -> u32 {
+    DEFAULT_HISTORY_LIMIT
+}
+
+impl Default for HistoryQuery {
+    fn default() -> Self {
+        HistoryQuery {
+            before: None,
+            after: None,
+            limit: DEFAULT_HISTORY_LIMIT,
+        }
+    }
+}
+
+impl HistoryQuery {
+    pub fn limit(&self) -> u32 {
+        self.limit.clamp(1, MAX_HISTORY_LIMIT)
+    }
+
+    pub fn to_query_string(&self) -> String {
+        let mut params = vec![format!("limit={}", self.limit())];
+        if let Some(before) = &self.before {
+            params.push(format!("before={}", before));
+        }
+        if let Some(after) = &self.after {
+            params.push(format!("after={}", after));
+        }
+        params.join("&")
+    }
+}
+
+/// Messages of a room, oldest first, with the cursors to continue
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+pub struct HistoryPage {
+    pub messages: Vec<Message>,
+    /// None when there is nothing older
+    pub older: Option<String>,
+    /// None when there was nothing newer
+    pub newer: Option<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_reaction, Cursor};
+
+    #[test]
+    fn cursor_round_trip() {
+        let cursor = Cursor {
+            created_at: "2020-10-19T12:00:00.000000Z".to_string(),
+            key: "3f9a|b".to_string(),
+        };
+        let encoded = cursor.encode();
+        assert!(encoded.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(Cursor::decode(&encoded), Some(cursor));
+        assert_eq!(Cursor::decode("zz"), None);
+        assert_eq!(Cursor::decode("abc"), None);
+        assert_eq!(Cursor::decode("61"), None);
+    }
+
+    #[test]
+    fn reactions() {
+        assert!(is_reaction("👍"));
+        assert!(is_reaction("❤️"));
+        assert!(!is_reaction(""));
+        assert!(!is_reaction(":+1:"));
+        assert!(!is_reaction("é"));
+        assert!(!is_reaction(&"👍".repeat(9)));
+    }
+}
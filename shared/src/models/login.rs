@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// Coarse fingerprint of where a login comes from, precise enough to notice a
+/// new device but stable across browser updates and address changes in the
+/// same network
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Default)]
+pub struct DeviceFingerprint {
+    pub browser: String,
+    pub os: String,
+    /// `/24` network for IPv4, `/48` for IPv6
+    pub ip_prefix: String,
+}
+
+impl DeviceFingerprint {
+    pub fn new(user_agent: Option<&str>, ip: Option<&str>) -> Self {
+        let user_agent = user_agent.unwrap_or("");
+        DeviceFingerprint {
+            browser: browser_family(user_agent).to_string(),
+            os: os_family(user_agent).to_string(),
+            ip_prefix: ip.map_or_else(|| "unknown".to_string(), ip_prefix),
+        }
+    }
+
+    /// ex: `Firefox on Linux from 192.168.1.0/24`
+    pub fn describe(&self) -> String {
+        format!("{} on {} from {}", self.browser, self.os, self.ip_prefix)
+    }
+}
+
+/// The order matters, most browsers also claim to be the ones they are based
+/// on
+fn browser_family(user_agent: &str) -> &'static str {
+    if user_agent.contains("Edg/") || user_agent.contains("Edge/") {
+        "Edge"
+    } else if user_agent.contains("OPR/") || user_agent.contains("Opera") {
+        "Opera"
+    } else if user_agent.contains("Chrome/") || user_agent.contains("CriOS/") {
+        "Chrome"
+    } else if user_agent.contains("Firefox/") || user_agent.contains("FxiOS/") {
+        "Firefox"
+    } else if user_agent.contains("Safari/") {
+        "Safari"
+    } else {
+        "Other"
+    }
+}
+
+fn os_family(user_agent: &str) -> &'static str {
+    if user_agent.contains("Windows") {
+        "Windows"
+    } else if user_agent.contains("Android") {
+        "Android"
+    } else if user_agent.contains("iPhone") || user_agent.contains("iPad") {
+        "iOS"
+    } else if user_agent.contains("Mac OS X") || user_agent.contains("Macintosh") {
+        "macOS"
+    } else if user_agent.contains("CrOS") {
+        "Chrome OS"
+    } else if user_agent.contains("Linux") {
+        "Linux"
+    } else {
+        "Other"
+    }
+}
+
+fn ip_prefix(ip: &str) -> String {
+    match ip.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            let octets = ip.octets();
+            format!("{}.{}.{}.0/24", octets[0], octets[1], octets[2])
+        }
+        Ok(IpAddr::V6(ip)) => match ip.to_ipv4() {
+            // IPv4 clients of a dual stack server
+            Some(ip) if ip.octets()[..2] != [0, 0] => ip_prefix(&ip.to_string()),
+            _ => {
+                let segments = ip.segments();
+                format!("{:x}:{:x}:{:x}::/48", segments[0], segments[1], segments[2])
+            }
+        },
+        Err(_) => "unknown".to_string(),
+    }
+}
+
+/// A successful login as shown to its user
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LoginEntry {
+    pub device: DeviceFingerprint,
+    pub ip: Option<String>,
+    /// Should be ISO date
+    pub created_at: String,
+    /// First login from this device
+    pub new_device: bool,
+    /// The login of the session asking for the history
+    pub current: bool,
+}
+
+#[cfg(test)]
+mod test {
+    use super::DeviceFingerprint;
+
+    #[test]
+    fn fingerprint_is_coarse() {
+        let firefox = "Mozilla/5.0 (X11; Linux x86_64; rv:81.0) Gecko/20100101 Firefox/81.0";
+        let chrome = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 \
+                      (KHTML, like Gecko) Chrome/86.0.4240.75 Safari/537.36";
+        let safari = "Mozilla/5.0 (iPhone; CPU iPhone OS 14_0 like Mac OS X) AppleWebKit/605.1.15 \
+                      (KHTML, like Gecko) Version/14.0 Mobile/15E148 Safari/604.1";
+
+        let device = DeviceFingerprint::new(Some(firefox), Some("192.168.1.42"));
+        assert_eq!(device.describe(), "Firefox on Linux from 192.168.1.0/24");
+        assert_eq!(
+            device,
+            DeviceFingerprint::new(Some(firefox), Some("192.168.1.7"))
+        );
+        assert_eq!(
+            DeviceFingerprint::new(Some(chrome), Some("2001:db8:85a3:8d3:1319:8a2e:370:7348"))
+                .describe(),
+            "Chrome on Windows from 2001:db8:85a3::/48"
+        );
+        assert_eq!(
+            DeviceFingerprint::new(Some(safari), Some("::ffff:10.0.0.1")).describe(),
+            "Safari on iOS from 10.0.0.0/24"
+        );
+        assert_eq!(
+            DeviceFingerprint::new(None, None).describe(),
+            "Other on Other from unknown"
+        );
+    }
+}
@@ -1,3 +1,10 @@
+pub mod admin;
+pub mod audit;
 pub mod auth;
+pub mod chat;
+pub mod login;
+pub mod policy;
 pub mod power;
+pub mod presence;
+pub mod role;
 pub mod user;
@@ -0,0 +1,177 @@
+use crate::{i18n::Message, models::power::Power, strength::estimate};
+use serde::{Deserialize, Serialize};
+
+/// Kind of characters a password can be required to contain
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CharacterClass {
+    Lowercase,
+    Uppercase,
+    Digit,
+    Symbol,
+}
+
+impl CharacterClass {
+    pub fn contains(self, c: char) -> bool {
+        match self {
+            CharacterClass::Lowercase => c.is_lowercase(),
+            CharacterClass::Uppercase => c.is_uppercase(),
+            CharacterClass::Digit => c.is_numeric(),
+            CharacterClass::Symbol => !c.is_alphanumeric() && !c.is_whitespace(),
+        }
+    }
+}
+
+/// Rules a new password must follow, the server loads it from its config
+/// and the client gets it from `GET /api/policy` so both check the same thing
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub required_classes: Vec<CharacterClass>,
+    /// Minimum units of `Power` given by the strength estimation
+    pub min_power: u8,
+    /// Forbidden parts of the password, the user inputs (username, email,
+    /// names) are always added to them
+    pub banned_substrings: Vec<String>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            min_length: 8,
+            max_length: 30,
+            required_classes: Vec::new(),
+            min_power: 101,
+            banned_substrings: Vec::new(),
+        }
+    }
+}
+
+/// A single rule of the policy
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub enum Rule {
+    MinLength(usize),
+    MaxLength(usize),
+    Contains(CharacterClass),
+    MinPower(u8),
+    NoBannedSubstring,
+}
+
+impl Rule {
+    pub fn message(&self) -> Message {
+        match self {
+            Rule::MinLength(length) => Message::new("rule.min_length").with("length", length),
+            Rule::MaxLength(length) => Message::new("rule.max_length").with("length", length),
+            Rule::Contains(CharacterClass::Lowercase) => Message::new("rule.lowercase"),
+            Rule::Contains(CharacterClass::Uppercase) => Message::new("rule.uppercase"),
+            Rule::Contains(CharacterClass::Digit) => Message::new("rule.digit"),
+            Rule::Contains(CharacterClass::Symbol) => Message::new("rule.symbol"),
+            Rule::MinPower(power) => Message::new("rule.min_power").with("power", power),
+            Rule::NoBannedSubstring => Message::new("rule.no_banned_substring"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct RuleCheck {
+    pub rule: Rule,
+    pub passed: bool,
+}
+
+impl PasswordPolicy {
+    /// Check every rule, the user inputs are the personal information of the
+    /// user which are also used for the strength estimation
+    pub fn check(&self, password: &str, user_inputs: &[&str]) -> Vec<RuleCheck> {
+        let length = password.chars().count();
+        let mut checks = vec![
+            RuleCheck {
+                rule: Rule::MinLength(self.min_length),
+                passed: length >= self.min_length,
+            },
+            RuleCheck {
+                rule: Rule::MaxLength(self.max_length),
+                passed: length <= self.max_length,
+            },
+        ];
+        for class in &self.required_classes {
+            checks.push(RuleCheck {
+                rule: Rule::Contains(*class),
+                passed: password.chars().any(|c| class.contains(c)),
+            });
+        }
+        checks.push(RuleCheck {
+            rule: Rule::MinPower(self.min_power),
+            passed: Power::units_from_estimate(&estimate(password, user_inputs)) >= self.min_power,
+        });
+        checks.push(RuleCheck {
+            rule: Rule::NoBannedSubstring,
+            passed: !self.contains_banned_substring(password, user_inputs),
+        });
+        checks
+    }
+
+    pub fn is_satisfied(&self, password: &str, user_inputs: &[&str]) -> bool {
+        self.check(password, user_inputs)
+            .iter()
+            .all(|check| check.passed)
+    }
+
+    fn contains_banned_substring(&self, password: &str, user_inputs: &[&str]) -> bool {
+        let password = password.to_lowercase();
+        let inputs = user_inputs
+            .iter()
+            // only the local part of an email is likely to be used
+            .map(|input| input.split('@').next().unwrap_or(input));
+        self.banned_substrings
+            .iter()
+            .map(String::as_str)
+            .chain(inputs)
+            // Banning very short parts would ban almost everything
+            .filter(|banned| banned.chars().count() >= 3)
+            .any(|banned| password.contains(&banned.to_lowercase()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CharacterClass, PasswordPolicy, Rule};
+
+    #[test]
+    fn check_every_rule() {
+        let policy = PasswordPolicy {
+            required_classes: vec![CharacterClass::Digit, CharacterClass::Symbol],
+            banned_substrings: vec!["avocado".to_string()],
+            ..PasswordPolicy::default()
+        };
+
+        let failed: Vec<Rule> = policy
+            .check("Avocado", &["arn"])
+            .into_iter()
+            .filter(|check| !check.passed)
+            .map(|check| check.rule)
+            .collect();
+        assert_eq!(
+            failed,
+            vec![
+                Rule::MinLength(8),
+                Rule::Contains(CharacterClass::Digit),
+                Rule::Contains(CharacterClass::Symbol),
+                Rule::MinPower(101),
+                Rule::NoBannedSubstring,
+            ]
+        );
+
+        assert!(policy.is_satisfied("x9!Kq7#vLw2z", &["arn"]));
+        assert!(!policy.is_satisfied("x9!Kq7#arnaud", &["arnaud@tiny.tree"]));
+    }
+
+    #[test]
+    fn guessable_passwords_are_not_powerful_enough() {
+        let policy = PasswordPolicy::default();
+        for password in &["Avocado2020!", "Tr0ub4dor&3", "Password123!"] {
+            assert!(!policy.is_satisfied(password, &[]));
+        }
+        assert!(policy.is_satisfied("walrus lantern meadow", &[]));
+    }
+}
@@ -1,3 +1,8 @@
+use crate::{
+    i18n::Message,
+    strength::{estimate, Estimate},
+};
+
 pub enum Power {
     NoPower,
     BabyPower(u8),
@@ -23,25 +28,19 @@ impl Power {
             _ => Power::NoPower,
         }
     }
-    pub fn display(&self) -> &str {
+    /// Stable name of the power level, used for messages and styling
+    pub fn key(&self) -> &'static str {
         match &self {
-            Power::NoPower => "Cannot live",
-            Power::BabyPower(pw) => "I am a baby and I cry a lot",
-            Power::LowPower(pw) => "I am so weak ",
-            Power::MediumPower(pw) => "I am ok ",
-            Power::HighPower(pw) => "I feel so good ",
-            Power::MaxPower(pw) => "Mouahaha I am so OP",
+            Power::NoPower => "no_power",
+            Power::BabyPower(_) => "baby_power",
+            Power::LowPower(_) => "low_power",
+            Power::MediumPower(_) => "medium_power",
+            Power::HighPower(_) => "high_power",
+            Power::MaxPower(_) => "max_power",
         }
     }
-    pub fn class(&self) -> &str {
-        match &self {
-            Power::NoPower => "no-power",
-            Power::BabyPower(pw) => "baby-power",
-            Power::LowPower(pw) => "low-power",
-            Power::MediumPower(pw) => "medium-power",
-            Power::HighPower(pw) => "high-power",
-            Power::MaxPower(pw) => "max-power",
-        }
+    pub fn message(&self) -> Message {
+        Message::new(&format!("power.{}", self.key())).with("units", self.units())
     }
     pub fn units(&self) -> &u8 {
         match &self {
@@ -53,33 +52,18 @@ impl Power {
             Power::MaxPower(pw) => pw,
         }
     }
-    /// Calculate the power and return a maximum value of 255.
-    /// # Note sure what would happen if we get more than 255
+    /// Calculate the power from the estimated guesses needed to find the
+    /// password, 255 is reached at 10^20 guesses and never exceeded
     pub fn calculate_power(text: String) -> u8 {
-        let mut power = 0;
-        let pwd = text;
-        let characters = pwd.chars();
-        let compared_characteres = characters.clone();
-        power = characters.clone().count() as u8;
-        for c in characters {
-            if c.is_numeric() {
-                power += 1;
-            }
-            if c.is_uppercase() {
-                power += 2;
-            }
-            if c.is_ascii_punctuation() {
-                power += 2;
-            }
-            let count = compared_characteres.clone().filter(|o| c.eq(o)).count();
-            if count == 1 {
-                power += 4;
-            } else if (count > 1) & (count < 3) {
-                power += 3;
-            } else {
-                power -= 1;
-            }
-        }
-        power
+        Power::units_from_estimate(&estimate(&text, &[]))
+    }
+
+    /// Units of power for an estimate, 101 units are around 10^8 guesses
+    pub fn units_from_estimate(estimate: &Estimate) -> u8 {
+        (estimate.guesses_log10 * 12.75).round().clamp(0.0, 255.0) as u8
+    }
+
+    pub fn from_estimate(estimate: &Estimate) -> Power {
+        Power::rank(Power::units_from_estimate(estimate))
     }
 }
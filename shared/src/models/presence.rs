@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+/// Whether a user can be reached in the chat
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PresenceStatus {
+    /// Connected and active lately
+    Online,
+    /// Connected but idle, or the page is in the background
+    Away,
+    #[default]
+    Offline,
+}
+
+impl PresenceStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PresenceStatus::Online => "online",
+            PresenceStatus::Away => "away",
+            PresenceStatus::Offline => "offline",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Presence {
+    /// Username as displayed
+    pub username: String,
+    pub status: PresenceStatus,
+}
@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+
+/// Role of a user, a user can have many and gets the permissions of all
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Moderator,
+    Admin,
+}
+
+/// What a handler can require from the logged user
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    /// List, edit, suspend and delete accounts
+    ManageUsers,
+    /// Give or take roles
+    ManageRoles,
+    /// Read the audit log and the password hash report
+    ViewReports,
+    /// Edit or delete the messages of other users
+    ModerateMessages,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::User, Role::Moderator, Role::Admin];
+
+    /// Name used in urls and in the db
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn permissions(self) -> &'static [Permission] {
+        match self {
+            Role::User => &[],
+            Role::Moderator => &[Permission::ModerateMessages],
+            Role::Admin => &[
+                Permission::ManageUsers,
+                Permission::ManageRoles,
+                Permission::ViewReports,
+                Permission::ModerateMessages,
+            ],
+        }
+    }
+}
+
+/// Every user has at least the user role
+pub fn default_roles() -> Vec<Role> {
+    vec![Role::User]
+}
+
+pub fn has_permission(roles: &[Role], permission: Permission) -> bool {
+    roles
+        .iter()
+        .any(|role| role.permissions().contains(&permission))
+}
+
+/// Payload of an admin setting the roles of a user
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RolesChange {
+    pub roles: Vec<Role>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::{has_permission, Permission, Role};
+
+    #[test]
+    fn permissions_of_roles() {
+        assert!(!has_permission(&[Role::User], Permission::ManageUsers));
+        assert!(has_permission(
+            &[Role::User, Role::Moderator],
+            Permission::ModerateMessages
+        ));
+        assert!(has_permission(&[Role::Admin], Permission::ManageRoles));
+    }
+}
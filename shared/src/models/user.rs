@@ -1,6 +1,9 @@
-use crate::models::auth::AuthData;
+use crate::models::{
+    auth::AuthData,
+    role::{default_roles, has_permission, Permission, Role},
+};
 use serde::{Deserialize, Serialize};
-#[derive(Debug, Deserialize, Serialize, Default)]
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
 pub struct User {
     pub first_name: String,
     pub last_name: String,
@@ -14,15 +17,28 @@ pub struct LoggedUser {
     pub last_name: String,
     username: String,
     email: String,
+    #[serde(default = "default_roles")]
+    roles: Vec<Role>,
+    /// An admin asked for a new password, the user should change it first
+    #[serde(default)]
+    pub password_reset_required: bool,
 }
 
 impl LoggedUser {
-    pub fn new(first_name: String, last_name: String, username: String, email: String) -> Self {
+    pub fn new(
+        first_name: String,
+        last_name: String,
+        username: String,
+        email: String,
+        roles: Vec<Role>,
+    ) -> Self {
         LoggedUser {
             first_name,
             last_name,
             username,
             email,
+            roles,
+            password_reset_required: false,
         }
     }
 }
@@ -34,4 +50,82 @@ impl LoggedUser {
     pub fn email(&self) -> &str {
         &self.email
     }
+    pub fn roles(&self) -> &[Role] {
+        &self.roles
+    }
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        has_permission(&self.roles, permission)
+    }
+}
+
+/// Lifecycle of an account, only active accounts can login
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountStatus {
+    /// Registered but waiting for an admin approval
+    Pending,
+    #[default]
+    Active,
+    Suspended,
+    /// Kept so the username is not reused, it cannot come back
+    Deleted,
+}
+
+impl AccountStatus {
+    pub const ALL: [AccountStatus; 4] = [
+        AccountStatus::Pending,
+        AccountStatus::Active,
+        AccountStatus::Suspended,
+        AccountStatus::Deleted,
+    ];
+
+    /// Name used in urls and in the db
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AccountStatus::Pending => "pending",
+            AccountStatus::Active => "active",
+            AccountStatus::Suspended => "suspended",
+            AccountStatus::Deleted => "deleted",
+        }
+    }
+
+    /// Transitions an admin is allowed to make
+    pub fn can_become(self, next: AccountStatus) -> bool {
+        use AccountStatus::*;
+        matches!(
+            (self, next),
+            (Pending, Active)
+                | (Pending, Deleted)
+                | (Active, Suspended)
+                | (Active, Deleted)
+                | (Suspended, Active)
+                | (Suspended, Deleted)
+        )
+    }
+}
+
+/// Current status of an account, when and why it was set
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+pub struct AccountState {
+    pub status: AccountStatus,
+    /// Should be ISO date, none for accounts older than the lifecycle
+    pub changed_at: Option<String>,
+    pub reason: Option<String>,
+}
+
+impl AccountState {
+    pub fn new(status: AccountStatus, changed_at: String, reason: Option<String>) -> Self {
+        AccountState {
+            status,
+            changed_at: Some(changed_at),
+            reason,
+        }
+    }
+}
+
+/// Payload of an admin changing the status of an account
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StatusTransition {
+    pub status: AccountStatus,
+    pub reason: Option<String>,
 }
@@ -0,0 +1,196 @@
+//! Frames of the chat WebSocket, both sides send JSON text frames wrapping an
+//! event with the version of the protocol they speak
+
+use crate::models::{
+    chat::{Message, ReadReceipt, Room},
+    presence::Presence,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Bumped on every change the other side cannot ignore
+pub const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Frame<E> {
+    pub version: u32,
+    pub event: E,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProtocolError {
+    /// The frame is not JSON or not an event we know
+    Invalid(String),
+    UnsupportedVersion(u32),
+}
+
+/// Only the version of a frame, read before its event
+#[derive(Deserialize)]
+struct FrameVersion {
+    version: u32,
+}
+
+impl<E: Serialize + DeserializeOwned> Frame<E> {
+    pub fn new(event: E) -> Self {
+        Frame {
+            version: PROTOCOL_VERSION,
+            event,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Frames should serialize")
+    }
+
+    /// Read a frame, the version is checked before the event so a newer
+    /// event is reported as a version problem
+    pub fn from_json(text: &str) -> Result<Self, ProtocolError> {
+        let version: FrameVersion =
+            serde_json::from_str(text).map_err(|err| ProtocolError::Invalid(err.to_string()))?;
+        if version.version != PROTOCOL_VERSION {
+            return Err(ProtocolError::UnsupportedVersion(version.version));
+        }
+        serde_json::from_str(text).map_err(|err| ProtocolError::Invalid(err.to_string()))
+    }
+}
+
+/// Sent by the client
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientEvent {
+    /// Receive the events of a room the user is a member of
+    Subscribe {
+        room: String,
+    },
+    Unsubscribe {
+        room: String,
+    },
+    /// `parent` is given to reply in the thread of a message
+    Post {
+        room: String,
+        body: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        parent: Option<String>,
+    },
+    /// Sent regularly while connected, `active` is false when the user is
+    /// idle or the page is hidden
+    Heartbeat {
+        active: bool,
+    },
+    /// The user is writing in a room, sent again while it goes on
+    Typing {
+        room: String,
+    },
+    /// The user read a room up to `message`, an older message than the
+    /// current marker is ignored
+    MarkRead {
+        room: String,
+        message: String,
+    },
+}
+
+/// Sent by the server
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerEvent {
+    /// First frame of a connection
+    Welcome {
+        username: String,
+    },
+    Subscribed {
+        room: String,
+    },
+    Unsubscribed {
+        room: String,
+    },
+    /// Replies too, the clients tell them by their `parent`
+    MessagePosted {
+        message: Message,
+    },
+    /// The new version of an edited message
+    MessageEdited {
+        message: Message,
+    },
+    /// The tombstone of a deleted message
+    MessageDeleted {
+        message: Message,
+    },
+    ReactionAdded {
+        room: String,
+        message: String,
+        emoji: String,
+        username: String,
+    },
+    ReactionRemoved {
+        room: String,
+        message: String,
+        emoji: String,
+        username: String,
+    },
+    /// A direct conversation including the user was opened
+    RoomOpened {
+        room: Room,
+    },
+    MemberJoined {
+        room: String,
+        username: String,
+    },
+    MemberLeft {
+        room: String,
+        username: String,
+    },
+    /// A user sharing a room with the receiver changed status
+    PresenceChanged {
+        presence: Presence,
+    },
+    Typing {
+        room: String,
+        username: String,
+    },
+    /// A member of the room, maybe the receiver on another device, read
+    /// further
+    ReadMarkerMoved {
+        room: String,
+        receipt: ReadReceipt,
+    },
+    /// A client event could not be handled
+    Error {
+        message: String,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ClientEvent, Frame, ProtocolError, PROTOCOL_VERSION};
+
+    #[test]
+    fn read_frames() {
+        let frame = Frame::new(ClientEvent::Post {
+            room: "avocados".to_string(),
+            body: "Hello".to_string(),
+            parent: None,
+        });
+        let json = frame.to_json();
+        assert_eq!(
+            json,
+            format!(
+                r#"{{"version":{},"event":{{"type":"post","room":"avocados","body":"Hello"}}}}"#,
+                PROTOCOL_VERSION
+            )
+        );
+        assert_eq!(Frame::from_json(&json), Ok(frame));
+
+        let reply = Frame::new(ClientEvent::Post {
+            room: "avocados".to_string(),
+            body: "Hi".to_string(),
+            parent: Some("abc".to_string()),
+        });
+        assert_eq!(Frame::from_json(&reply.to_json()), Ok(reply));
+
+        let newer = r#"{"version":999,"event":{"type":"wave"}}"#;
+        assert_eq!(
+            Frame::<ClientEvent>::from_json(newer),
+            Err(ProtocolError::UnsupportedVersion(999))
+        );
+        assert!(Frame::<ClientEvent>::from_json("not json").is_err());
+    }
+}
@@ -0,0 +1,54 @@
+//! Ranked word lists and keyboard layouts used by the matchers
+//! Lists are ordered from the most to the least common, the rank of a word is
+//! its position starting at 1
+
+/// Most common passwords of public leaks, one per line
+pub const PASSWORDS: &str = include_str!("lists/passwords.txt");
+
+/// Most common English words of films, books and Wikipedia, one per line
+pub const ENGLISH: &str = include_str!("lists/english.txt");
+
+/// Most common first names of the US census, followed by common European and
+/// Asian ones, one per line
+pub const FIRST_NAMES: &str = include_str!("lists/first_names.txt");
+
+/// Most common surnames of the US census, followed by common European ones,
+/// one per line
+pub const SURNAMES: &str = include_str!("lists/surnames.txt");
+
+/// Words of the site itself, always guessed with the user inputs
+pub const SITE_WORDS: [&str; 3] = ["tiny", "avocado", "tree"];
+
+/// Keyboard rows, each key is a pair of the character and its shifted
+/// character, the second value is the offset of the row
+pub const QWERTY: [(&str, usize); 4] = [
+    ("`~ 1! 2@ 3# 4$ 5% 6^ 7& 8* 9( 0) -_ =+", 0),
+    ("qQ wW eE rR tT yY uU iI oO pP [{ ]} \\|", 1),
+    ("aA sS dD fF gG hH jJ kK lL ;: '\"", 1),
+    ("zZ xX cC vV bB nN mM ,< .> /?", 1),
+];
+
+/// Keypad rows, keys are not shifted and rows are aligned
+pub const KEYPAD: [(&str, usize); 5] = [
+    ("/ * -", 1),
+    ("7 8 9 +", 0),
+    ("4 5 6", 0),
+    ("1 2 3", 0),
+    ("0 .", 1),
+];
+
+/// Common substitutions of letters when people write in l33t
+pub const L33T_TABLE: [(char, &str); 12] = [
+    ('a', "4@"),
+    ('b', "8"),
+    ('c', "({[<"),
+    ('e', "3"),
+    ('g', "69"),
+    ('i', "1!|"),
+    ('l', "1|7"),
+    ('o', "0"),
+    ('s', "$5"),
+    ('t', "+7"),
+    ('x', "%"),
+    ('z', "2"),
+];
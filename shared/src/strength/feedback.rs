@@ -0,0 +1,220 @@
+use crate::{
+    i18n::Message,
+    strength::matching::{Dictionary, Match, Pattern},
+};
+use serde::{Deserialize, Serialize};
+
+/// What makes the password weak
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub enum Warning {
+    TopTenPassword,
+    TopHundredPassword,
+    CommonPassword,
+    SimilarToCommonPassword,
+    WordByItself,
+    NamesByThemselves,
+    CommonNames,
+    PersonalInformation,
+    StraightRow,
+    ShortKeyboardPattern,
+    RepeatedCharacters,
+    RepeatedPattern,
+    Sequence,
+    RecentYear,
+    Date,
+}
+
+/// How to make the password stronger
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub enum Suggestion {
+    UseFewWords,
+    NoNeedForSymbols,
+    AddAnotherWord,
+    CapitalizationDoesNotHelp,
+    AllUppercaseDoesNotHelp,
+    ReversedWordsDoNotHelp,
+    PredictableSubstitutions,
+    LongerKeyboardPattern,
+    AvoidRepeats,
+    AvoidSequences,
+    AvoidRecentYears,
+    AvoidPersonalYears,
+    AvoidPersonalDates,
+    AvoidPersonalInformation,
+}
+
+impl Warning {
+    /// Stable key of the warning in the message catalog
+    pub fn key(self) -> &'static str {
+        match self {
+            Warning::TopTenPassword => "warning.top_ten_password",
+            Warning::TopHundredPassword => "warning.top_hundred_password",
+            Warning::CommonPassword => "warning.common_password",
+            Warning::SimilarToCommonPassword => "warning.similar_to_common_password",
+            Warning::WordByItself => "warning.word_by_itself",
+            Warning::NamesByThemselves => "warning.names_by_themselves",
+            Warning::CommonNames => "warning.common_names",
+            Warning::PersonalInformation => "warning.personal_information",
+            Warning::StraightRow => "warning.straight_row",
+            Warning::ShortKeyboardPattern => "warning.short_keyboard_pattern",
+            Warning::RepeatedCharacters => "warning.repeated_characters",
+            Warning::RepeatedPattern => "warning.repeated_pattern",
+            Warning::Sequence => "warning.sequence",
+            Warning::RecentYear => "warning.recent_year",
+            Warning::Date => "warning.date",
+        }
+    }
+
+    pub fn message(self) -> Message {
+        Message::new(self.key())
+    }
+}
+
+impl Suggestion {
+    /// Stable key of the suggestion in the message catalog
+    pub fn key(self) -> &'static str {
+        match self {
+            Suggestion::UseFewWords => "suggestion.use_few_words",
+            Suggestion::NoNeedForSymbols => "suggestion.no_need_for_symbols",
+            Suggestion::AddAnotherWord => "suggestion.add_another_word",
+            Suggestion::CapitalizationDoesNotHelp => "suggestion.capitalization_does_not_help",
+            Suggestion::AllUppercaseDoesNotHelp => "suggestion.all_uppercase_does_not_help",
+            Suggestion::ReversedWordsDoNotHelp => "suggestion.reversed_words_do_not_help",
+            Suggestion::PredictableSubstitutions => "suggestion.predictable_substitutions",
+            Suggestion::LongerKeyboardPattern => "suggestion.longer_keyboard_pattern",
+            Suggestion::AvoidRepeats => "suggestion.avoid_repeats",
+            Suggestion::AvoidSequences => "suggestion.avoid_sequences",
+            Suggestion::AvoidRecentYears => "suggestion.avoid_recent_years",
+            Suggestion::AvoidPersonalYears => "suggestion.avoid_personal_years",
+            Suggestion::AvoidPersonalDates => "suggestion.avoid_personal_dates",
+            Suggestion::AvoidPersonalInformation => "suggestion.avoid_personal_information",
+        }
+    }
+
+    pub fn message(self) -> Message {
+        Message::new(self.key())
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Feedback {
+    pub warning: Option<Warning>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+/// Explain the weakest part of the password, nothing to say for good
+/// passwords
+pub fn feedback(score: u8, sequence: &[Match]) -> Feedback {
+    if sequence.is_empty() {
+        return Feedback {
+            warning: None,
+            suggestions: vec![Suggestion::UseFewWords, Suggestion::NoNeedForSymbols],
+        };
+    }
+    if score > 2 {
+        return Feedback::default();
+    }
+
+    let longest = sequence.iter().fold(&sequence[0], |longest, m| {
+        if m.len() > longest.len() {
+            m
+        } else {
+            longest
+        }
+    });
+    let mut feedback = match_feedback(longest, sequence.len() == 1);
+    feedback.suggestions.insert(0, Suggestion::AddAnotherWord);
+    feedback
+}
+
+fn match_feedback(m: &Match, is_sole_match: bool) -> Feedback {
+    match &m.pattern {
+        Pattern::Dictionary { .. } => dictionary_feedback(m, is_sole_match),
+        Pattern::Spatial { turns, .. } => Feedback {
+            warning: Some(if *turns == 1 {
+                Warning::StraightRow
+            } else {
+                Warning::ShortKeyboardPattern
+            }),
+            suggestions: vec![Suggestion::LongerKeyboardPattern],
+        },
+        Pattern::Repeat { base_token, .. } => Feedback {
+            warning: Some(if base_token.chars().count() == 1 {
+                Warning::RepeatedCharacters
+            } else {
+                Warning::RepeatedPattern
+            }),
+            suggestions: vec![Suggestion::AvoidRepeats],
+        },
+        Pattern::Sequence { .. } => Feedback {
+            warning: Some(Warning::Sequence),
+            suggestions: vec![Suggestion::AvoidSequences],
+        },
+        Pattern::Year => Feedback {
+            warning: Some(Warning::RecentYear),
+            suggestions: vec![Suggestion::AvoidRecentYears, Suggestion::AvoidPersonalYears],
+        },
+        Pattern::Date { .. } => Feedback {
+            warning: Some(Warning::Date),
+            suggestions: vec![Suggestion::AvoidPersonalDates],
+        },
+        Pattern::Bruteforce => Feedback::default(),
+    }
+}
+
+fn dictionary_feedback(m: &Match, is_sole_match: bool) -> Feedback {
+    let (dictionary, rank, l33t, reversed) = match &m.pattern {
+        Pattern::Dictionary {
+            dictionary,
+            rank,
+            l33t,
+            reversed,
+            ..
+        } => (*dictionary, *rank, !l33t.is_empty(), *reversed),
+        _ => return Feedback::default(),
+    };
+
+    let warning = match dictionary {
+        Dictionary::Passwords if is_sole_match && !l33t && !reversed => {
+            if rank <= 10 {
+                Some(Warning::TopTenPassword)
+            } else if rank <= 100 {
+                Some(Warning::TopHundredPassword)
+            } else {
+                Some(Warning::CommonPassword)
+            }
+        }
+        Dictionary::Passwords => Some(Warning::SimilarToCommonPassword),
+        Dictionary::English if is_sole_match => Some(Warning::WordByItself),
+        Dictionary::English => None,
+        Dictionary::Names if is_sole_match => Some(Warning::NamesByThemselves),
+        Dictionary::Names => Some(Warning::CommonNames),
+        Dictionary::UserInputs => Some(Warning::PersonalInformation),
+    };
+
+    let mut suggestions = Vec::new();
+    if dictionary == Dictionary::UserInputs {
+        suggestions.push(Suggestion::AvoidPersonalInformation);
+    }
+    let chars: Vec<char> = m.token.chars().collect();
+    let starts_upper = matches!(chars.first(), Some(c) if c.is_uppercase())
+        && chars[1..].iter().all(|c| !c.is_uppercase());
+    let all_upper =
+        chars.iter().all(|c| !c.is_lowercase()) && chars.iter().any(|c| c.is_uppercase());
+    if starts_upper {
+        suggestions.push(Suggestion::CapitalizationDoesNotHelp);
+    } else if all_upper {
+        suggestions.push(Suggestion::AllUppercaseDoesNotHelp);
+    }
+    if reversed && m.len() >= 4 {
+        suggestions.push(Suggestion::ReversedWordsDoNotHelp);
+    }
+    if l33t {
+        suggestions.push(Suggestion::PredictableSubstitutions);
+    }
+
+    Feedback {
+        warning,
+        suggestions,
+    }
+}
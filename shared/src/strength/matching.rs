@@ -0,0 +1,703 @@
+use crate::strength::{
+    data::{ENGLISH, FIRST_NAMES, KEYPAD, L33T_TABLE, PASSWORDS, QWERTY, SITE_WORDS, SURNAMES},
+    scoring,
+};
+use lazy_static::lazy_static;
+use std::{collections::HashMap, iter};
+
+/// Dates and years are compared to this year, we do not read the clock so
+/// the client and the server always agree
+pub const REFERENCE_YEAR: i32 = 2020;
+const DATE_MIN_YEAR: i32 = 1000;
+const DATE_MAX_YEAR: i32 = 2050;
+const MAX_SEQUENCE_DELTA: i32 = 5;
+const DATE_SEPARATORS: [char; 6] = [' ', '-', '/', '\\', '_', '.'];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dictionary {
+    Passwords,
+    English,
+    Names,
+    UserInputs,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Keyboard {
+    Qwerty,
+    Keypad,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pattern {
+    Dictionary {
+        dictionary: Dictionary,
+        rank: usize,
+        matched_word: String,
+        /// Pairs of the l33t character and the letter it replaces
+        l33t: Vec<(char, char)>,
+        reversed: bool,
+    },
+    Spatial {
+        keyboard: Keyboard,
+        turns: usize,
+        shifted: usize,
+    },
+    Repeat {
+        base_token: String,
+        base_guesses: f64,
+        repeat_count: usize,
+    },
+    Sequence {
+        ascending: bool,
+    },
+    Year,
+    Date {
+        year: i32,
+        separator: bool,
+    },
+    Bruteforce,
+}
+
+/// A part of the password, from the char index `i` to `j` included
+#[derive(Clone, Debug, PartialEq)]
+pub struct Match {
+    pub i: usize,
+    pub j: usize,
+    pub token: String,
+    pub pattern: Pattern,
+}
+
+impl Match {
+    fn new(password: &[char], i: usize, j: usize, pattern: Pattern) -> Self {
+        Match {
+            i,
+            j,
+            token: password[i..=j].iter().collect(),
+            pattern,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.j - self.i + 1
+    }
+
+    /// A match covers at least one character
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+/// Every match we can find in the password, they may overlap
+pub fn omnimatch(password: &[char], user_inputs: &[&str]) -> Vec<Match> {
+    let ranked_user_inputs = rank_user_inputs(user_inputs);
+    let dictionaries = ranked_dictionaries(&ranked_user_inputs);
+    let mut matches = Vec::new();
+    matches.extend(dictionary_match(password, &dictionaries));
+    matches.extend(reverse_dictionary_match(password, &dictionaries));
+    matches.extend(l33t_match(password, &dictionaries));
+    matches.extend(spatial_match(password, Keyboard::Qwerty));
+    matches.extend(spatial_match(password, Keyboard::Keypad));
+    matches.extend(repeat_match(password, user_inputs));
+    matches.extend(sequence_match(password));
+    matches.extend(year_match(password));
+    matches.extend(date_match(password));
+    matches.sort_by_key(|m| (m.i, m.j));
+    matches
+}
+
+type Ranked = HashMap<String, usize>;
+type RankedDictionaries<'a> = Vec<(Dictionary, &'a Ranked)>;
+
+lazy_static! {
+    /// The shipped lists are ranked once, an estimate runs on every keystroke
+    /// and the repeat matcher estimates again the repeated part
+    static ref RANKED_LISTS: Vec<(Dictionary, Ranked)> = vec![
+        (Dictionary::Passwords, rank(split(PASSWORDS))),
+        (Dictionary::English, rank(split(ENGLISH))),
+        (Dictionary::Names, rank(split(FIRST_NAMES))),
+        (Dictionary::Names, rank(split(SURNAMES))),
+    ];
+}
+
+fn split(list: &str) -> impl Iterator<Item = String> + '_ {
+    list.split_whitespace().map(str::to_string)
+}
+
+fn rank(words: impl Iterator<Item = String>) -> Ranked {
+    let mut ranked = HashMap::new();
+    for (index, word) in words.enumerate() {
+        ranked.entry(word).or_insert(index + 1);
+    }
+    ranked
+}
+
+/// The user inputs change with every call, they are ranked with the words of
+/// the site
+fn rank_user_inputs(user_inputs: &[&str]) -> Ranked {
+    rank(
+        user_inputs
+            .iter()
+            .filter(|input| !input.is_empty())
+            .map(|input| input.to_lowercase())
+            .chain(SITE_WORDS.iter().map(|word| word.to_string())),
+    )
+}
+
+fn ranked_dictionaries(user_inputs: &Ranked) -> RankedDictionaries<'_> {
+    RANKED_LISTS
+        .iter()
+        .map(|(dictionary, ranked)| (*dictionary, ranked))
+        .chain(iter::once((Dictionary::UserInputs, user_inputs)))
+        .collect()
+}
+
+fn dictionary_match(password: &[char], dictionaries: &RankedDictionaries<'_>) -> Vec<Match> {
+    let lower: Vec<char> = password
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect();
+    let mut matches = Vec::new();
+    for i in 0..lower.len() {
+        for j in i..lower.len() {
+            let word: String = lower[i..=j].iter().collect();
+            for (dictionary, ranked) in dictionaries {
+                if let Some(rank) = ranked.get(&word) {
+                    matches.push(Match::new(
+                        password,
+                        i,
+                        j,
+                        Pattern::Dictionary {
+                            dictionary: *dictionary,
+                            rank: *rank,
+                            matched_word: word.clone(),
+                            l33t: Vec::new(),
+                            reversed: false,
+                        },
+                    ));
+                }
+            }
+        }
+    }
+    matches
+}
+
+fn reverse_dictionary_match(
+    password: &[char],
+    dictionaries: &RankedDictionaries<'_>,
+) -> Vec<Match> {
+    let reversed: Vec<char> = password.iter().rev().cloned().collect();
+    let n = password.len();
+    dictionary_match(&reversed, dictionaries)
+        .into_iter()
+        .filter(|m| m.len() > 1)
+        .map(|m| {
+            let (i, j) = (n - 1 - m.j, n - 1 - m.i);
+            let pattern = match m.pattern {
+                Pattern::Dictionary {
+                    dictionary,
+                    rank,
+                    matched_word,
+                    ..
+                } => Pattern::Dictionary {
+                    dictionary,
+                    rank,
+                    matched_word,
+                    l33t: Vec::new(),
+                    reversed: true,
+                },
+                pattern => pattern,
+            };
+            Match::new(password, i, j, pattern)
+        })
+        // A palindrome is already found by the normal match
+        .filter(|m| {
+            m.token.to_lowercase().chars().rev().collect::<String>() != m.token.to_lowercase()
+        })
+        .collect()
+}
+
+/// Every way to read the l33t characters of the password, capped so a
+/// password full of digits does not explode
+fn l33t_substitutions(password: &[char]) -> Vec<HashMap<char, char>> {
+    let mut candidates: Vec<(char, Vec<char>)> = Vec::new();
+    for c in password {
+        if candidates.iter().any(|(l33t, _)| l33t == c) {
+            continue;
+        }
+        let letters: Vec<char> = L33T_TABLE
+            .iter()
+            .filter(|(_, subs)| subs.contains(*c))
+            .map(|(letter, _)| *letter)
+            .collect();
+        if !letters.is_empty() {
+            candidates.push((*c, letters));
+        }
+    }
+
+    let mut substitutions = vec![HashMap::new()];
+    for (l33t, letters) in candidates {
+        let mut next = Vec::new();
+        for substitution in &substitutions {
+            for letter in &letters {
+                let mut extended: HashMap<char, char> = substitution.clone();
+                extended.insert(l33t, *letter);
+                next.push(extended);
+            }
+        }
+        next.truncate(32);
+        substitutions = next;
+    }
+    substitutions.retain(|substitution| !substitution.is_empty());
+    substitutions
+}
+
+fn l33t_match(password: &[char], dictionaries: &RankedDictionaries<'_>) -> Vec<Match> {
+    let mut matches: Vec<Match> = Vec::new();
+    for substitution in l33t_substitutions(password) {
+        let subbed: Vec<char> = password
+            .iter()
+            .map(|c| *substitution.get(c).unwrap_or(c))
+            .collect();
+        for m in dictionary_match(&subbed, dictionaries) {
+            let token: Vec<char> = password[m.i..=m.j].to_vec();
+            let mut l33t: Vec<(char, char)> = substitution
+                .iter()
+                .filter(|(from, _)| token.contains(from))
+                .map(|(from, to)| (*from, *to))
+                .collect();
+            if l33t.is_empty() || token.len() <= 1 {
+                continue;
+            }
+            l33t.sort();
+            let m = match m.pattern {
+                Pattern::Dictionary {
+                    dictionary,
+                    rank,
+                    matched_word,
+                    ..
+                } => Match::new(
+                    password,
+                    m.i,
+                    m.j,
+                    Pattern::Dictionary {
+                        dictionary,
+                        rank,
+                        matched_word,
+                        l33t,
+                        reversed: false,
+                    },
+                ),
+                _ => continue,
+            };
+            if !matches.contains(&m) {
+                matches.push(m);
+            }
+        }
+    }
+    matches
+}
+
+/// Position of a key on a keyboard and if it is shifted
+struct Key {
+    x: i32,
+    y: i32,
+    shifted: bool,
+}
+
+impl Keyboard {
+    fn rows(self) -> &'static [(&'static str, usize)] {
+        match self {
+            Keyboard::Qwerty => &QWERTY,
+            Keyboard::Keypad => &KEYPAD,
+        }
+    }
+
+    fn locate(self, c: char) -> Option<Key> {
+        for (y, (row, offset)) in self.rows().iter().enumerate() {
+            for (x, key) in row.split(' ').enumerate() {
+                if let Some(index) = key.chars().position(|k| k == c) {
+                    return Some(Key {
+                        x: (x + offset) as i32,
+                        y: y as i32,
+                        shifted: index == 1,
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    fn exists(self, x: i32, y: i32) -> bool {
+        match self.rows().get(y as usize) {
+            Some((row, offset)) => {
+                x >= *offset as i32 && ((x as usize) - offset) < row.split(' ').count()
+            }
+            None => false,
+        }
+    }
+
+    /// Relative positions of the neighbours, rows of a qwerty keyboard are
+    /// slanted so they have 6 neighbours, a keypad has 8
+    fn directions(self) -> &'static [(i32, i32)] {
+        match self {
+            Keyboard::Qwerty => &[(-1, 0), (0, -1), (1, -1), (1, 0), (0, 1), (-1, 1)],
+            Keyboard::Keypad => &[
+                (-1, 0),
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+                (0, 1),
+                (-1, 1),
+            ],
+        }
+    }
+
+    /// The direction to go from a key to the next one if they are neighbours
+    fn direction(self, from: char, to: char) -> Option<usize> {
+        let from = self.locate(from)?;
+        let to = self.locate(to)?;
+        self.directions()
+            .iter()
+            .position(|(dx, dy)| from.x + dx == to.x && from.y + dy == to.y)
+    }
+
+    pub fn starting_positions(self) -> usize {
+        self.rows()
+            .iter()
+            .map(|(row, _)| row.split(' ').count())
+            .sum()
+    }
+
+    pub fn average_degree(self) -> f64 {
+        let mut degrees = 0;
+        for (y, (row, offset)) in self.rows().iter().enumerate() {
+            for x in 0..row.split(' ').count() {
+                let (x, y) = ((x + offset) as i32, y as i32);
+                degrees += self
+                    .directions()
+                    .iter()
+                    .filter(|(dx, dy)| self.exists(x + dx, y + dy))
+                    .count();
+            }
+        }
+        degrees as f64 / self.starting_positions() as f64
+    }
+}
+
+fn spatial_match(password: &[char], keyboard: Keyboard) -> Vec<Match> {
+    let mut matches = Vec::new();
+    let n = password.len();
+    let mut i = 0;
+    while i + 1 < n {
+        let mut j = i + 1;
+        let mut last_direction = None;
+        let mut turns = 0;
+        let mut shifted = match keyboard.locate(password[i]) {
+            Some(key) if key.shifted => 1,
+            _ => 0,
+        };
+        loop {
+            let direction = if j < n {
+                keyboard.direction(password[j - 1], password[j])
+            } else {
+                None
+            };
+            match direction {
+                Some(direction) => {
+                    if matches!(keyboard.locate(password[j]), Some(key) if key.shifted) {
+                        shifted += 1;
+                    }
+                    if last_direction != Some(direction) {
+                        turns += 1;
+                        last_direction = Some(direction);
+                    }
+                    j += 1;
+                }
+                None => {
+                    if j - i > 2 {
+                        matches.push(Match::new(
+                            password,
+                            i,
+                            j - 1,
+                            Pattern::Spatial {
+                                keyboard,
+                                turns,
+                                shifted,
+                            },
+                        ));
+                    }
+                    i = j;
+                    break;
+                }
+            }
+        }
+    }
+    matches
+}
+
+fn repeat_match(password: &[char], user_inputs: &[&str]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    let n = password.len();
+    let mut i = 0;
+    while i < n {
+        // Longest repeated run starting at i, with the shortest base for it
+        let mut best: Option<(usize, usize)> = None;
+        for base in 1..=(n - i) / 2 {
+            let mut count = 1;
+            while i + (count + 1) * base <= n
+                && password[i..i + base] == password[i + count * base..i + (count + 1) * base]
+            {
+                count += 1;
+            }
+            let better = match best {
+                Some((b, c)) => base * count > b * c,
+                None => true,
+            };
+            if count > 1 && better {
+                best = Some((base, count));
+            }
+        }
+        match best {
+            Some((base, count)) => {
+                let base_chars = &password[i..i + base];
+                let base_guesses =
+                    scoring::most_guessable(base_chars, omnimatch(base_chars, user_inputs)).guesses;
+                let j = i + base * count - 1;
+                matches.push(Match::new(
+                    password,
+                    i,
+                    j,
+                    Pattern::Repeat {
+                        base_token: base_chars.iter().collect(),
+                        base_guesses,
+                        repeat_count: count,
+                    },
+                ));
+                i = j + 1;
+            }
+            None => i += 1,
+        }
+    }
+    matches
+}
+
+fn sequence_match(password: &[char]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    let n = password.len();
+    if n < 2 {
+        return matches;
+    }
+    let mut push = |i: usize, j: usize, delta: i32| {
+        if (j - i > 1 || delta.abs() == 1) && delta != 0 && delta.abs() <= MAX_SEQUENCE_DELTA {
+            matches.push(Match::new(
+                password,
+                i,
+                j,
+                Pattern::Sequence {
+                    ascending: delta > 0,
+                },
+            ));
+        }
+    };
+
+    let mut i = 0;
+    let mut last_delta = None;
+    for k in 1..n {
+        let delta = password[k] as i32 - password[k - 1] as i32;
+        if last_delta.is_none() {
+            last_delta = Some(delta);
+        }
+        if last_delta == Some(delta) {
+            continue;
+        }
+        let j = k - 1;
+        push(i, j, last_delta.unwrap());
+        i = j;
+        last_delta = Some(delta);
+    }
+    push(i, n - 1, last_delta.unwrap());
+    matches
+}
+
+fn year_match(password: &[char]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    let mut i = 0;
+    while i + 4 <= password.len() {
+        let token = &password[i..i + 4];
+        let is_year = token.iter().all(|c| c.is_ascii_digit())
+            && (token[..2] == ['1', '9'] || token[..2] == ['2', '0']);
+        if is_year {
+            matches.push(Match::new(password, i, i + 3, Pattern::Year));
+            i += 4;
+        } else {
+            i += 1;
+        }
+    }
+    matches
+}
+
+fn two_to_four_digit_year(year: i32) -> i32 {
+    if year > 99 {
+        year
+    } else if year > 50 {
+        year + 1900
+    } else {
+        year + 2000
+    }
+}
+
+fn map_ints_to_dm(day: i32, month: i32) -> bool {
+    (1..=31).contains(&day) && (1..=12).contains(&month)
+}
+
+/// Read three numbers as a day, a month and a year in any sensible order
+/// and return the year
+fn map_ints_to_dmy(ints: [i32; 3]) -> Option<i32> {
+    if ints[1] > 31 || ints[1] <= 0 {
+        return None;
+    }
+    let mut over_12 = 0;
+    let mut over_31 = 0;
+    let mut under_1 = 0;
+    for int in &ints {
+        if (99 < *int && *int < DATE_MIN_YEAR) || *int > DATE_MAX_YEAR {
+            return None;
+        }
+        if *int > 31 {
+            over_31 += 1;
+        }
+        if *int > 12 {
+            over_12 += 1;
+        }
+        if *int <= 0 {
+            under_1 += 1;
+        }
+    }
+    if over_31 >= 2 || over_12 == 3 || under_1 >= 2 {
+        return None;
+    }
+
+    let splits = [(ints[2], ints[0], ints[1]), (ints[0], ints[1], ints[2])];
+    for (year, a, b) in &splits {
+        if DATE_MIN_YEAR <= *year && *year <= DATE_MAX_YEAR {
+            return if map_ints_to_dm(*a, *b) || map_ints_to_dm(*b, *a) {
+                Some(*year)
+            } else {
+                None
+            };
+        }
+    }
+    for (year, a, b) in &splits {
+        if map_ints_to_dm(*a, *b) || map_ints_to_dm(*b, *a) {
+            return Some(two_to_four_digit_year(*year));
+        }
+    }
+    None
+}
+
+fn to_int(digits: &[char]) -> i32 {
+    digits
+        .iter()
+        .fold(0, |acc, c| acc * 10 + c.to_digit(10).unwrap_or(0) as i32)
+}
+
+fn date_match(password: &[char]) -> Vec<Match> {
+    let mut matches: Vec<Match> = Vec::new();
+    let n = password.len();
+    let closest = |candidates: Vec<i32>| {
+        candidates
+            .into_iter()
+            .min_by_key(|year| (year - REFERENCE_YEAR).abs())
+    };
+
+    // Dates without separator ex: 13101991
+    for i in 0..n {
+        for j in i + 3..(i + 8).min(n) {
+            let token = &password[i..=j];
+            if !token.iter().all(|c| c.is_ascii_digit()) {
+                continue;
+            }
+            let splits: &[(usize, usize)] = match token.len() {
+                4 => &[(1, 2), (2, 3)],
+                5 => &[(1, 3), (2, 3)],
+                6 => &[(1, 2), (2, 4), (4, 5)],
+                7 => &[(1, 3), (2, 3), (4, 5), (4, 6)],
+                8 => &[(2, 4), (4, 6)],
+                _ => &[],
+            };
+            let years = splits
+                .iter()
+                .filter_map(|(k, l)| {
+                    map_ints_to_dmy([
+                        to_int(&token[..*k]),
+                        to_int(&token[*k..*l]),
+                        to_int(&token[*l..]),
+                    ])
+                })
+                .collect();
+            if let Some(year) = closest(years) {
+                matches.push(Match::new(
+                    password,
+                    i,
+                    j,
+                    Pattern::Date {
+                        year,
+                        separator: false,
+                    },
+                ));
+            }
+        }
+    }
+
+    // Dates with separator ex: 13/10/1991 or 1991-10-13
+    for i in 0..n {
+        for j in i + 5..(i + 10).min(n) {
+            let token = &password[i..=j];
+            let separators: Vec<usize> = token
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| !c.is_ascii_digit())
+                .map(|(index, _)| index)
+                .collect();
+            if separators.len() != 2
+                || token[separators[0]] != token[separators[1]]
+                || !DATE_SEPARATORS.contains(&token[separators[0]])
+            {
+                continue;
+            }
+            let (first, second, third) = (
+                &token[..separators[0]],
+                &token[separators[0] + 1..separators[1]],
+                &token[separators[1] + 1..],
+            );
+            let valid_lengths = (1..=4).contains(&first.len())
+                && (1..=2).contains(&second.len())
+                && (1..=4).contains(&third.len());
+            if !valid_lengths {
+                continue;
+            }
+            if let Some(year) = map_ints_to_dmy([to_int(first), to_int(second), to_int(third)]) {
+                matches.push(Match::new(
+                    password,
+                    i,
+                    j,
+                    Pattern::Date {
+                        year,
+                        separator: true,
+                    },
+                ));
+            }
+        }
+    }
+
+    // A date inside a longer date is not interesting
+    let dates = matches.clone();
+    matches.retain(|m| {
+        !dates
+            .iter()
+            .any(|other| other != m && other.i <= m.i && other.j >= m.j)
+    });
+    matches
+}
@@ -0,0 +1,163 @@
+//! Password strength estimation in the spirit of zxcvbn
+//! The password is split in the cheapest sequence of known patterns
+//! (dictionary words, keyboard walks, repeats, sequences, years and dates)
+//! and the guesses needed by an attacker are estimated from it.
+//! It lives in shared so the client and the server get the same result.
+
+mod data;
+pub mod feedback;
+pub mod matching;
+mod scoring;
+
+use crate::strength::{
+    feedback::Feedback,
+    matching::{omnimatch, Match},
+};
+use serde::{Deserialize, Serialize};
+
+/// Longer passwords are only estimated on their beginning, which is already
+/// way past any sensible threshold
+pub const MAX_LENGTH: usize = 100;
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Estimate {
+    /// Estimated number of guesses needed to find the password
+    pub guesses: f64,
+    pub guesses_log10: f64,
+    /// From 0 (too guessable) to 4 (very unguessable)
+    pub score: u8,
+    pub feedback: Feedback,
+}
+
+/// Estimate the strength of the password, the user inputs are words the
+/// attacker may know about the user like the username or the email
+pub fn estimate(password: &str, user_inputs: &[&str]) -> Estimate {
+    let password: Vec<char> = password.chars().take(MAX_LENGTH).collect();
+    let guessable = scoring::most_guessable(&password, omnimatch(&password, user_inputs));
+    let score = score(guessable.guesses);
+
+    Estimate {
+        guesses: guessable.guesses,
+        guesses_log10: guessable.guesses.log10(),
+        score,
+        feedback: feedback::feedback(score, &guessable.sequence),
+    }
+}
+
+/// The sequence of patterns found in the password
+pub fn sequence(password: &str, user_inputs: &[&str]) -> Vec<Match> {
+    let password: Vec<char> = password.chars().take(MAX_LENGTH).collect();
+    scoring::most_guessable(&password, omnimatch(&password, user_inputs)).sequence
+}
+
+fn score(guesses: f64) -> u8 {
+    // Margin so a sequence of the exact threshold does not get the next score
+    let delta = 5.0;
+    if guesses < 1e3 + delta {
+        0
+    } else if guesses < 1e6 + delta {
+        1
+    } else if guesses < 1e8 + delta {
+        2
+    } else if guesses < 1e10 + delta {
+        3
+    } else {
+        4
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{estimate, sequence};
+    use crate::strength::{
+        feedback::Warning,
+        matching::{Dictionary, Pattern},
+    };
+
+    #[test]
+    fn common_passwords_are_weak() {
+        let password = estimate("password", &[]);
+        assert_eq!(password.score, 0);
+        assert_eq!(password.feedback.warning, Some(Warning::TopTenPassword));
+
+        assert!(estimate("Password123!", &[]).score <= 2);
+        assert!(estimate("p@ssw0rd", &[]).score <= 1);
+        assert!(estimate("Tr0ub4dor&3", &[]).score <= 2);
+    }
+
+    #[test]
+    fn patterns_are_found() {
+        let keyboard = sequence("qwertyuiop[]", &[]);
+        assert!(keyboard.iter().any(|m| matches!(
+            m.pattern,
+            Pattern::Spatial { .. } | Pattern::Dictionary { .. }
+        )));
+
+        let abc = sequence("abcdefgh", &[]);
+        assert!(matches!(
+            abc[0].pattern,
+            Pattern::Sequence { ascending: true }
+        ));
+
+        let date = sequence("13/10/1991", &[]);
+        assert!(matches!(
+            date[0].pattern,
+            Pattern::Date {
+                year: 1991,
+                separator: true
+            }
+        ));
+
+        let repeat = sequence("zzzzzzzz", &[]);
+        assert!(matches!(
+            repeat[0].pattern,
+            Pattern::Repeat {
+                repeat_count: 8,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn user_inputs_are_guessable() {
+        let with_inputs = sequence("avocadotree42", &["avocadotree42@mail.com", "avocadotree"]);
+        assert!(with_inputs.iter().any(|m| matches!(
+            m.pattern,
+            Pattern::Dictionary {
+                dictionary: Dictionary::UserInputs,
+                ..
+            }
+        )));
+    }
+
+    #[test]
+    fn site_words_are_guessable() {
+        let avocado = sequence("Avocado2020!", &[]);
+        assert!(matches!(
+            avocado[0].pattern,
+            Pattern::Dictionary {
+                dictionary: Dictionary::UserInputs,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn words_of_the_lists_are_found() {
+        let words = sequence("walrusmeadowsmith", &[]);
+        assert_eq!(words.len(), 3);
+        assert!(words.iter().all(|m| matches!(
+            m.pattern,
+            Pattern::Dictionary { dictionary, .. } if dictionary != Dictionary::UserInputs
+        )));
+    }
+
+    #[test]
+    fn long_random_passwords_are_strong() {
+        assert_eq!(estimate("walrus lantern meadow", &[]).score, 4);
+        assert_eq!(estimate("g7#Kq!v9Lw@2zP", &[]).score, 4);
+        // Used to overflow the old power calculation
+        let long = "aB3$".repeat(500);
+        assert!(estimate(&long, &[]).guesses.is_finite());
+    }
+}
@@ -0,0 +1,284 @@
+use crate::strength::matching::{Keyboard, Match, Pattern, REFERENCE_YEAR};
+use std::collections::BTreeMap;
+
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+const MIN_GUESSES_BEFORE_GROWING_SEQUENCE: f64 = 10_000.0;
+const MIN_SUBMATCH_GUESSES_SINGLE_CHAR: f64 = 10.0;
+const MIN_SUBMATCH_GUESSES_MULTI_CHAR: f64 = 50.0;
+const MIN_YEAR_SPACE: i32 = 20;
+
+/// The cheapest way to guess the password from the matches
+pub struct Guessable {
+    pub guesses: f64,
+    pub sequence: Vec<Match>,
+}
+
+fn factorial(n: usize) -> f64 {
+    (1..=n).fold(1.0, |acc, k| acc * k as f64)
+}
+
+fn n_ck(n: usize, k: usize) -> f64 {
+    if k > n {
+        return 0.0;
+    }
+    (1..=k).fold(1.0, |acc, d| acc * (n - k + d) as f64 / d as f64)
+}
+
+/// Sum of the ways to choose between 1 and min(a, b) elements in a + b
+fn variations(a: usize, b: usize) -> f64 {
+    (1..=a.min(b)).map(|i| n_ck(a + b, i)).sum()
+}
+
+fn uppercase_variations(token: &str) -> f64 {
+    let upper = token.chars().filter(|c| c.is_uppercase()).count();
+    let lower = token.chars().filter(|c| c.is_lowercase()).count();
+    let chars: Vec<char> = token.chars().collect();
+    let only_first = matches!(chars.first(), Some(c) if c.is_uppercase()) && upper == 1;
+    let only_last = matches!(chars.last(), Some(c) if c.is_uppercase()) && upper == 1;
+
+    if upper == 0 {
+        1.0
+    } else if only_first || only_last || lower == 0 {
+        2.0
+    } else {
+        variations(upper, lower)
+    }
+}
+
+fn l33t_variations(token: &str, l33t: &[(char, char)]) -> f64 {
+    let lower = token.to_lowercase();
+    l33t.iter().fold(1.0, |acc, (subbed, unsubbed)| {
+        let subbed_count = lower.chars().filter(|c| c == subbed).count();
+        let unsubbed_count = lower.chars().filter(|c| c == unsubbed).count();
+        if subbed_count == 0 || unsubbed_count == 0 {
+            acc * 2.0
+        } else {
+            acc * variations(subbed_count, unsubbed_count)
+        }
+    })
+}
+
+fn spatial_guesses(keyboard: Keyboard, length: usize, turns: usize, shifted: usize) -> f64 {
+    let starts = keyboard.starting_positions() as f64;
+    let degree = keyboard.average_degree();
+    let mut guesses = 0.0;
+    for i in 2..=length {
+        for j in 1..=turns.min(i - 1) {
+            guesses += n_ck(i - 1, j - 1) * starts * degree.powi(j as i32);
+        }
+    }
+    if shifted > 0 {
+        let unshifted = length - shifted;
+        if unshifted == 0 {
+            guesses *= 2.0;
+        } else {
+            guesses *= variations(shifted, unshifted);
+        }
+    }
+    guesses
+}
+
+fn sequence_guesses(token: &str, ascending: bool) -> f64 {
+    let first = token.chars().next().unwrap_or('a');
+    let mut base = if ['a', 'A', 'z', 'Z', '0', '1', '9'].contains(&first) {
+        4.0
+    } else if first.is_ascii_digit() {
+        10.0
+    } else {
+        26.0
+    };
+    if !ascending {
+        base *= 2.0;
+    }
+    base * token.chars().count() as f64
+}
+
+fn year_space(year: i32) -> f64 {
+    (year - REFERENCE_YEAR).abs().max(MIN_YEAR_SPACE) as f64
+}
+
+/// Guesses needed to find this part of the password
+pub fn estimate_guesses(m: &Match, password_length: usize) -> f64 {
+    let min_guesses = if m.len() < password_length {
+        if m.len() == 1 {
+            MIN_SUBMATCH_GUESSES_SINGLE_CHAR
+        } else {
+            MIN_SUBMATCH_GUESSES_MULTI_CHAR
+        }
+    } else {
+        1.0
+    };
+
+    let guesses = match &m.pattern {
+        Pattern::Dictionary {
+            rank,
+            l33t,
+            reversed,
+            ..
+        } => {
+            let reversed_variations = if *reversed { 2.0 } else { 1.0 };
+            *rank as f64
+                * uppercase_variations(&m.token)
+                * l33t_variations(&m.token, l33t)
+                * reversed_variations
+        }
+        Pattern::Spatial {
+            keyboard,
+            turns,
+            shifted,
+        } => spatial_guesses(*keyboard, m.len(), *turns, *shifted),
+        Pattern::Repeat {
+            base_guesses,
+            repeat_count,
+            ..
+        } => base_guesses * *repeat_count as f64,
+        Pattern::Sequence { ascending } => sequence_guesses(&m.token, *ascending),
+        Pattern::Year => {
+            let year: i32 = m.token.parse().unwrap_or(REFERENCE_YEAR);
+            year_space(year)
+        }
+        Pattern::Date { year, separator } => {
+            let separator_variations = if *separator { 4.0 } else { 1.0 };
+            year_space(*year) * 365.0 * separator_variations
+        }
+        Pattern::Bruteforce => {
+            let min_bruteforce = if m.len() == 1 {
+                MIN_SUBMATCH_GUESSES_SINGLE_CHAR + 1.0
+            } else {
+                MIN_SUBMATCH_GUESSES_MULTI_CHAR + 1.0
+            };
+            BRUTEFORCE_CARDINALITY
+                .powi(m.len() as i32)
+                .max(min_bruteforce)
+        }
+    };
+    guesses.max(min_guesses)
+}
+
+/// Best sequences found so far ending at each position, indexed by their
+/// number of matches
+#[derive(Default)]
+struct Optimal {
+    matches: Vec<BTreeMap<usize, Match>>,
+    products: Vec<BTreeMap<usize, f64>>,
+    guesses: Vec<BTreeMap<usize, f64>>,
+}
+
+impl Optimal {
+    fn new(length: usize) -> Self {
+        Optimal {
+            matches: vec![BTreeMap::new(); length],
+            products: vec![BTreeMap::new(); length],
+            guesses: vec![BTreeMap::new(); length],
+        }
+    }
+
+    fn update(&mut self, m: Match, length: usize, password_length: usize) {
+        let k = m.j;
+        let mut product = estimate_guesses(&m, password_length);
+        if length > 1 {
+            product *= self.products[m.i - 1][&(length - 1)];
+        }
+        let guesses = factorial(length) * product
+            + MIN_GUESSES_BEFORE_GROWING_SEQUENCE.powi(length as i32 - 1);
+
+        // A shorter or equal sequence with less guesses wins
+        let beaten =
+            self.guesses
+                .get(k)
+                .into_iter()
+                .flatten()
+                .any(|(other_length, other_guesses)| {
+                    *other_length <= length && *other_guesses <= guesses
+                });
+        if beaten {
+            return;
+        }
+        self.guesses[k].insert(length, guesses);
+        self.products[k].insert(length, product);
+        self.matches[k].insert(length, m);
+    }
+
+    fn bruteforce_update(&mut self, password: &[char], k: usize) {
+        let n = password.len();
+        self.update(bruteforce(password, 0, k), 1, n);
+        for i in 1..=k {
+            let previous: Vec<(usize, bool)> = self.matches[i - 1]
+                .iter()
+                .map(|(length, m)| (*length, m.pattern == Pattern::Bruteforce))
+                .collect();
+            for (length, is_bruteforce) in previous {
+                // Two bruteforce matches next to each other are one match
+                if !is_bruteforce {
+                    self.update(bruteforce(password, i, k), length + 1, n);
+                }
+            }
+        }
+    }
+}
+
+fn bruteforce(password: &[char], i: usize, j: usize) -> Match {
+    Match {
+        i,
+        j,
+        token: password[i..=j].iter().collect(),
+        pattern: Pattern::Bruteforce,
+    }
+}
+
+/// Find the sequence of non overlapping matches covering the password which
+/// needs the least guesses, gaps are filled with bruteforce
+pub fn most_guessable(password: &[char], matches: Vec<Match>) -> Guessable {
+    let n = password.len();
+    if n == 0 {
+        return Guessable {
+            guesses: 1.0,
+            sequence: Vec::new(),
+        };
+    }
+
+    let mut matches_by_end: Vec<Vec<Match>> = vec![Vec::new(); n];
+    for m in matches {
+        matches_by_end[m.j].push(m);
+    }
+    for list in &mut matches_by_end {
+        list.sort_by_key(|m| m.i);
+    }
+
+    let mut optimal = Optimal::new(n);
+    for (k, ending) in matches_by_end.into_iter().enumerate() {
+        for m in ending {
+            if m.i > 0 {
+                let lengths: Vec<usize> = optimal.matches[m.i - 1].keys().cloned().collect();
+                for length in lengths {
+                    optimal.update(m.clone(), length + 1, n);
+                }
+            } else {
+                optimal.update(m, 1, n);
+            }
+        }
+        optimal.bruteforce_update(password, k);
+    }
+
+    // Walk back from the end with the best number of matches
+    let (mut length, guesses) =
+        optimal.guesses[n - 1]
+            .iter()
+            .fold((0, f64::INFINITY), |best, (length, guesses)| {
+                if *guesses < best.1 {
+                    (*length, *guesses)
+                } else {
+                    best
+                }
+            });
+    let mut sequence = Vec::new();
+    let mut k = n as isize - 1;
+    while k >= 0 && length > 0 {
+        let m = optimal.matches[k as usize][&length].clone();
+        k = m.i as isize - 1;
+        length -= 1;
+        sequence.insert(0, m);
+    }
+
+    Guessable { guesses, sequence }
+}
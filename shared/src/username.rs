@@ -0,0 +1,54 @@
+//! Usernames are shown as typed but compared in a canonical form, so `Ｊｏｈｎ`,
+//! `JOHN` and `john` are the same user
+
+use caseless::default_case_fold_str;
+use unicode_normalization::UnicodeNormalization;
+use unicode_security::{confusable_detection::skeleton, MixedScript};
+
+/// Form used to check uniqueness and to look a user up, NFKC and case folding
+/// applied until nothing changes anymore (NFKC_Casefold)
+pub fn canonical(username: &str) -> String {
+    let mut current: String = username.trim().nfkc().collect();
+    loop {
+        let next: String = default_case_fold_str(&current).nfkc().collect();
+        if next == current {
+            return current;
+        }
+        current = next;
+    }
+}
+
+/// Shape of the username from UTS #39, two usernames with the same skeleton
+/// look alike, ex: `paypal` and `pаypal` with a cyrillic `а`
+pub fn confusable_skeleton(username: &str) -> String {
+    skeleton(&canonical(username)).collect()
+}
+
+/// Mixing scripts in a name is mostly done to spoof another one
+pub fn is_single_script(username: &str) -> bool {
+    canonical(username).is_single_script()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{canonical, confusable_skeleton, is_single_script};
+
+    #[test]
+    fn canonical_form() {
+        assert_eq!(canonical("Ｊｏｈｎ"), "john");
+        assert_eq!(canonical("JOHN"), "john");
+        assert_eq!(canonical("Straße"), "strasse");
+        assert_eq!(canonical("ﬁlou"), "filou");
+    }
+
+    #[test]
+    fn confusables() {
+        assert_eq!(
+            confusable_skeleton("paypal"),
+            confusable_skeleton("p\u{0430}ypal")
+        );
+        assert!(!is_single_script("p\u{0430}ypal"));
+        assert!(is_single_script("élodie"));
+        assert!(is_single_script("дмитрий"));
+    }
+}
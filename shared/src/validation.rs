@@ -0,0 +1,365 @@
+//! Field validation shared by the server handlers and the client forms
+//! Every invalid field is reported at once instead of stopping at the first
+
+use crate::{
+    i18n::Message,
+    models::{
+        auth::LoginCredentials,
+        chat::{NewConversation, NewMessage, NewRoom},
+        user::User,
+    },
+    username::{canonical, is_single_script},
+};
+use serde::{Deserialize, Serialize};
+
+pub const USERNAME_MIN_LENGTH: usize = 5;
+pub const USERNAME_MAX_LENGTH: usize = 25;
+pub const NAME_MAX_LENGTH: usize = 50;
+/// Longest address accepted by SMTP, RFC 5321
+pub const EMAIL_MAX_LENGTH: usize = 254;
+pub const ROOM_NAME_MAX_LENGTH: usize = 50;
+pub const TOPIC_MAX_LENGTH: usize = 200;
+pub const MESSAGE_MAX_LENGTH: usize = 4000;
+/// Participants of a direct conversation, counting the one opening it
+pub const CONVERSATION_MAX_PARTICIPANTS: usize = 8;
+const EMAIL_LOCAL_MAX_LENGTH: usize = 64;
+const DOMAIN_LABEL_MAX_LENGTH: usize = 63;
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    FirstName,
+    LastName,
+    Username,
+    Email,
+    Password,
+    /// Username or email used to login
+    Target,
+    RoomName,
+    Topic,
+    /// Text of a chat message
+    Body,
+    /// Usernames of the participants of a direct conversation
+    Participants,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct FieldError {
+    pub field: Field,
+    pub message: Message,
+}
+
+/// Errors of a single field
+pub fn errors_for(errors: &[FieldError], field: Field) -> Vec<&FieldError> {
+    errors.iter().filter(|error| error.field == field).collect()
+}
+
+pub fn validate_user(user: &User) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    check(
+        &mut errors,
+        Field::FirstName,
+        validate_name(&user.first_name),
+    );
+    check(&mut errors, Field::LastName, validate_name(&user.last_name));
+    check(
+        &mut errors,
+        Field::Username,
+        validate_username(user.credentials.username()),
+    );
+    check(
+        &mut errors,
+        Field::Email,
+        validate_email(user.credentials.email()),
+    );
+    check(
+        &mut errors,
+        Field::Password,
+        required(user.credentials.password()),
+    );
+    errors
+}
+
+pub fn validate_login(credentials: &LoginCredentials) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    check(
+        &mut errors,
+        Field::Target,
+        required(credentials.target())
+            .and_then(|_| max_length(credentials.target(), EMAIL_MAX_LENGTH)),
+    );
+    check(
+        &mut errors,
+        Field::Password,
+        required(credentials.password()),
+    );
+    errors
+}
+
+pub fn validate_room(room: &NewRoom) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    check(
+        &mut errors,
+        Field::RoomName,
+        required(&room.name).and_then(|_| max_length(&room.name, ROOM_NAME_MAX_LENGTH)),
+    );
+    if let Some(topic) = &room.topic {
+        check(
+            &mut errors,
+            Field::Topic,
+            max_length(topic, TOPIC_MAX_LENGTH),
+        );
+    }
+    errors
+}
+
+pub fn validate_message(message: &NewMessage) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    check(
+        &mut errors,
+        Field::Body,
+        required(&message.body).and_then(|_| max_length(&message.body, MESSAGE_MAX_LENGTH)),
+    );
+    errors
+}
+
+pub fn validate_conversation(conversation: &NewConversation) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    let mut others: Vec<String> = conversation
+        .participants
+        .iter()
+        .filter(|username| !username.trim().is_empty())
+        .map(|username| canonical(username))
+        .collect();
+    others.sort();
+    others.dedup();
+    let result = if others.is_empty() {
+        Err(Message::new("validation.required"))
+    } else if others.len() >= CONVERSATION_MAX_PARTICIPANTS {
+        Err(Message::new("validation.too_many_participants")
+            .with("max", CONVERSATION_MAX_PARTICIPANTS))
+    } else {
+        Ok(())
+    };
+    check(&mut errors, Field::Participants, result);
+    errors
+}
+
+fn check(errors: &mut Vec<FieldError>, field: Field, result: Result<(), Message>) {
+    if let Err(message) = result {
+        errors.push(FieldError { field, message });
+    }
+}
+
+fn required(value: &str) -> Result<(), Message> {
+    if value.trim().is_empty() {
+        Err(Message::new("validation.required"))
+    } else {
+        Ok(())
+    }
+}
+
+fn max_length(value: &str, max: usize) -> Result<(), Message> {
+    if value.chars().count() > max {
+        Err(Message::new("validation.too_long").with("max", max))
+    } else {
+        Ok(())
+    }
+}
+
+pub fn validate_name(name: &str) -> Result<(), Message> {
+    required(name)?;
+    max_length(name, NAME_MAX_LENGTH)
+}
+
+/// Letters of any single script, digits, `.`, `_` and `-`, starting with a
+/// letter or a digit, checked on the canonical form
+pub fn validate_username(username: &str) -> Result<(), Message> {
+    required(username)?;
+    let username = canonical(username);
+    if username.chars().count() < USERNAME_MIN_LENGTH {
+        return Err(Message::new("validation.too_short").with("min", USERNAME_MIN_LENGTH));
+    }
+    max_length(&username, USERNAME_MAX_LENGTH)?;
+    let starts_well = matches!(username.chars().next(), Some(c) if c.is_alphanumeric());
+    let allowed = username
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '.' || c == '_' || c == '-');
+    if !starts_well || !allowed {
+        Err(Message::new("validation.username_charset"))
+    } else if !is_single_script(&username) {
+        Err(Message::new("validation.username_mixed_scripts"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Syntax of an addr-spec from RFC 5322, without comments and folding white
+/// spaces, ex: `"john doe"@example.com` or `jane+tag@[192.168.0.1]`
+pub fn validate_email(email: &str) -> Result<(), Message> {
+    required(email)?;
+    max_length(email, EMAIL_MAX_LENGTH)?;
+    let valid = match email.rfind('@') {
+        Some(at) => {
+            let (local, domain) = (&email[..at], &email[at + 1..]);
+            local.len() <= EMAIL_LOCAL_MAX_LENGTH
+                && (is_dot_atom(local) || is_quoted_string(local))
+                && (is_domain_name(domain) || is_domain_literal(domain))
+        }
+        None => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(Message::new("validation.email_syntax"))
+    }
+}
+
+fn is_atext(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~".contains(c)
+}
+
+fn is_dot_atom(text: &str) -> bool {
+    !text.is_empty()
+        && text
+            .split('.')
+            .all(|atom| !atom.is_empty() && atom.chars().all(is_atext))
+}
+
+fn is_quoted_string(text: &str) -> bool {
+    if text.len() < 2 || !text.starts_with('"') || !text.ends_with('"') {
+        return false;
+    }
+    let mut chars = text[1..text.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped) if escaped == ' ' || escaped.is_ascii_graphic() => {}
+                _ => return false,
+            },
+            '"' => return false,
+            c if c == ' ' || c.is_ascii_graphic() => {}
+            _ => return false,
+        }
+    }
+    true
+}
+
+fn is_domain_name(domain: &str) -> bool {
+    !domain.is_empty()
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= DOMAIN_LABEL_MAX_LENGTH
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+fn is_domain_literal(domain: &str) -> bool {
+    domain.len() > 2
+        && domain.starts_with('[')
+        && domain.ends_with(']')
+        && domain[1..domain.len() - 1]
+            .chars()
+            .all(|c| c.is_ascii_graphic() && c != '[' && c != ']' && c != '\\')
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        validate_conversation, validate_email, validate_message, validate_room, validate_user,
+        validate_username, Field, CONVERSATION_MAX_PARTICIPANTS, MESSAGE_MAX_LENGTH,
+    };
+    use crate::models::{
+        chat::{NewConversation, NewMessage, NewRoom},
+        user::User,
+    };
+
+    #[test]
+    fn email_syntax() {
+        for valid in &[
+            "jane@example.com",
+            "jane.doe+tag@sub.example.co.uk",
+            "\"john doe\"@example.com",
+            "admin@localhost",
+            "user@[192.168.0.1]",
+        ] {
+            assert!(validate_email(valid).is_ok(), "{}", valid);
+        }
+        for invalid in &[
+            "plainaddress",
+            "@example.com",
+            "jane.@example.com",
+            "jane..doe@example.com",
+            "jane@-example.com",
+            "jane@example..com",
+            "ja ne@example.com",
+        ] {
+            assert!(validate_email(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn username_charset() {
+        assert!(validate_username("avocado_42").is_ok());
+        assert!(validate_username("_avocado").is_err());
+        assert!(validate_username("avo cado").is_err());
+        assert!(validate_username("avo").is_err());
+        assert!(validate_username("Élodie_42").is_ok());
+        assert!(validate_username("p\u{0430}ypal").is_err());
+    }
+
+    #[test]
+    fn report_every_field() {
+        let errors = validate_user(&User::default());
+        let fields: Vec<Field> = errors.iter().map(|error| error.field).collect();
+        assert_eq!(
+            fields,
+            vec![
+                Field::FirstName,
+                Field::LastName,
+                Field::Username,
+                Field::Email,
+                Field::Password
+            ]
+        );
+    }
+
+    #[test]
+    fn chat_fields() {
+        let room = NewRoom {
+            name: "  ".to_string(),
+            topic: Some("avocado".repeat(50)),
+        };
+        let fields: Vec<Field> = validate_room(&room).iter().map(|e| e.field).collect();
+        assert_eq!(fields, vec![Field::RoomName, Field::Topic]);
+
+        let message = NewMessage {
+            body: "a".repeat(MESSAGE_MAX_LENGTH + 1),
+            parent: None,
+        };
+        assert_eq!(validate_message(&message).len(), 1);
+        let message = NewMessage {
+            body: "Hello avocados".to_string(),
+            parent: Some("abc".to_string()),
+        };
+        assert!(validate_message(&message).is_empty());
+
+        let conversation = NewConversation {
+            participants: vec!["  ".to_string()],
+        };
+        assert_eq!(validate_conversation(&conversation).len(), 1);
+        let conversation = NewConversation {
+            participants: (0..CONVERSATION_MAX_PARTICIPANTS)
+                .map(|i| format!("avocado{}", i))
+                .collect(),
+        };
+        assert_eq!(validate_conversation(&conversation).len(), 1);
+        // The same user written twice is one participant
+        let conversation = NewConversation {
+            participants: vec!["Avocado".to_string(), "AVOCADO".to_string()],
+        };
+        assert!(validate_conversation(&conversation).is_empty());
+    }
+}